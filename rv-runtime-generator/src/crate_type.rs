@@ -27,11 +27,15 @@ impl CrateType {
     }
 }
 
-pub fn create_root_rs_filewriter(dirpath: &Path, crate_type: CrateType) -> FileWriter {
+pub fn create_root_rs_filewriter(
+    dirpath: &Path,
+    crate_type: CrateType,
+    banner: &str,
+) -> FileWriter {
     let filepath = dirpath.join(crate_type.filename());
     let fw = FileWriter::new(filepath, BlockDelimiter::Parens);
 
-    fw.add_line(&format!("// {}", auto_generate_banner()));
+    add_banner(&fw, "//", banner);
     if crate_type.is_library() {
         // In case of module, no_std is expected to be added to the real crate root
         fw.add_line("#![no_std]");
@@ -85,6 +85,10 @@ impl FileWriter {
         self.add_to_contents("", 0);
     }
 
+    pub fn contents(&self) -> String {
+        self.contents.borrow().clone()
+    }
+
     pub fn write(&self) -> std::io::Result<()> {
         let mut file = File::create(&self.filepath)?;
         file.write_all(self.contents.borrow().as_bytes())
@@ -101,3 +105,10 @@ impl FileWriter {
 pub fn auto_generate_banner() -> String {
     format!("DO NOT EDIT. AUTOGENERATED BY '{}'", env!("CARGO_PKG_NAME"))
 }
+
+// Banners can span multiple lines, each of which is emitted as a separate comment line
+pub fn add_banner(fw: &FileWriter, comment_prefix: &str, banner: &str) {
+    for line in banner.lines() {
+        fw.add_line(&format!("{comment_prefix} {line}"));
+    }
+}
@@ -6,6 +6,8 @@ use lazy_static::lazy_static;
 use std::collections::HashMap;
 
 pub const START_SYMBOL: &str = "_start";
+pub const PARK_HART_SYMBOL: &str = "_park_hart";
+pub const TRAP_VECTOR_TABLE_SYMBOL: &str = "trap_vector_table";
 
 #[derive(Debug, Hash, Eq, PartialEq, Copy, Clone)]
 pub enum GeneratedFunc {
@@ -18,6 +20,24 @@ pub enum GeneratedFunc {
     TpBlock,
     SwitchTo,
     RestoreTrapFrame,
+    PerHartAddr,
+    CurrentContext,
+    SetCurrentContext,
+    WaitForValue,
+    ReadTp,
+    HartStarted,
+    MaskInterrupt,
+    UnmaskInterrupt,
+    DelayCycles,
+    SecondaryArg,
+    FenceTso,
+    ClearSoftwareInterrupt,
+    TrapCount,
+    ResumeFrame,
+    ReleaseSecondaries,
+    SetTimer,
+    BootLockAcquire,
+    BootLockRelease,
 }
 
 pub struct GeneratedFuncMap {
@@ -46,6 +66,27 @@ lazy_static! {
             (GeneratedFunc::TpBlock, "my_tpblock_mut"),
             (GeneratedFunc::SwitchTo, "switch_to"),
             (GeneratedFunc::RestoreTrapFrame, "get_restore_tf_label"),
+            (GeneratedFunc::PerHartAddr, "per_hart_addr"),
+            (GeneratedFunc::CurrentContext, "current_context"),
+            (GeneratedFunc::SetCurrentContext, "set_current_context"),
+            (GeneratedFunc::WaitForValue, "wait_for_value"),
+            (GeneratedFunc::ReadTp, "read_tp"),
+            (GeneratedFunc::HartStarted, "hart_started"),
+            (GeneratedFunc::MaskInterrupt, "mask_interrupt"),
+            (GeneratedFunc::UnmaskInterrupt, "unmask_interrupt"),
+            (GeneratedFunc::DelayCycles, "delay_cycles"),
+            (GeneratedFunc::SecondaryArg, "secondary_arg"),
+            (GeneratedFunc::FenceTso, "fence_tso"),
+            (
+                GeneratedFunc::ClearSoftwareInterrupt,
+                "clear_software_interrupt"
+            ),
+            (GeneratedFunc::TrapCount, "trap_count"),
+            (GeneratedFunc::ResumeFrame, "resume_frame"),
+            (GeneratedFunc::ReleaseSecondaries, "release_secondaries"),
+            (GeneratedFunc::SetTimer, "set_timer"),
+            (GeneratedFunc::BootLockAcquire, "boot_lock_acquire"),
+            (GeneratedFunc::BootLockRelease, "boot_lock_release"),
         ]
         .iter()
         .copied()
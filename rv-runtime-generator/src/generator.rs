@@ -13,6 +13,16 @@ pub struct RuntimeConfig<'a> {
     pub rt_config: RtConfig,
 }
 
+impl<'a> RuntimeConfig<'a> {
+    // Use the builder pattern to replace the banner placed at the top of every generated file
+    // (assembly, Rust and linker script). Defaults to `auto_generate_banner()`.
+    pub fn with_banner(mut self, banner: &str) -> Self {
+        self.linker_config = self.linker_config.with_banner(banner);
+        self.rt_config = self.rt_config.with_banner(banner);
+        self
+    }
+}
+
 pub fn write_rv_runtime_files_as_module<'a>(
     runtime_config: &'a RuntimeConfig<'a>,
 ) -> std::io::Result<()> {
@@ -3,6 +3,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::cell::RefCell;
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 
 use crate::crate_type::*;
@@ -68,6 +69,13 @@ impl MemoryAttribs {
     }
 }
 
+impl MemoryAttribs {
+    // R, W and X bits of a pmpcfg entry
+    pub(crate) fn pmp_permission_bits(&self) -> usize {
+        (self.read as usize) | ((self.write as usize) << 1) | ((self.execute as usize) << 2)
+    }
+}
+
 impl std::fmt::Display for MemoryAttribs {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let mut print_str = String::new();
@@ -99,11 +107,14 @@ fn is_aligned(val: usize, alignment: usize) -> bool {
     (val % alignment) == 0
 }
 
-fn is_power_of_2(val: usize) -> bool {
-    (val & (val - 1)) == 0
+// Zero is not a power of 2
+pub(crate) fn is_power_of_2(val: usize) -> bool {
+    val != 0 && (val & (val - 1)) == 0
 }
 
+// NAPOT lengths must be a nonzero power of 2, with the base aligned to the length
 fn check_napot(name: &str, base: usize, length: usize) {
+    assert!(length != 0, "NAPOT memory {name:#} has a zero length");
     assert!(
         is_power_of_2(length),
         "Memory {name:#} has a length {length:#x} which is not a power-of-2"
@@ -114,7 +125,7 @@ fn check_napot(name: &str, base: usize, length: usize) {
     );
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct SubRegion {
     name: String,
     length: usize,
@@ -131,7 +142,7 @@ impl SubRegion {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct MemoryRegion {
     name: String,
     base: usize,
@@ -139,6 +150,7 @@ pub struct MemoryRegion {
     napot: bool,
     attribs: MemoryAttribs,
     sub_regions: Vec<SubRegion>,
+    load_region: Option<String>, // Memory the contents are loaded from, if not this region
 }
 
 impl MemoryRegion {
@@ -157,12 +169,79 @@ impl MemoryRegion {
             napot,
             attribs,
             sub_regions,
+            load_region: None,
         }
     }
 
+    // Use the builder pattern to load the contents of the sections placed in this region (and its
+    // sub-regions) from the memory `load_region` instead, e.g. initialized data that runs from RAM
+    // but is stored in flash. The contents are copied to RAM with `copy_data_to_ram()`, except for
+    // the Text section which has to be copied before any code runs from it. The reset path uses
+    // the runtime variables in .data before any Rust code runs, so the Data section can't be
+    // placed in such a region.
+    pub fn with_load_region(mut self, load_region: &str) -> Self {
+        self.load_region = Some(load_region.to_string());
+        self
+    }
+
     fn end(&self) -> usize {
         self.base + self.length
     }
+
+    fn overlaps(&self, base: usize, length: usize) -> bool {
+        self.base < base + length && base < self.end()
+    }
+}
+
+// A physical memory map shared by multiple components (e.g. an M-mode firmware and the S-mode
+// payload it boots), each of which is linked with its own linker script. The map is declared once
+// and each component is handed the regions it owns, so that the generated linker scripts can't
+// overlap.
+#[derive(Debug)]
+pub struct SharedMemoryMap {
+    regions: Vec<MemoryRegion>,
+}
+
+impl SharedMemoryMap {
+    pub fn new(regions: Vec<MemoryRegion>) -> Self {
+        for (idx, region) in regions.iter().enumerate() {
+            for other in &regions[idx + 1..] {
+                assert!(
+                    !region.overlaps(other.base, other.length),
+                    "Memory regions {:?} and {:?} in the shared memory map overlap",
+                    region.name,
+                    other.name
+                );
+            }
+        }
+
+        Self { regions }
+    }
+
+    fn select(&self, region_names: &[&str], owned: bool) -> Vec<MemoryRegion> {
+        for name in region_names {
+            assert!(
+                self.regions.iter().any(|r| r.name == *name),
+                "Memory region {name:?} is not part of the shared memory map"
+            );
+        }
+
+        self.regions
+            .iter()
+            .filter(|r| region_names.contains(&r.name.as_str()) == owned)
+            .cloned()
+            .collect()
+    }
+
+    // Regions of the map owned by a component
+    pub fn carve(&self, region_names: &[&str]) -> Vec<MemoryRegion> {
+        self.select(region_names, true)
+    }
+
+    // Regions of the map owned by every other component
+    pub fn exclude(&self, region_names: &[&str]) -> Vec<MemoryRegion> {
+        self.select(region_names, false)
+    }
 }
 
 #[derive(Debug)]
@@ -171,24 +250,35 @@ pub struct Memory<'a> {
     base: usize,
     length: usize,
     attribs: MemoryAttribs,
+    load_region: Option<String>,
     sections: RefCell<Vec<&'a Section>>,
 }
 
 impl<'a> Memory<'a> {
-    fn new(name: &str, base: usize, length: usize, attribs: MemoryAttribs) -> Self {
+    fn new(
+        name: &str,
+        base: usize,
+        length: usize,
+        attribs: MemoryAttribs,
+        load_region: Option<String>,
+    ) -> Self {
         Self {
             name: name.to_string(),
             base,
             length,
             attribs,
+            load_region,
             sections: RefCell::new(Vec::new()),
         }
     }
 
+    // Non-allocated (COPY/INFO) sections don't occupy the memory, so they are skipped when
+    // computing its bounds
     fn first_section_start_symbol(&self) -> String {
         self.sections
             .borrow()
-            .first()
+            .iter()
+            .find(|section| section.is_allocated())
             .unwrap()
             .ty
             .section_entry_start_symbol()
@@ -197,14 +287,19 @@ impl<'a> Memory<'a> {
     fn last_section_end_symbol(&self) -> String {
         self.sections
             .borrow()
-            .last()
+            .iter()
+            .rfind(|section| section.is_allocated())
             .unwrap()
             .ty
             .section_entry_end_symbol()
     }
 
     fn is_empty(&self) -> bool {
-        self.sections.borrow().is_empty()
+        !self
+            .sections
+            .borrow()
+            .iter()
+            .any(|section| section.is_allocated())
     }
 
     fn from_memory_region(region: &MemoryRegion) -> Vec<Self> {
@@ -219,6 +314,7 @@ impl<'a> Memory<'a> {
             region.base,
             region.length,
             region.attribs,
+            region.load_region.clone(),
         ));
 
         let mut base = region.base;
@@ -239,6 +335,7 @@ impl<'a> Memory<'a> {
                 base,
                 sub_region.length,
                 region.attribs,
+                region.load_region.clone(),
             ));
 
             base += sub_region.length;
@@ -289,8 +386,15 @@ pub enum SectionType {
     Rodata,
     Bss,
     Heap,
+    // Additional heap with its own name and size, for consumers that need more than one heap
+    // region (e.g. a DMA-coherent heap next to the regular one).
+    NamedHeap(String, usize),
     Stack,
+    Dtb,
     Custom(String, usize),
+    // Read-only .note.<name> section holding the given bytes, e.g. toolchain or provenance metadata
+    // for analysis tools
+    Note(String, Vec<u8>),
 }
 
 pub fn program_start_symbol() -> String {
@@ -305,23 +409,97 @@ pub fn stack_top_symbol() -> String {
     "_stack_top".to_string()
 }
 
+// Per-hart stack size, exposed for external tooling such as debuggers
+pub fn stack_size_symbol() -> String {
+    "__stack_size".to_string()
+}
+
+// Start of the guard region below the stack of hart `boot_id`, with page stack guards
+pub fn stack_guard_symbol(boot_id: usize) -> String {
+    format!("_stack_guard_{boot_id}")
+}
+
+pub fn abort_stack_start_symbol() -> String {
+    "_sabort_stack".to_string()
+}
+
+pub fn abort_stack_end_symbol() -> String {
+    "_eabort_stack".to_string()
+}
+
+pub fn abort_stack_top_symbol() -> String {
+    "_abort_stack_top".to_string()
+}
+
+pub fn interrupt_stack_start_symbol() -> String {
+    "_sinterrupt_stack".to_string()
+}
+
+pub fn interrupt_stack_end_symbol() -> String {
+    "_einterrupt_stack".to_string()
+}
+
+pub fn interrupt_stack_top_symbol() -> String {
+    "_interrupt_stack_top".to_string()
+}
+
+// Start of the guard region below the interrupt stack of hart `boot_id`, with page stack guards
+pub fn interrupt_stack_guard_symbol(boot_id: usize) -> String {
+    format!("_interrupt_stack_guard_{boot_id}")
+}
+
 pub fn global_pointer_symbol() -> String {
     "_global_pointer".to_string()
 }
 
+// Default input section of the reset entry, see HartConfig::with_reset_section()
 pub fn reset_section() -> String {
     ".text.entry".to_string()
 }
 
+// Input section of the stub copying the reset entry, see HartConfig::with_reset_entry_load_region()
+pub fn reset_entry_copy_section() -> String {
+    ".text.entry_copy".to_string()
+}
+
 pub fn custom_reset_section() -> String {
     ".text.custom_reset_entry".to_string()
 }
 
+pub fn trap_code_section() -> String {
+    ".text.trap".to_string()
+}
+
+pub fn reset_entry_start_symbol() -> String {
+    "_stext_entry".to_string()
+}
+
+pub fn reset_entry_end_symbol() -> String {
+    "_etext_entry".to_string()
+}
+
+pub fn reset_entry_load_symbol() -> String {
+    "_sitext_entry".to_string()
+}
+
+pub fn trap_code_start_symbol() -> String {
+    "_strap_code".to_string()
+}
+
+pub fn trap_code_end_symbol() -> String {
+    "_etrap_code".to_string()
+}
+
 pub fn text_default_section() -> String {
     let sections = SectionType::Text.default_sections();
     sections[0].to_string()
 }
 
+pub fn rodata_default_section() -> String {
+    let sections = SectionType::Rodata.default_sections();
+    sections[0].to_string()
+}
+
 pub fn data_default_section() -> String {
     let sections = SectionType::Data.default_sections();
     sections[0].to_string()
@@ -336,7 +514,8 @@ impl SectionType {
             Self::Bss => "bss",
             Self::Heap => "heap",
             Self::Stack => "stack",
-            Self::Custom(name, _) => name,
+            Self::Dtb => "dtb",
+            Self::NamedHeap(name, _) | Self::Custom(name, _) | Self::Note(name, _) => name,
         }
     }
 
@@ -346,12 +525,27 @@ impl SectionType {
             Self::Data => vec![".data", ".sdata"],
             Self::Rodata => vec![".rodata", ".srodata"],
             Self::Bss => vec![".bss", ".sbss"],
-            Self::Heap | Self::Stack | Self::Custom(_, _) => Vec::new(),
+            Self::Dtb => vec![".dtb"],
+            Self::Heap
+            | Self::NamedHeap(_, _)
+            | Self::Stack
+            | Self::Custom(_, _)
+            | Self::Note(_, _) => Vec::new(),
         }
     }
 
     pub fn section_entry_name(&self) -> String {
-        format!(".{:#}", self.name())
+        match self {
+            Self::Note(name, _) => format!(".note.{name:#}"),
+            _ => format!(".{:#}", self.name()),
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Self::Text | Self::Rodata | Self::Dtb | Self::Note(_, _)
+        )
     }
 
     pub fn section_entry_start_symbol(&self) -> String {
@@ -361,6 +555,11 @@ impl SectionType {
     pub fn section_entry_end_symbol(&self) -> String {
         format!("_e{:#}", self.name())
     }
+
+    // Load address of a section whose contents are loaded from another memory
+    pub fn section_entry_load_symbol(&self) -> String {
+        format!("_si{:#}", self.name())
+    }
 }
 
 // Subsections can be added to Sections to be included in the linker script. They only have
@@ -371,6 +570,8 @@ pub struct SubSection {
     alignment_in_bytes: usize,
     max_size: Option<usize>,
     mark_as_keep: bool,
+    // Files the input section is taken from, e.g. `libpayload.a:*` or `*payload.o`
+    file_pattern: String,
 }
 
 impl SubSection {
@@ -384,6 +585,7 @@ impl SubSection {
             alignment_in_bytes,
             max_size,
             mark_as_keep: false,
+            file_pattern: "*".to_string(),
         }
     }
 
@@ -397,6 +599,35 @@ impl SubSection {
         self.mark_as_keep = true;
         self
     }
+
+    // Use the builder pattern to only take the input section from the files matching `pattern`
+    // (an archive member pattern such as `libpayload.a:*`, or an object file name) instead of all
+    // the input files
+    pub fn from_files(mut self, pattern: &str) -> Self {
+        self.file_pattern = pattern.to_string();
+        self
+    }
+}
+
+// Output section types of the linker script, other than the default (loadable)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputSectionType {
+    // Allocated but not loaded
+    NoLoad,
+    // Neither allocated nor loaded, the contents are kept in the output file
+    Copy,
+    Info,
+}
+
+impl std::fmt::Display for OutputSectionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let print_str = match self {
+            Self::NoLoad => "(NOLOAD)",
+            Self::Copy => "(COPY)",
+            Self::Info => "(INFO)",
+        };
+        write!(f, "{print_str}")
+    }
 }
 
 // Deals with standard sections defined by the section type above. If custom sections are required for any purpose,
@@ -409,6 +640,13 @@ pub struct Section {
     target_memory: String,
     subsections: Vec<SubSection>,
     load_address: Option<String>, // Symbol indicating load address
+    reset_entry_alignment_in_bytes: Option<usize>, // Alignment emitted after the reset entry input section
+    reset_entry_max_size: Option<usize>, // Upper bound on the size of the reset entry input section
+    placement_order: Option<usize>,      // Physical order of the section within its target memory
+    min_size: Option<usize>,             // Lower bound on the size of the output section
+    max_size: Option<usize>,             // Upper bound on the size of the output section
+    fill: Option<u32>,                   // Pattern the section is initialized with
+    output_type: Option<OutputSectionType>, // Overrides the type of the output section
 }
 
 impl Section {
@@ -422,6 +660,13 @@ impl Section {
             target_memory: target_memory.to_string(),
             subsections: Vec::new(),
             load_address: None,
+            reset_entry_alignment_in_bytes: None,
+            reset_entry_max_size: None,
+            placement_order: None,
+            min_size: None,
+            max_size: None,
+            fill: None,
+            output_type: None,
         }
     }
 
@@ -434,6 +679,135 @@ impl Section {
         self.load_address = Some(load_address.to_string());
         self
     }
+
+    // Use the builder pattern to align the code following the reset entry input section. Only
+    // applicable to the text section since that is where the reset entry is placed.
+    pub fn with_reset_entry_alignment(mut self, alignment_in_bytes: usize) -> Self {
+        assert!(
+            self.ty == SectionType::Text,
+            "Reset entry alignment is only applicable to the text section"
+        );
+        self.reset_entry_alignment_in_bytes = Some(alignment_in_bytes);
+        self
+    }
+
+    // Use the builder pattern to fail the link if the reset entry code grows beyond `max_size`
+    // bytes, e.g. when a boot ROM jumps to a fixed-size reset vector region. Only applicable to the
+    // text section since that is where the reset entry is placed.
+    pub fn with_reset_entry_max_size(mut self, max_size: usize) -> Self {
+        assert!(
+            self.ty == SectionType::Text,
+            "Reset entry max size is only applicable to the text section"
+        );
+        self.reset_entry_max_size = Some(max_size);
+        self
+    }
+
+    // Use the builder pattern to control where this section is physically placed within its
+    // target memory, independent of its position in the section list. Sections are placed in
+    // ascending order; sections without an explicit order come first, in list order.
+    pub fn with_placement_order(mut self, order: usize) -> Self {
+        self.placement_order = Some(order);
+        self
+    }
+
+    // Use the builder pattern to fail the link if this section grows beyond `max_size` bytes,
+    // e.g. to guarantee that .text fits in flash
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        assert!(
+            self.min_size.is_none_or(|min_size| min_size <= max_size),
+            "Max size of {:#} is below its min size",
+            self.ty.name()
+        );
+        self.max_size = Some(max_size);
+        self
+    }
+
+    // Use the builder pattern to initialize a heap or custom section with the 4-byte `pattern`
+    // (e.g. a poison value for use-before-init debugging). The section becomes loadable so that
+    // the loader writes the pattern.
+    pub fn with_fill(mut self, pattern: u32) -> Self {
+        assert!(
+            matches!(
+                self.ty,
+                SectionType::Heap | SectionType::NamedHeap(_, _) | SectionType::Custom(_, _)
+            ),
+            "Fill pattern is only applicable to heap and custom sections"
+        );
+        self.fill = Some(pattern);
+        self
+    }
+
+    fn is_allocated(&self) -> bool {
+        !matches!(
+            self.output_type,
+            Some(OutputSectionType::Copy | OutputSectionType::Info)
+        )
+    }
+
+    // Whether the contents of this section are part of the loadable image
+    fn is_loaded(&self) -> bool {
+        if self.output_type.is_some() {
+            return false;
+        }
+        match self.ty {
+            SectionType::Bss | SectionType::Stack => false,
+            SectionType::Heap | SectionType::NamedHeap(_, _) => self.fill.is_some(),
+            SectionType::Custom(_, _) => !self.subsections.is_empty() || self.fill.is_some(),
+            _ => true,
+        }
+    }
+
+    // Use the builder pattern to set the type of the output section, e.g. (INFO) for metadata
+    // consumed by external tools but not loaded at runtime
+    pub fn with_output_type(mut self, output_type: OutputSectionType) -> Self {
+        assert!(
+            matches!(self.ty, SectionType::Custom(_, _)),
+            "Output section type is only applicable to custom sections"
+        );
+        self.output_type = Some(output_type);
+        self
+    }
+
+    // Use the builder pattern to fail the link if this section is smaller than `min_size` bytes
+    pub fn with_min_size(mut self, min_size: usize) -> Self {
+        assert!(
+            self.max_size.is_none_or(|max_size| min_size <= max_size),
+            "Min size of {:#} is above its max size",
+            self.ty.name()
+        );
+        self.min_size = Some(min_size);
+        self
+    }
+}
+
+// Reorder the sections of each target memory by their placement order. The slots occupied by the
+// sections of a memory in the list are kept, so the relative order of different memories is
+// unchanged.
+fn sort_sections_by_placement_order(sections: &mut [Section]) {
+    let mut memory_names: Vec<String> = Vec::new();
+    for section in sections.iter() {
+        if !memory_names.contains(&section.target_memory) {
+            memory_names.push(section.target_memory.clone());
+        }
+    }
+
+    for memory_name in memory_names {
+        let slots: Vec<usize> = (0..sections.len())
+            .filter(|idx| sections[*idx].target_memory == memory_name)
+            .collect();
+
+        let mut order: Vec<usize> = slots.clone();
+        order.sort_by_key(|idx| sections[*idx].placement_order);
+
+        // Apply the permutation using swaps so that sections don't need to be cloned
+        let mut current: Vec<usize> = slots.clone();
+        for (pos, wanted) in order.iter().enumerate() {
+            let from = current.iter().position(|idx| idx == wanted).unwrap();
+            sections.swap(slots[pos], slots[from]);
+            current.swap(pos, from);
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
@@ -482,6 +856,22 @@ impl Symbol {
             value: value.to_string(),
         }
     }
+
+    // Symbols such as `__global_pointer$` can't be referred to from Rust, so no accessor is
+    // generated for them
+    fn has_rust_accessor(&self) -> bool {
+        let mut chars = self.name.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            && self.name.chars().any(|c| c != '_')
+    }
+
+    // `_sfoo` -> `sfoo_addr()`
+    fn accessor_fn_name(&self) -> String {
+        format!("{:#}_addr", self.name.trim_start_matches('_'))
+    }
 }
 
 #[derive(Debug)]
@@ -491,6 +881,13 @@ pub struct LinkerConfig<'a> {
     pub stack_location: StackLocation,
     pub target_config: TargetConfig,
     pub symbols: Vec<Symbol>,
+    pub banner: String,
+    // Regions owned by other components sharing the memory map, which this program must not overlap
+    pub foreign_regions: Vec<MemoryRegion>,
+    // (size, alignment) of the gap left between read-only and writable sections of a memory
+    pub ro_rw_guard_gap: Option<(usize, usize)>,
+    // The runtime keeps a stack overflow detection sentry at the bottom of the stacks
+    pub stack_sentry: bool,
 }
 
 impl<'a> LinkerConfig<'a> {
@@ -500,6 +897,8 @@ impl<'a> LinkerConfig<'a> {
         stack_location: StackLocation,
         target_config: TargetConfig,
     ) -> Self {
+        sort_sections_by_placement_order(&mut sections);
+
         let mut memories = Vec::new();
         let mut region_iter = memory_regions.iter().peekable();
 
@@ -548,6 +947,60 @@ impl<'a> LinkerConfig<'a> {
         // Ensure all the memories are sorted by their base address.
         memories.sort_by(|a, b| a.base.cmp(&b.base));
 
+        for memory in &memories {
+            let Some(load_region) = &memory.load_region else {
+                continue;
+            };
+            let load_memory = memories.iter().find(|m| &m.name == load_region);
+            assert!(
+                load_memory.is_some_and(|m| m.load_region.is_none()),
+                "Load region {load_region:?} of memory {:?} is not a memory loaded in place",
+                memory.name
+            );
+            for section in sections.iter().filter(|s| s.target_memory == memory.name) {
+                assert!(
+                    section.load_address.is_none(),
+                    "Section {:?} has a load address but memory {:?} has a load region",
+                    section.ty.name(),
+                    memory.name
+                );
+                // Harts use the runtime variables in .data from reset on, long before it could
+                // be copied
+                assert!(
+                    section.ty != SectionType::Data,
+                    "Data section can't be in memory {:?} with a load region",
+                    memory.name
+                );
+            }
+        }
+
+        if let Some(load_region) = target_config.reset_entry_load_region() {
+            assert!(
+                memories
+                    .iter()
+                    .any(|m| m.name == load_region && m.load_region.is_none()),
+                "Reset entry load region {load_region:?} is not a memory loaded in place"
+            );
+            // The reset entry runs from the start of the text section, which is itself run in place
+            let text = sections.iter().find(|s| s.ty == SectionType::Text);
+            assert!(
+                text.is_some_and(|s| s.target_memory != load_region
+                    && s.load_address.is_none()
+                    && memories
+                        .iter()
+                        .any(|m| m.name == s.target_memory && m.load_region.is_none())),
+                "Reset entry load region {load_region:?} needs a text section run in place elsewhere"
+            );
+        }
+
+        if let StackGuard::Page(size) = target_config.stack_guard() {
+            assert!(
+                target_config.per_hart_interrupt_stack_size() % size == 0,
+                "Per-hart interrupt stack size {:#x} is not a multiple of the stack guard size {size:#x}",
+                target_config.per_hart_interrupt_stack_size()
+            );
+        }
+
         if stack_location.is_stack_in_separate_section() {
             assert!(
                 sections.iter().any(|s| s.ty == SectionType::Stack),
@@ -555,15 +1008,122 @@ impl<'a> LinkerConfig<'a> {
             );
         }
 
+        // Each heap gets its own start/end symbols, so their names must not collide.
+        for (idx, section) in sections.iter().enumerate() {
+            if let SectionType::NamedHeap(name, _) = &section.ty {
+                assert!(
+                    sections[..idx].iter().all(|s| s.ty.name() != name),
+                    "Duplicate section name {name:?} for heap section"
+                );
+            }
+        }
+
         Self {
             memories,
             sections,
             stack_location,
             target_config,
             symbols: vec![],
+            banner: auto_generate_banner(),
+            foreign_regions: vec![],
+            ro_rw_guard_gap: None,
+            stack_sentry: false,
         }
     }
 
+    // Create the config of a component owning `region_names` in a memory map shared with other
+    // components. The generated linker script asserts that the program doesn't overlap the regions
+    // owned by the other components.
+    pub fn from_shared_memory_map(
+        memory_map: &SharedMemoryMap,
+        region_names: &[&str],
+        sections: Vec<Section>,
+        stack_location: StackLocation,
+        target_config: TargetConfig,
+    ) -> Self {
+        Self::new(
+            memory_map.carve(region_names),
+            sections,
+            stack_location,
+            target_config,
+        )
+        .with_foreign_regions(memory_map.exclude(region_names))
+    }
+
+    // Use the builder pattern to leave an unused gap of `size_in_bytes` between the last read-only
+    // section and the first writable section placed in the same memory. Stray accesses into the
+    // gap can then be made to fault (e.g. by not mapping it). The read-only sections of each
+    // memory have to be placed before its writable sections.
+    pub fn with_ro_rw_guard_gap(mut self, size_in_bytes: usize, alignment_in_bytes: usize) -> Self {
+        for (i, section) in self.sections.iter().enumerate() {
+            if let Some(writable) = self.sections[..i]
+                .iter()
+                .find(|prev| prev.target_memory == section.target_memory && !prev.ty.is_read_only())
+            {
+                assert!(
+                    !section.ty.is_read_only(),
+                    "Read-only section {} is placed after writable section {} in memory {}, the RO/RW guard gap requires read-only sections first",
+                    section.ty.section_entry_name(),
+                    writable.ty.section_entry_name(),
+                    section.target_memory
+                );
+            }
+        }
+        self.ro_rw_guard_gap = Some((size_in_bytes, alignment_in_bytes));
+        self
+    }
+
+    // Use the builder pattern to tell that the runtime is generated with stack overflow detection,
+    // i.e. that the bottom word of the stacks holds the sentry value and isn't usable
+    pub fn with_stack_sentry(mut self) -> Self {
+        assert!(
+            self.target_config.stack_guard() == StackGuard::Sentry,
+            "Stack sentry requires the sentry stack guard"
+        );
+        self.stack_sentry = true;
+        self
+    }
+
+    // Use the builder pattern to pad the loadable image up to a multiple of `alignment_in_bytes`
+    // (e.g. the flash page size), by aligning the end of the last loaded section. The last
+    // loaded section is the one placed last in the memory with the highest base address.
+    pub fn with_image_alignment(mut self, alignment_in_bytes: usize) -> Self {
+        assert!(
+            alignment_in_bytes.is_power_of_two(),
+            "Image alignment {alignment_in_bytes:#x} is not a power-of-2"
+        );
+
+        let last_section = self.memories.iter().rev().find_map(|memory| {
+            self.sections
+                .iter()
+                .rposition(|section| section.target_memory == memory.name && section.is_loaded())
+        });
+        let Some(idx) = last_section else {
+            panic!("No loaded section to pad to the image alignment");
+        };
+
+        let section = &mut self.sections[idx];
+        section.end_alignment_in_bytes = section.end_alignment_in_bytes.max(alignment_in_bytes);
+        self
+    }
+
+    // Use the builder pattern to provide the regions owned by other components
+    pub fn with_foreign_regions(mut self, foreign_regions: Vec<MemoryRegion>) -> Self {
+        for region in &foreign_regions {
+            for memory in &self.memories {
+                assert!(
+                    !region.overlaps(memory.base, memory.length),
+                    "Memory {:?} overlaps region {:?} owned by another component",
+                    memory.name,
+                    region.name
+                );
+            }
+        }
+
+        self.foreign_regions = foreign_regions;
+        self
+    }
+
     pub fn section_types(&self) -> Vec<SectionType> {
         let mut sections = Vec::new();
 
@@ -582,14 +1142,43 @@ impl<'a> LinkerConfig<'a> {
         self.target_config.per_hart_stack_size()
     }
 
+    // Memory the contents of `section` are loaded from, if it is not loaded in place
+    fn section_load_region(&self, section: &Section) -> Option<&str> {
+        if !section.is_loaded() {
+            return None;
+        }
+        self.memories
+            .iter()
+            .find(|memory| memory.name == section.target_memory)
+            .and_then(|memory| memory.load_region.as_deref())
+    }
+
+    // Sections copied from their load region to their target memory by copy_data_to_ram()
+    fn relocated_sections(&self) -> Vec<&SectionType> {
+        self.sections
+            .iter()
+            .filter(|section| section.ty != SectionType::Text)
+            .filter(|section| self.section_load_region(section).is_some())
+            .map(|section| &section.ty)
+            .collect()
+    }
+
     fn stack_region_size(&self) -> usize {
-        self.hart_stack_size() * self.target_config.max_hart_count()
+        self.target_config.per_hart_stack_stride() * self.target_config.max_hart_count()
     }
 
     fn heap_size(&self) -> usize {
         self.target_config.heap_size()
     }
 
+    fn abort_stack_region_size(&self) -> usize {
+        self.target_config.per_hart_abort_stack_size() * self.target_config.max_hart_count()
+    }
+
+    fn interrupt_stack_region_size(&self) -> usize {
+        self.target_config.per_hart_interrupt_stack_stride() * self.target_config.max_hart_count()
+    }
+
     fn stack_in_bss_alignment(&self) -> usize {
         match self.stack_location {
             StackLocation::InBss(StackAlignment::Default) => 4096, // 4KiB
@@ -607,6 +1196,12 @@ impl<'a> LinkerConfig<'a> {
     pub fn add_symbol(&mut self, symbol: Symbol) {
         self.symbols.push(symbol);
     }
+
+    // Use the builder pattern to replace the banner placed at the top of every generated file
+    pub fn with_banner(mut self, banner: &str) -> Self {
+        self.banner = banner.to_string();
+        self
+    }
 }
 
 #[derive(Debug)]
@@ -630,16 +1225,18 @@ enum LinkerSentence<'a> {
     Memory(&'a [Memory<'a>]), // (slice of Memory structures)
     SectionsStart,
     SectionsEnd,
-    OutputSectionStart(String, bool, usize, Option<String>), // (name, noload, alignment, load_address)
-    OutputSectionEnd(String),                                // (target_memory)
-    InputSections(String, bool),                             // (input sections string, keep)
-    SetRelativeToLocationCounter(String, isize),             // (symbol, offset)
-    SetToCurrent(String),                                    // (symbol)
-    SetToValue(String, usize),                               // (symbol, value)
-    SetToSymbol(String, String),                             // (symbol, symbol)
-    AdvanceLocationCounter(usize),                           // (size)
-    Align(usize),                                            // (alignment)
-    Assert(String, String),                                  // (assert condition, error message)
+    OutputSectionStart(String, Option<OutputSectionType>, usize, Option<String>), // (name, type, alignment, load_address)
+    OutputSectionEnd(String, Option<String>), // (target_memory, load_memory)
+    InputSections(String, String, bool),      // (file pattern, input sections string, keep)
+    SetRelativeToLocationCounter(String, isize), // (symbol, offset)
+    SetToCurrent(String),                     // (symbol)
+    SetToValue(String, usize),                // (symbol, value)
+    SetToSymbol(String, String),              // (symbol, symbol)
+    AdvanceLocationCounter(usize),            // (size)
+    Fill(u32),                                // (pattern)
+    Byte(u8),                                 // (value)
+    Align(usize),                             // (alignment)
+    Assert(String, String),                   // (assert condition, error message)
     DiscardSectionStart,
     DiscardSectionEnd,
     Symbol(String, String), // (name, value expression)
@@ -660,25 +1257,32 @@ impl<'a> LinkerSentence<'a> {
             }
             Self::SectionsStart => fw.new_block("SECTIONS"),
             Self::SectionsEnd => fw.end_block(),
-            Self::OutputSectionStart(name, noload, alignment, load_address) => {
-                let noload = if *noload { "(NOLOAD)" } else { "" };
+            Self::OutputSectionStart(name, output_type, alignment, load_address) => {
+                let output_type = match output_type {
+                    Some(output_type) => output_type.to_string(),
+                    None => "".to_string(),
+                };
                 let load_addr = if let Some(symbol) = load_address {
                     format!("AT({symbol}) ")
                 } else {
                     "".to_string()
                 };
                 fw.new_block(&format!(
-                    "{name:#} {noload:#}: {load_addr}ALIGN({alignment:#})"
+                    "{name:#} {output_type:#}: {load_addr}ALIGN({alignment:#})"
                 ));
             }
-            Self::OutputSectionEnd(target_memory) => {
-                fw.end_block_with_suffix(&format!(">{target_memory:#}"))
+            Self::OutputSectionEnd(target_memory, load_memory) => {
+                let load_memory = match load_memory {
+                    Some(load_memory) => format!(" AT>{load_memory:#}"),
+                    None => "".to_string(),
+                };
+                fw.end_block_with_suffix(&format!(">{target_memory:#}{load_memory:#}"))
             }
-            Self::InputSections(sections, keep) => {
+            Self::InputSections(files, sections, keep) => {
                 if *keep {
-                    fw.add_line(&format!("KEEP(*({sections:#}))"));
+                    fw.add_line(&format!("KEEP({files:#}({sections:#}))"));
                 } else {
-                    fw.add_line(&format!("*({sections:#})"));
+                    fw.add_line(&format!("{files:#}({sections:#})"));
                 }
             }
             Self::SetRelativeToLocationCounter(symbol, size) => {
@@ -690,6 +1294,8 @@ impl<'a> LinkerSentence<'a> {
                 fw.add_line(&format!("{symbola:#} = {symbolb:#};"))
             }
             Self::AdvanceLocationCounter(size) => fw.add_line(&format!(". += {size:#x};")),
+            Self::Fill(pattern) => fw.add_line(&format!("FILL({pattern:#010x});")),
+            Self::Byte(value) => fw.add_line(&format!("BYTE({value:#04x});")),
             Self::Align(alignment) => fw.add_line(&format!(". = ALIGN({alignment:#});")),
             Self::Assert(assert_cond, error_msg) => {
                 fw.add_line(&format!("ASSERT({assert_cond:#}, {error_msg:?})"))
@@ -714,7 +1320,9 @@ impl<'a> LinkerBuilder<'a> {
             linker_config,
             sentences: RefCell::new(Vec::new()),
         };
-        lb.comment(&auto_generate_banner());
+        for line in linker_config.banner.lines() {
+            lb.comment(line);
+        }
         lb
     }
 
@@ -748,7 +1356,9 @@ impl<'a> LinkerBuilder<'a> {
     }
 
     fn entry(&self) {
-        self.add_sentence(LinkerSentence::Entry(START_SYMBOL.to_string()));
+        self.add_sentence(LinkerSentence::Entry(
+            self.linker_config.target_config.start_symbol().to_string(),
+        ));
     }
 
     fn memory(&self) {
@@ -768,9 +1378,16 @@ impl<'a> LinkerBuilder<'a> {
         }
     }
 
+    fn stack_size_symbol(&self) {
+        self.add_sentence(LinkerSentence::SetToValue(
+            stack_size_symbol(),
+            self.linker_config.hart_stack_size(),
+        ));
+    }
+
     fn program_symbols(&self) {
         for memory in &self.linker_config.memories {
-            if memory.sections.borrow().is_empty() {
+            if memory.is_empty() {
                 continue;
             }
             self.add_sentence(LinkerSentence::SetToSymbol(
@@ -780,7 +1397,7 @@ impl<'a> LinkerBuilder<'a> {
             break;
         }
         for memory in self.linker_config.memories.iter().rev() {
-            if memory.sections.borrow().is_empty() {
+            if memory.is_empty() {
                 continue;
             }
             self.add_sentence(LinkerSentence::SetToSymbol(
@@ -800,14 +1417,42 @@ impl<'a> LinkerBuilder<'a> {
     ) {
         self.add_sentence(LinkerSentence::OutputSectionStart(
             name,
-            noload,
+            noload.then_some(OutputSectionType::NoLoad),
             alignment,
             load_address,
         ));
     }
 
+    // Start the output section of `section_info`, which is (NOLOAD) if `noload` is set unless the
+    // section overrides its type
+    fn section_output_start(&self, section_info: &Section, noload: bool) {
+        self.add_sentence(LinkerSentence::OutputSectionStart(
+            section_info.ty.section_entry_name(),
+            section_info
+                .output_type
+                .or(noload.then_some(OutputSectionType::NoLoad)),
+            section_info.start_alignment_in_bytes,
+            section_info.load_address.clone(),
+        ));
+    }
+
     fn output_section_end(&self, section_suffix: String) {
-        self.add_sentence(LinkerSentence::OutputSectionEnd(section_suffix));
+        self.add_sentence(LinkerSentence::OutputSectionEnd(section_suffix, None));
+    }
+
+    fn section_output_end(&self, section_info: &Section) {
+        self.add_sentence(LinkerSentence::OutputSectionEnd(
+            section_info.target_memory.to_string(),
+            self.linker_config
+                .section_load_region(section_info)
+                .map(str::to_string),
+        ));
+    }
+
+    fn fill(&self, pattern: Option<u32>) {
+        if let Some(pattern) = pattern {
+            self.add_sentence(LinkerSentence::Fill(pattern));
+        }
     }
 
     fn set_symbol_to_current(&self, symbol: String) {
@@ -819,7 +1464,12 @@ impl<'a> LinkerBuilder<'a> {
     }
 
     fn input_section(&self, section: &str, keep: bool) {
+        self.input_section_from_files("*", section, keep);
+    }
+
+    fn input_section_from_files(&self, files: &str, section: &str, keep: bool) {
         self.add_sentence(LinkerSentence::InputSections(
+            files.to_string(),
             format!("{section:#} {section:#}.*"),
             keep,
         ));
@@ -855,7 +1505,7 @@ impl<'a> LinkerBuilder<'a> {
             let start = format!("_s{section_symbol_suffix}");
             self.set_symbol_to_current(start.clone());
 
-            self.input_section(&ss.input_section, ss.mark_as_keep);
+            self.input_section_from_files(&ss.file_pattern, &ss.input_section, ss.mark_as_keep);
 
             // . = ALIGN(...);
             self.align(ss.alignment_in_bytes);
@@ -874,22 +1524,139 @@ impl<'a> LinkerBuilder<'a> {
         }
     }
 
-    fn add_text_section(&self, section_info: &Section) {
+    fn add_section_size_asserts(&self, section_info: &Section) {
+        let ty = &section_info.ty;
+        let start = ty.section_entry_start_symbol();
+        let end = ty.section_entry_end_symbol();
+
+        // ASSERT(_esection - _ssection <= max_size, "Section section exceeded max size");
+        if let Some(max_size) = section_info.max_size {
+            self.assert(
+                format!("{end:#} - {start:#} <= {max_size:#}"),
+                format!(
+                    "{:#} exceeds its max size of {max_size:#} bytes",
+                    ty.section_entry_name()
+                ),
+            );
+        }
+
+        // ASSERT(_etext_entry - _stext_entry <= max_size, "Reset entry exceeds its max size");
+        if let Some(max_size) = section_info.reset_entry_max_size {
+            self.assert(
+                format!(
+                    "{:#} - {:#} <= {max_size:#}",
+                    reset_entry_end_symbol(),
+                    reset_entry_start_symbol()
+                ),
+                format!("Reset entry exceeds its max size of {max_size:#} bytes"),
+            );
+        }
+
+        // ASSERT(_esection - _ssection >= min_size, "Section section is below min size");
+        if let Some(min_size) = section_info.min_size {
+            self.assert(
+                format!("{end:#} - {start:#} >= {min_size:#}"),
+                format!(
+                    "{:#} is below its min size of {min_size:#} bytes",
+                    ty.section_entry_name()
+                ),
+            );
+        }
+    }
+
+    // The stub copying the reset entry to its run address comes first in its load memory, so that
+    // it sits at the reset vector.
+    fn add_reset_entry_copy_section(&self, load_region: &str) {
+        // .text.entry_copy : ALIGN(4) {
+        self.output_section_start(reset_entry_copy_section(), false, 4, None);
+
+        // KEEP(*(.text.entry_copy .text.entry_copy.*))
+        self.input_section(&reset_entry_copy_section(), true);
+
+        // } >{LOAD MEMORY}
+        self.output_section_end(load_region.to_string());
+    }
+
+    // Place the reset entry in its own output section ahead of the text section, run from the text
+    // memory but loaded from `load_region`. The stub copies it in xlen-sized words.
+    fn add_reset_entry_section(&self, section_info: &Section, load_region: &str) {
         let ty = &section_info.ty;
+        let reset_section = self.linker_config.target_config.reset_section();
+        let xlen_bytes = self.linker_config.target_config.xlen_bytes() as usize;
 
-        // .text : ALIGN(...) {
+        // .text.entry : ALIGN(...) {
         self.output_section_start(
-            ty.section_entry_name(),
+            reset_section.to_string(),
             false,
-            section_info.start_alignment_in_bytes,
-            section_info.load_address.clone(),
+            section_info.start_alignment_in_bytes.max(xlen_bytes),
+            None,
         );
 
-        // _stext =  .;
+        // _stext = .;
+        // _stext_entry = .;
+        // KEEP(*(.text.entry .text.entry.*)), unless the reset section is renamed
+        // . = ALIGN(xlen);
+        // _etext_entry = .;
+        // The start symbol is in the stub, so only KEEP protects the reset entry from GC
         self.set_symbol_to_current(ty.section_entry_start_symbol());
+        self.set_symbol_to_current(reset_entry_start_symbol());
+        self.input_section(reset_section, true);
+        self.align(xlen_bytes);
+        self.set_symbol_to_current(reset_entry_end_symbol());
+
+        // } >{MEMORY} AT>{LOAD MEMORY}
+        self.add_sentence(LinkerSentence::OutputSectionEnd(
+            section_info.target_memory.to_string(),
+            Some(load_region.to_string()),
+        ));
+
+        // _sitext_entry = LOADADDR(.text.entry);
+        self.add_sentence(LinkerSentence::Symbol(
+            reset_entry_load_symbol(),
+            format!("LOADADDR({reset_section:#})"),
+        ));
+    }
+
+    fn add_text_section(&self, section_info: &Section) {
+        let ty = &section_info.ty;
+        let reset_entry_load_region = self.linker_config.target_config.reset_entry_load_region();
+
+        if let Some(load_region) = reset_entry_load_region {
+            self.add_reset_entry_section(section_info, load_region);
+
+            // .text : ALIGN(4) {
+            // Keep the text section contiguous with the reset entry
+            self.add_sentence(LinkerSentence::OutputSectionStart(
+                ty.section_entry_name(),
+                section_info.output_type,
+                4,
+                None,
+            ));
+        } else {
+            // .text : ALIGN(...) {
+            self.section_output_start(section_info, false);
+
+            // _stext =  .;
+            self.set_symbol_to_current(ty.section_entry_start_symbol());
+
+            // _stext_entry = .;
+            // *(.text.entry .text.entry.*), unless the reset section is renamed
+            // _etext_entry = .;
+            // The bounding symbols are only needed for the size assertion
+            let bound_reset_entry = section_info.reset_entry_max_size.is_some();
+            if bound_reset_entry {
+                self.set_symbol_to_current(reset_entry_start_symbol());
+            }
+            self.input_section(self.linker_config.target_config.reset_section(), false);
+            if bound_reset_entry {
+                self.set_symbol_to_current(reset_entry_end_symbol());
+            }
+        }
 
-        // *(.text.entry .text.entry.*)
-        self.input_section(&reset_section(), false);
+        // . = ALIGN(...);
+        if let Some(alignment) = section_info.reset_entry_alignment_in_bytes {
+            self.align(alignment);
+        }
 
         // *(.text.custom_reset_entry .text.custom_reset_entry.*)
         /*
@@ -901,6 +1668,14 @@ impl<'a> LinkerBuilder<'a> {
             self.input_section(&custom_reset_section(), false);
         }
 
+        // Trap entry/create/restore code is kept contiguous so that it can be protected as a unit
+        // _strap_code = .;
+        // *(.text.trap .text.trap.*)
+        // _etrap_code = .;
+        self.set_symbol_to_current(trap_code_start_symbol());
+        self.input_section(&trap_code_section(), false);
+        self.set_symbol_to_current(trap_code_end_symbol());
+
         // *(.text .text.*)
         let default_sections = ty.default_sections();
         for input_section in default_sections {
@@ -917,19 +1692,14 @@ impl<'a> LinkerBuilder<'a> {
         self.set_symbol_to_current(ty.section_entry_end_symbol());
 
         // } >{MEMORY}
-        self.output_section_end(section_info.target_memory.to_string());
+        self.section_output_end(section_info);
     }
 
     fn add_rodata_section(&self, section_info: &Section) {
         let ty = &section_info.ty;
 
         // .rodata : ALIGN(...) {
-        self.output_section_start(
-            ty.section_entry_name(),
-            false,
-            section_info.start_alignment_in_bytes,
-            section_info.load_address.clone(),
-        );
+        self.section_output_start(section_info, false);
 
         // _srodata =  .;
         self.set_symbol_to_current(ty.section_entry_start_symbol());
@@ -951,19 +1721,14 @@ impl<'a> LinkerBuilder<'a> {
         self.set_symbol_to_current(ty.section_entry_end_symbol());
 
         // } >{MEMORY}
-        self.output_section_end(section_info.target_memory.to_string());
+        self.section_output_end(section_info);
     }
 
     fn add_data_section(&self, section_info: &Section) {
         let ty = &section_info.ty;
 
         // .data : ALIGN(...) {
-        self.output_section_start(
-            ty.section_entry_name(),
-            false,
-            section_info.start_alignment_in_bytes,
-            section_info.load_address.clone(),
-        );
+        self.section_output_start(section_info, false);
 
         // _sdata =  .;
         self.set_symbol_to_current(ty.section_entry_start_symbol());
@@ -988,19 +1753,101 @@ impl<'a> LinkerBuilder<'a> {
         self.set_symbol_to_current(ty.section_entry_end_symbol());
 
         // } >{MEMORY}
-        self.output_section_end(section_info.target_memory.to_string());
+        self.section_output_end(section_info);
+    }
+
+    fn add_ro_rw_guard_gap(&self, target_memory: &str) {
+        let Some((size, alignment)) = self.linker_config.ro_rw_guard_gap else {
+            return;
+        };
+
+        // .guard_{MEMORY} (NOLOAD) : ALIGN(...) {
+        self.output_section_start(format!(".guard_{target_memory:#}"), true, alignment, None);
+
+        // _sguard_{MEMORY} = .;
+        self.set_symbol_to_current(format!("_sguard_{target_memory:#}"));
+
+        // . = . + size;
+        self.advance_location_counter(size);
+
+        // . = ALIGN(...);
+        self.align(alignment);
+
+        // _eguard_{MEMORY} = .;
+        self.set_symbol_to_current(format!("_eguard_{target_memory:#}"));
+
+        // } >{MEMORY}
+        self.output_section_end(target_memory.to_string());
     }
 
     fn add_stack_section_contents(&self) {
         let ty = SectionType::Stack;
+        let target_config = &self.linker_config.target_config;
+        let guard_size = target_config.stack_guard_size();
+        if guard_size != 0 {
+            // . = ALIGN(guard size);
+            self.align(guard_size);
+        }
         // _sstack =  .;
         self.set_symbol_to_current(ty.section_entry_start_symbol());
-        // . = . + size;
-        self.advance_location_counter(self.linker_config.stack_region_size());
+        if guard_size != 0 {
+            // Hart 0 has the highest stack, so lay out the harts from the last one upwards
+            for boot_id in (0..target_config.max_hart_count()).rev() {
+                // _stack_guard_N = .;
+                self.set_symbol_to_current(stack_guard_symbol(boot_id));
+                // . = . + guard size;
+                self.advance_location_counter(guard_size);
+                // . = . + stack size;
+                self.advance_location_counter(self.linker_config.hart_stack_size());
+            }
+        } else {
+            // . = . + size;
+            self.advance_location_counter(self.linker_config.stack_region_size());
+        }
         // _stack_top = .;
         self.set_symbol_to_current(stack_top_symbol());
         // _estack = .;
         self.set_symbol_to_current(ty.section_entry_end_symbol());
+
+        if self.linker_config.target_config.needs_abort_stack() {
+            // Abort stacks are kept outside of the hart stacks so that an overflowing hart stack
+            // cannot corrupt them.
+            // _sabort_stack = .;
+            self.set_symbol_to_current(abort_stack_start_symbol());
+            // . = . + size;
+            self.advance_location_counter(self.linker_config.abort_stack_region_size());
+            // _abort_stack_top = .;
+            self.set_symbol_to_current(abort_stack_top_symbol());
+            // _eabort_stack = .;
+            self.set_symbol_to_current(abort_stack_end_symbol());
+        }
+
+        if self.linker_config.target_config.needs_interrupt_stack() {
+            // An overflowing interrupt stack would otherwise run into the stack below it
+            if guard_size != 0 {
+                // . = ALIGN(guard size);
+                self.align(guard_size);
+            }
+            // _sinterrupt_stack = .;
+            self.set_symbol_to_current(interrupt_stack_start_symbol());
+            if guard_size != 0 {
+                for boot_id in (0..target_config.max_hart_count()).rev() {
+                    // _interrupt_stack_guard_N = .;
+                    self.set_symbol_to_current(interrupt_stack_guard_symbol(boot_id));
+                    // . = . + guard size;
+                    self.advance_location_counter(guard_size);
+                    // . = . + interrupt stack size;
+                    self.advance_location_counter(target_config.per_hart_interrupt_stack_size());
+                }
+            } else {
+                // . = . + size;
+                self.advance_location_counter(self.linker_config.interrupt_stack_region_size());
+            }
+            // _interrupt_stack_top = .;
+            self.set_symbol_to_current(interrupt_stack_top_symbol());
+            // _einterrupt_stack = .;
+            self.set_symbol_to_current(interrupt_stack_end_symbol());
+        }
     }
 
     fn add_stack_section(&self, section_info: &Section) {
@@ -1008,15 +1855,8 @@ impl<'a> LinkerBuilder<'a> {
             return;
         }
 
-        let ty = &section_info.ty;
-
         // .stack (NOLOAD): ALIGN(...) {
-        self.output_section_start(
-            ty.section_entry_name(),
-            true,
-            section_info.start_alignment_in_bytes,
-            section_info.load_address.clone(),
-        );
+        self.section_output_start(section_info, true);
 
         self.add_stack_section_contents();
 
@@ -1024,19 +1864,14 @@ impl<'a> LinkerBuilder<'a> {
         self.align(section_info.end_alignment_in_bytes);
 
         // } >{MEMORY}
-        self.output_section_end(section_info.target_memory.to_string());
+        self.section_output_end(section_info);
     }
 
     fn add_bss_section(&self, section_info: &Section) {
         let ty = &section_info.ty;
 
         // .bss (NOLOAD): ALIGN(...) {
-        self.output_section_start(
-            ty.section_entry_name(),
-            true,
-            section_info.start_alignment_in_bytes,
-            section_info.load_address.clone(),
-        );
+        self.section_output_start(section_info, true);
 
         // _sbss =  .;
         self.set_symbol_to_current(ty.section_entry_start_symbol());
@@ -1061,27 +1896,26 @@ impl<'a> LinkerBuilder<'a> {
         self.set_symbol_to_current(ty.section_entry_end_symbol());
 
         // } >{MEMORY}
-        self.output_section_end(section_info.target_memory.to_string());
+        self.section_output_end(section_info);
     }
 
-    fn add_heap_section(&self, section_info: &Section) {
-        let heap_size = self.linker_config.heap_size();
-
+    fn add_heap_section(&self, section_info: &Section, heap_size: usize) {
         if heap_size == 0 {
             return;
         }
 
         let ty = &section_info.ty;
 
-        // .heap (NOLOAD): ALIGN(...) {
-        self.output_section_start(
-            ty.section_entry_name(),
-            true,
-            section_info.start_alignment_in_bytes,
-            section_info.load_address.clone(),
-        );
+        // If no fill pattern is set:
+        // .{heap} (NOLOAD): ALIGN(...) {
+        // Otherwise:
+        // .{heap} : ALIGN(...) {
+        self.section_output_start(section_info, section_info.fill.is_none());
+
+        // FILL(pattern);
+        self.fill(section_info.fill);
 
-        // _sheap =  .;
+        // _s{heap} =  .;
         self.set_symbol_to_current(ty.section_entry_start_symbol());
 
         // . = . + heap_size;
@@ -1090,11 +1924,11 @@ impl<'a> LinkerBuilder<'a> {
         // . = ALIGN(...);
         self.align(section_info.end_alignment_in_bytes);
 
-        // _eheap = .;
+        // _e{heap} = .;
         self.set_symbol_to_current(ty.section_entry_end_symbol());
 
         // } >{MEMORY}
-        self.output_section_end(section_info.target_memory.to_string());
+        self.section_output_end(section_info);
     }
 
     fn add_custom_section(&self, section_info: &Section, size: usize) {
@@ -1104,17 +1938,18 @@ impl<'a> LinkerBuilder<'a> {
 
         let ty = &section_info.ty;
 
-        // If subsections are empty:
+        // If subsections are empty and no fill pattern is set:
         // .{name} (NOLOAD): ALIGN(...) {
-        // If subsections are not empty:
+        // Otherwise:
         // .{name} : ALIGN(...) {
-        self.output_section_start(
-            ty.section_entry_name(),
-            section_info.subsections.is_empty(),
-            section_info.start_alignment_in_bytes,
-            section_info.load_address.clone(),
+        self.section_output_start(
+            section_info,
+            section_info.subsections.is_empty() && section_info.fill.is_none(),
         );
 
+        // FILL(pattern);
+        self.fill(section_info.fill);
+
         // _s{name} =  .;
         self.set_symbol_to_current(ty.section_entry_start_symbol());
 
@@ -1133,7 +1968,63 @@ impl<'a> LinkerBuilder<'a> {
         self.set_symbol_to_current(ty.section_entry_end_symbol());
 
         // } >{MEMORY}
-        self.output_section_end(section_info.target_memory.to_string());
+        self.section_output_end(section_info);
+    }
+
+    fn add_dtb_section(&self, section_info: &Section) {
+        let ty = &section_info.ty;
+
+        // .dtb : ALIGN(...) {
+        self.section_output_start(section_info, false);
+
+        // _sdtb =  .;
+        self.set_symbol_to_current(ty.section_entry_start_symbol());
+
+        // KEEP(*(.dtb .dtb.*))
+        // The blob is never referenced by code, so it must be kept explicitly.
+        let default_sections = ty.default_sections();
+        for input_section in default_sections {
+            self.input_section(input_section, true);
+        }
+
+        // _edtb = .;
+        // End symbol is set before the end alignment so that the region size is the size of the blob.
+        self.set_symbol_to_current(ty.section_entry_end_symbol());
+
+        // . = ALIGN(...);
+        self.align(section_info.end_alignment_in_bytes);
+
+        // } >{MEMORY}
+        self.section_output_end(section_info);
+    }
+
+    fn add_note_section(&self, section_info: &Section, content: &[u8]) {
+        let ty = &section_info.ty;
+
+        // .note.{name} : ALIGN(...) {
+        self.section_output_start(section_info, false);
+
+        // _s{name} =  .;
+        self.set_symbol_to_current(ty.section_entry_start_symbol());
+
+        // KEEP(*(.note.{name} .note.{name}.*))
+        // Notes are never referenced by code, so they must be kept explicitly.
+        self.input_section(&ty.section_entry_name(), true);
+
+        // BYTE(...);
+        for byte in content {
+            self.add_sentence(LinkerSentence::Byte(*byte));
+        }
+
+        // _e{name} = .;
+        // End symbol is set before the end alignment so that the region size is the size of the note.
+        self.set_symbol_to_current(ty.section_entry_end_symbol());
+
+        // . = ALIGN(...);
+        self.align(section_info.end_alignment_in_bytes);
+
+        // } >{MEMORY}
+        self.section_output_end(section_info);
     }
 
     fn add_discard_section(&self) {
@@ -1153,16 +2044,41 @@ impl<'a> LinkerBuilder<'a> {
     fn sections(&self) {
         self.add_sentence(LinkerSentence::SectionsStart);
 
+        if let Some(load_region) = self.linker_config.target_config.reset_entry_load_region() {
+            self.add_reset_entry_copy_section(load_region);
+        }
+
+        // Memories holding read-only sections, and the ones whose guard gap has been placed
+        let mut ro_memories = HashSet::new();
+        let mut guarded_memories = HashSet::new();
         for section in &self.linker_config.sections {
+            let memory = section.target_memory.as_str();
+            if section.ty.is_read_only() {
+                ro_memories.insert(memory);
+            } else if ro_memories.contains(memory) && guarded_memories.insert(memory) {
+                self.add_ro_rw_guard_gap(memory);
+            }
+
             match section.ty {
                 SectionType::Text => self.add_text_section(section),
                 SectionType::Rodata => self.add_rodata_section(section),
                 SectionType::Data => self.add_data_section(section),
                 SectionType::Bss => self.add_bss_section(section),
                 SectionType::Stack => self.add_stack_section(section),
-                SectionType::Heap => self.add_heap_section(section),
+                SectionType::Heap => self.add_heap_section(section, self.linker_config.heap_size()),
+                SectionType::NamedHeap(_, size) => self.add_heap_section(section, size),
+                SectionType::Dtb => self.add_dtb_section(section),
                 SectionType::Custom(_, size) => self.add_custom_section(section, size),
+                SectionType::Note(_, ref content) => self.add_note_section(section, content),
+            }
+            if self.linker_config.section_load_region(section).is_some() {
+                // _si{name} = LOADADDR(.{name});
+                self.add_sentence(LinkerSentence::Symbol(
+                    section.ty.section_entry_load_symbol(),
+                    format!("LOADADDR({:#})", section.ty.section_entry_name()),
+                ));
             }
+            self.add_section_size_asserts(section);
             self.add_section_to_memory(section);
         }
 
@@ -1170,6 +2086,7 @@ impl<'a> LinkerBuilder<'a> {
 
         self.program_symbols();
         self.memory_symbols();
+        self.stack_size_symbol();
         self.add_sentence(LinkerSentence::SectionsEnd);
     }
 
@@ -1209,6 +2126,32 @@ impl<'a> LinkerBuilder<'a> {
                 format!("{:#} overflow", memory.name),
             );
         }
+
+        // A misaligned vectored trap table would have its low address bits silently dropped
+        let trap_vector_alignment = self.linker_config.target_config.trap_vector_alignment();
+        self.assert(
+            format!(
+                "!DEFINED({TRAP_VECTOR_TABLE_SYMBOL:#}) || ({TRAP_VECTOR_TABLE_SYMBOL:#} & {:#x}) == 0",
+                trap_vector_alignment - 1
+            ),
+            format!("{TRAP_VECTOR_TABLE_SYMBOL:#} is not aligned to {trap_vector_alignment:#} bytes"),
+        );
+
+        for region in &self.linker_config.foreign_regions {
+            self.assert(
+                format!(
+                    "{:#} <= {:#x} || {:#} >= {:#x}",
+                    program_end_symbol(),
+                    region.base,
+                    program_start_symbol(),
+                    region.end()
+                ),
+                format!(
+                    "program overlaps {:#} owned by another component",
+                    region.name
+                ),
+            );
+        }
     }
 
     fn comment(&self, comment: &str) {
@@ -1272,17 +2215,205 @@ fn define_stack_for_hart(rust: &RustBuilder, linker_config: &LinkerConfig) {
     );
     rust.end_extern();
 
+    // The guard region (if any) sits below each hart's stack and is not part of it
+    let guard_size = linker_config.target_config.stack_guard_size();
+    let guard_offset = if guard_size != 0 {
+        format!(" + {guard_size:#x}")
+    } else {
+        String::new()
+    };
+
     rust.new_func_with_ret("my_stack".to_string(), "(usize, usize)".to_string());
     rust.new_unsafe_block();
     rust.implicit_ret(format!(
-        "({:#}() - {:#x} * ({:#}() + 1), {:#x})",
+        "({:#}() - {:#x} * ({:#}() + 1){guard_offset:#}, {:#x})",
         region_end_fn_name(SectionType::Stack.name()),
-        linker_config.hart_stack_size(),
+        linker_config.target_config.per_hart_stack_stride(),
         asm_fn_boot_id,
         linker_config.hart_stack_size()
     ));
     rust.end_unsafe_block();
     rust.end_func();
+
+    if guard_size != 0 {
+        // Guard region of hart `boot_id` as (start, size), matching `_stack_guard_N`, e.g. for
+        // page-table setup to unmap it
+        rust.new_func_with_arg_and_ret(
+            "stack_guard".to_string(),
+            "boot_id: usize".to_string(),
+            "(usize, usize)".to_string(),
+        );
+        rust.implicit_ret(format!(
+            "({:#}() - {:#x} * (boot_id + 1), {guard_size:#x})",
+            region_end_fn_name(SectionType::Stack.name()),
+            linker_config.target_config.per_hart_stack_stride(),
+        ));
+        rust.end_func();
+    }
+
+    if linker_config.target_config.needs_abort_stack() {
+        let abort_stack_size = linker_config.target_config.per_hart_abort_stack_size();
+
+        rust.new_func_with_ret("my_abort_stack".to_string(), "(usize, usize)".to_string());
+        rust.new_unsafe_block();
+        rust.implicit_ret(format!(
+            "({:#}() - {:#x} * ({:#}() + 1), {:#x})",
+            region_end_fn_name("abort_stack"),
+            abort_stack_size,
+            asm_fn_boot_id,
+            abort_stack_size
+        ));
+        rust.end_unsafe_block();
+        rust.end_func();
+    }
+
+    if linker_config.target_config.needs_interrupt_stack() {
+        let interrupt_stack_size = linker_config.target_config.per_hart_interrupt_stack_size();
+        let interrupt_stack_stride = linker_config
+            .target_config
+            .per_hart_interrupt_stack_stride();
+
+        rust.new_func_with_ret(
+            "my_interrupt_stack".to_string(),
+            "(usize, usize)".to_string(),
+        );
+        rust.new_unsafe_block();
+        rust.implicit_ret(format!(
+            "({:#}() - {:#x} * ({:#}() + 1){guard_offset:#}, {:#x})",
+            region_end_fn_name("interrupt_stack"),
+            interrupt_stack_stride,
+            asm_fn_boot_id,
+            interrupt_stack_size
+        ));
+        rust.end_unsafe_block();
+        rust.end_func();
+
+        if guard_size != 0 {
+            // Guard region below the interrupt stack of hart `boot_id` as (start, size), matching
+            // `_interrupt_stack_guard_N`
+            rust.new_func_with_arg_and_ret(
+                "interrupt_stack_guard".to_string(),
+                "boot_id: usize".to_string(),
+                "(usize, usize)".to_string(),
+            );
+            rust.implicit_ret(format!(
+                "({:#}() - {:#x} * (boot_id + 1), {guard_size:#x})",
+                region_end_fn_name("interrupt_stack"),
+                interrupt_stack_stride,
+            ));
+            rust.end_func();
+        }
+    }
+}
+
+// Read the stack pointer without a function call
+fn define_read_sp(rust: &RustBuilder) {
+    let sp = "sp";
+
+    rust.attribute("inline(always)");
+    rust.new_func_with_ret("read_sp".to_string(), "usize".to_string());
+    rust.let_uninit(sp, "usize");
+    rust.new_unsafe_block();
+    rust.inline_asm("mv {}, sp", vec![format!("out(reg) {sp:#}")]);
+    rust.end_unsafe_block();
+    rust.implicit_ret(sp.to_string());
+    rust.end_func();
+}
+
+fn define_on_stack(rust: &RustBuilder, fn_name: &str, stack_fn_name: &str) {
+    rust.new_func_with_ret(fn_name.to_string(), "bool".to_string());
+    rust.let_assign("(bottom, size)", &format!("{stack_fn_name:#}()"));
+    rust.implicit_ret("(bottom..=bottom + size).contains(&read_sp())".to_string());
+    rust.end_func();
+}
+
+// With stack overflow detection, the word at the bottom of the stack holds the sentry value, so it
+// doesn't count as remaining. With an interrupt stack, trap handlers run on it rather than on the
+// hart stack.
+fn define_stack_remaining(rust: &RustBuilder, linker_config: &LinkerConfig) {
+    rust.new_func_with_ret("stack_remaining".to_string(), "usize".to_string());
+    if linker_config.target_config.needs_interrupt_stack() {
+        rust.let_assign(
+            "(bottom, _)",
+            "if on_trap_stack() { my_interrupt_stack() } else { my_stack() }",
+        );
+    } else {
+        rust.let_assign("(bottom, _)", "my_stack()");
+    }
+    if linker_config.stack_sentry {
+        rust.implicit_ret(
+            "read_sp().saturating_sub(bottom + core::mem::size_of::<usize>())".to_string(),
+        );
+    } else {
+        rust.implicit_ret("read_sp().saturating_sub(bottom)".to_string());
+    }
+    rust.end_func();
+}
+
+// Trap handling runs on the current hart's interrupt stack if there is one, else on its hart stack,
+// so that is what on_trap_stack() checks against.
+fn define_stack_checks(rust: &RustBuilder, linker_config: &LinkerConfig) {
+    define_read_sp(rust);
+    let trap_stack_fn_name = if linker_config.target_config.needs_interrupt_stack() {
+        "my_interrupt_stack"
+    } else {
+        "my_stack"
+    };
+    define_on_stack(rust, "on_trap_stack", trap_stack_fn_name);
+    define_stack_remaining(rust, linker_config);
+
+    if linker_config.target_config.needs_abort_stack() {
+        define_on_stack(rust, "on_abort_stack", "my_abort_stack");
+    }
+}
+
+// Copy the sections stored in a load region to the memory they run from. Has to be called before
+// their contents are used, e.g. first thing in the boot hart's entrypoint.
+fn define_copy_data_to_ram(rust: &RustBuilder, sections: &[&SectionType]) {
+    for sty in sections {
+        define_get_addr_of(
+            rust,
+            format!("{:#}_load_start", sty.name()),
+            sty.section_entry_load_symbol(),
+        );
+    }
+
+    rust.doc_comment(
+        "# Safety\n\
+         \n\
+         Overwrites the relocated sections with their initial contents, so it must be called\n\
+         once, by a single hart, before anything in them is used.",
+    );
+    rust.new_unsafe_func("copy_data_to_ram".to_string());
+    rust.new_unsafe_block();
+    for sty in sections {
+        rust.call_without_ret(
+            "core::ptr::copy_nonoverlapping".to_string(),
+            vec![
+                format!("{:#}_load_start() as *const u8", sty.name()),
+                format!("{:#}() as *mut u8", region_start_fn_name(sty.name())),
+                format!("{:#}()", region_size_fn_name(sty.name())),
+            ],
+        );
+    }
+    rust.end_unsafe_block();
+    rust.end_func();
+}
+
+// The component embeds the device tree blob by placing it in the .dtb input section, e.g.
+// #[link_section = ".dtb"] static DTB: [u8; N] = *include_bytes!("platform.dtb");
+fn define_embedded_dtb(rust: &RustBuilder) {
+    let dtb = SectionType::Dtb.name();
+
+    rust.new_func_with_ret("embedded_dtb".to_string(), "&'static [u8]".to_string());
+    rust.new_unsafe_block();
+    rust.implicit_ret(format!(
+        "core::slice::from_raw_parts({:#}() as *const u8, {:#}())",
+        region_start_fn_name(dtb),
+        region_size_fn_name(dtb)
+    ));
+    rust.end_unsafe_block();
+    rust.end_func();
 }
 
 fn write_consts_rs_file(
@@ -1293,7 +2424,7 @@ fn write_consts_rs_file(
     let consts_rs_filename = "consts.rs";
     let filepath = dirpath.join(consts_rs_filename);
     let fw = FileWriter::new(filepath.clone(), BlockDelimiter::Parens);
-    let rust = RustBuilder::new();
+    let rust = RustBuilder::new(&linker_config.banner);
 
     rust.new_use("core::ptr::addr_of".to_string());
 
@@ -1314,6 +2445,36 @@ fn write_consts_rs_file(
     rust.static_def(program_start_symbol(), "usize".to_string());
     rust.static_def(program_end_symbol(), "usize".to_string());
 
+    for symbol in &linker_config.symbols {
+        if symbol.has_rust_accessor() {
+            rust.static_def(symbol.name.clone(), "usize".to_string());
+        }
+    }
+
+    let trap_code = "trap_code";
+    let has_text = section_types.contains(&SectionType::Text);
+    if has_text {
+        rust.static_def(trap_code_start_symbol(), "usize".to_string());
+        rust.static_def(trap_code_end_symbol(), "usize".to_string());
+    }
+
+    let abort_stack = "abort_stack";
+    if linker_config.target_config.needs_abort_stack() {
+        rust.static_def(abort_stack_start_symbol(), "usize".to_string());
+        rust.static_def(abort_stack_end_symbol(), "usize".to_string());
+    }
+
+    let interrupt_stack = "interrupt_stack";
+    if linker_config.target_config.needs_interrupt_stack() {
+        rust.static_def(interrupt_stack_start_symbol(), "usize".to_string());
+        rust.static_def(interrupt_stack_end_symbol(), "usize".to_string());
+    }
+
+    let relocated_sections = linker_config.relocated_sections();
+    for sty in &relocated_sections {
+        rust.static_def(sty.section_entry_load_symbol(), "usize".to_string());
+    }
+
     rust.end_extern();
 
     for sty in &section_types {
@@ -1350,7 +2511,81 @@ fn write_consts_rs_file(
     define_get_addr_of(&rust, region_end_fn_name(program), program_end_symbol());
     define_size_of(&rust, program);
 
+    // Provide the bounds of the trap handling code, e.g. for locking it down as execute-only.
+    if has_text {
+        define_get_addr_of(
+            &rust,
+            region_start_fn_name(trap_code),
+            trap_code_start_symbol(),
+        );
+        define_get_addr_of(&rust, region_end_fn_name(trap_code), trap_code_end_symbol());
+        define_size_of(&rust, trap_code);
+    }
+
+    // Provide the bounds of the code and read-only data, e.g. for write-protecting them once
+    // initialization completes
+    if has_text && section_types.contains(&SectionType::Rodata) {
+        let text = SectionType::Text.name();
+        let rodata = SectionType::Rodata.name();
+        rust.new_func_with_ret(
+            "lock_text_rodata".to_string(),
+            "[(usize, usize); 2]".to_string(),
+        );
+        rust.implicit_ret(format!(
+            "[({:#}(), {:#}()), ({:#}(), {:#}())]",
+            region_start_fn_name(text),
+            region_size_fn_name(text),
+            region_start_fn_name(rodata),
+            region_size_fn_name(rodata),
+        ));
+        rust.end_func();
+    }
+
+    if linker_config.target_config.needs_abort_stack() {
+        define_get_addr_of(
+            &rust,
+            region_start_fn_name(abort_stack),
+            abort_stack_start_symbol(),
+        );
+        define_get_addr_of(
+            &rust,
+            region_end_fn_name(abort_stack),
+            abort_stack_end_symbol(),
+        );
+        define_size_of(&rust, abort_stack);
+    }
+
+    if linker_config.target_config.needs_interrupt_stack() {
+        define_get_addr_of(
+            &rust,
+            region_start_fn_name(interrupt_stack),
+            interrupt_stack_start_symbol(),
+        );
+        define_get_addr_of(
+            &rust,
+            region_end_fn_name(interrupt_stack),
+            interrupt_stack_end_symbol(),
+        );
+        define_size_of(&rust, interrupt_stack);
+    }
+
+    // Provide the address of every symbol added to the linker config
+    for symbol in &linker_config.symbols {
+        if symbol.has_rust_accessor() {
+            define_get_addr_of(&rust, symbol.accessor_fn_name(), symbol.name.clone());
+        }
+    }
+
+    if !relocated_sections.is_empty() {
+        define_copy_data_to_ram(&rust, &relocated_sections);
+    }
+
     define_stack_for_hart(&rust, linker_config);
+    define_stack_checks(&rust, linker_config);
+
+    if section_types.contains(&SectionType::Dtb) {
+        define_embedded_dtb(&rust);
+    }
 
     rust.generate(&fw);
 
@@ -1358,16 +2593,25 @@ fn write_consts_rs_file(
     fw.write()
 }
 
+fn export_stack_size(linker_config: &LinkerConfig, root_fw: &FileWriter) {
+    root_fw.add_line("#[allow(dead_code)]");
+    root_fw.add_line(&format!(
+        "pub const STACK_SIZE: usize = {:#x};",
+        linker_config.hart_stack_size()
+    ));
+}
+
 pub fn write_linker_files<'a>(
     dirpath_name: &str,
     linker_config: &'a LinkerConfig<'a>,
     crate_type: CrateType,
 ) -> std::io::Result<()> {
     let dirpath = PathBuf::from(dirpath_name);
-    let root_fw = create_root_rs_filewriter(&dirpath, crate_type);
+    let root_fw = create_root_rs_filewriter(&dirpath, crate_type, &linker_config.banner);
 
     write_linker_ld_file(&dirpath, linker_config)?;
     write_consts_rs_file(&dirpath, linker_config, &root_fw)?;
+    export_stack_size(linker_config, &root_fw);
 
     root_fw.write()
 }
@@ -19,6 +19,10 @@ const SENTRY_VALUE_RV32: u32 = 0x4e45532d;
 
 const STATUS_FS_MASK_DIRTY: usize = 3 << 13;
 const STATUS_FS_CLEAN: usize = 2 << 13;
+const STATUS_VS_MASK_DIRTY: usize = 3 << 9;
+const STATUS_VS_CLEAN: usize = 2 << 9;
+// MPRV, SUM and MXR change the privilege/permissions used by loads and stores
+const STATUS_MEM_ACCESS_MASK: usize = (1 << 17) | (1 << 18) | (1 << 19);
 
 #[derive(Debug, Copy, Clone)]
 #[repr(u8)]
@@ -47,6 +51,7 @@ pub enum RtFlagBit {
     // translation/protection control registers being changed, thereby
     // requiring an sfence.vma to invalidate caches.
     TranslationRegChanged = 2,
+    VsStateWasDirty = 3,
     // This is to ensure that we support both rv32 and rv64 using a single
     // rt_flags field. For now, I don't think we would need more than 32
     // bits to track state.
@@ -70,10 +75,211 @@ impl RtFlagBit {
             "TranslationRegChanged",
             Self::TranslationRegChanged.as_mask() as usize,
         );
+        rust.enum_case_value("VsStateWasDirty", Self::VsStateWasDirty.as_mask() as usize);
         rust.end_enum();
+
+        // Helpers to query and update a raw rt_flags value (e.g. `get_rt_flags() as u32`)
+        // without masking by hand
+        rust.new_impl("RtFlags".to_string());
+        for (name, ret, expr) in [
+            ("contains", "bool", "bits & flag as u32 != 0"),
+            ("set", "u32", "bits | flag as u32"),
+            ("clear", "u32", "bits & !(flag as u32)"),
+        ] {
+            rust.new_const_func_with_arg_and_ret(
+                name.to_string(),
+                "bits: u32, flag: RtFlags".to_string(),
+                ret.to_string(),
+            );
+            rust.implicit_ret(expr.to_string());
+            rust.end_func();
+        }
+        rust.end_impl();
+    }
+}
+
+// Action taken by the trap handler once the trap is taken
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefaultTrapAction {
+    // Create a trap frame and call the trap Rust entrypoint
+    #[default]
+    CallRust,
+    // Execute ebreak so that an attached debugger stops on the trap. Useful for bring-up.
+    Ebreak,
+    // Park the hart
+    Park,
+}
+
+// Mode programmed in the MODE field of the trap vector CSR
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrapVectorMode {
+    // All traps set pc to the trap vector base
+    #[default]
+    Direct,
+    // Interrupts set pc to base + 4 * cause, exceptions still go to the base
+    Vectored,
+}
+
+impl TrapVectorMode {
+    fn mode_bits(&self) -> isize {
+        match self {
+            Self::Direct => 0,
+            Self::Vectored => 1,
+        }
+    }
+}
+
+// How the generated assembly is handed to the Rust compiler
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsmOutput {
+    // Write boot.S and pull it in with `global_asm!(include_str!(...))`
+    #[default]
+    SeparateFile,
+    // Embed the assembly as a string literal in the generated asm.rs, so that no .S file is needed
+    Inline,
+}
+
+// Number of entries in the vectored trap table. Covers all the standard interrupt causes.
+const TRAP_VECTOR_TABLE_ENTRIES: usize = 16;
+
+// Address matching mode of a PMP entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PmpMode {
+    // Top of range, the region is [addr, addr + length)
+    Tor,
+    // Naturally aligned power-of-2 region, of at least 8 bytes
+    Napot,
+}
+
+impl PmpMode {
+    // A field of a pmpcfg entry
+    fn cfg_bits(&self) -> usize {
+        match self {
+            Self::Tor => 1 << 3,
+            Self::Napot => 3 << 3,
+        }
+    }
+}
+
+// Locks a pmpcfg entry until reset, and enforces it in M-mode as well
+const PMP_CFG_LOCK: usize = 1 << 7;
+
+// Value of a pmpaddr register
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PmpSlotAddr {
+    Value(usize),
+    // Address of a linker symbol, resolved at runtime
+    Symbol(String),
+}
+
+// Physical memory protection entry programmed on every hart during reset
+#[derive(Debug, Clone, Copy)]
+pub struct PmpEntry {
+    pub addr: usize,
+    pub length: usize,
+    pub attribs: MemoryAttribs,
+    pub mode: PmpMode,
+}
+
+// How `delay_cycles` waits, as it has to work before any timer is set up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelayMethod {
+    // Count down a busy loop. Each iteration takes the given number of cycles on the target, as
+    // measured during bring-up.
+    CountedLoop(usize),
+    // Poll the cycle counter (Zicntr). In S-mode, it has to be enabled in mcounteren.
+    CycleCounter,
+}
+
+// How `clear_software_interrupt` acknowledges a software interrupt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoftwareInterruptAck {
+    // Clear sip.SSIP. Only writable from S-mode, mip.MSIP is read-only.
+    PendingCsr,
+    // Write 0 to the 32-bit MSIP register of the hart in an array (e.g. CLINT) starting at the given
+    // address and indexed by hart id
+    Msip(usize),
+}
+
+// How `set_timer` arms the timer interrupt of the calling hart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerCompare {
+    // Write the 64-bit mtimecmp register of the hart in an array (e.g. CLINT) starting at `base`,
+    // with the registers of consecutive hart ids `stride` bytes apart
+    Mtimecmp { base: usize, stride: usize },
+    // Call the set timer function of the SBI TIME extension
+    Sbi,
+}
+
+// Trace action emitted on trap entry, as soon as the trap path has a free register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceAction {
+    // Write the trap cause to the XLEN-wide MMIO register at the given address
+    MmioWrite(usize),
+    // Increment a per-hart counter in the thread pointer block, read back with `trap_count()`
+    CounterBump,
+}
+
+// Temporaries used as scratch registers by the generated code
+const DEFAULT_SCRATCH_REGS: [GeneralRegister; 7] = [
+    GeneralRegister::T0,
+    GeneralRegister::T1,
+    GeneralRegister::T2,
+    GeneralRegister::T3,
+    GeneralRegister::T4,
+    GeneralRegister::T5,
+    GeneralRegister::T6,
+];
+
+// Registers that the reset path may overwrite before the Rust entrypoint is called, so that they
+// can't be used to hand off values to the runtime
+const RESET_CLOBBERED_REGS: [GeneralRegister; 11] = [
+    GeneralRegister::Zero,
+    GeneralRegister::Ra,
+    GeneralRegister::Sp,
+    GeneralRegister::Tp,
+    GeneralRegister::T0,
+    GeneralRegister::T1,
+    GeneralRegister::T2,
+    GeneralRegister::T3,
+    GeneralRegister::T4,
+    GeneralRegister::T5,
+    GeneralRegister::T6,
+];
+
+// Number of PMP entries defined by the privileged spec
+const PMP_MAX_ENTRIES: usize = 64;
+
+// Width of the floating point registers, i.e. whether the target implements only the F extension
+// (Single) or the D extension as well (Double)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatWidth {
+    Single,
+    Double,
+}
+
+impl FloatWidth {
+    fn suffix(&self) -> &str {
+        match self {
+            Self::Single => "w",
+            Self::Double => "d",
+        }
     }
 }
 
+// Whether the assembler may use compressed (C extension) encodings for the runtime code
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressedCode {
+    // Leave it to the assembler defaults (as derived from the target arch)
+    #[default]
+    AssemblerDefault,
+    // Emit `.option rvc` so that the runtime code gets compressed, and don't pad the runtime
+    // functions to a 4-byte alignment that would give back the saved space
+    Allow,
+    // Emit `.option norvc` for cores without the C extension
+    Disallow,
+}
+
 #[derive(Debug, Eq, PartialEq, Hash)]
 pub enum EntrypointType {
     BootHart,
@@ -81,6 +287,37 @@ pub enum EntrypointType {
     Trap,
     CustomReset,
     StackOverflow,
+    Abort,
+    SoftwareInterrupt,
+    // Optional entrypoints of the timer and external interrupts targeting the current mode, which
+    // are otherwise handled by the Trap entrypoint like SoftwareInterrupt
+    TimerInterrupt,
+    ExternalInterrupt,
+    // Optional hook called on the boot hart before BSS is cleared, e.g. to bring up a UART
+    EarlyConsole,
+}
+
+// How handle_trap picks the entrypoint of the interrupts which have their own
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CauseDispatch {
+    // A compare and branch per interrupt with its own entrypoint, for targets where data
+    // referenced from the trap path or its relocations are a problem
+    #[default]
+    CompareChain,
+    // A load from a table of entrypoint addresses in .rodata indexed by the interrupt code, which
+    // takes the same time whatever the number of entrypoints
+    JumpTable,
+}
+
+// How the generated spinlocks try to take the lock
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpinlockAcquire {
+    // A single amoswap of the locked value
+    #[default]
+    Amoswap,
+    // A load-reserved/store-conditional pair, for targets whose AMOs are only supported on some
+    // memories or are slower than LR/SC
+    LrSc,
 }
 
 #[derive(Debug)]
@@ -94,7 +331,72 @@ pub struct RtConfig {
     stack_overflow_detection: bool,
     supports_atomic_extension: bool,
     floating_point_support: bool,
+    float_width: FloatWidth,
     sfence_on_trapframe_restore_feature: bool,
+    boot_magic: Option<(GeneralRegister, usize)>, // (handoff register, expected value)
+    secondary_arg: Option<GeneralRegister>,
+    // Pass (boot id, hart id, incoming a1) to the Rust entrypoints in a0-a2
+    entrypoint_args: bool,
+    mcountinhibit: Option<usize>,
+    // Values written to menvcfg/mseccfg during reset, in M-mode
+    menvcfg: Option<usize>,
+    mseccfg: Option<usize>,
+    // Value written to satp during reset, in S-mode
+    satp: Option<usize>,
+    // Exceptions/interrupts delegated to S-mode, 0 to handle everything in M-mode
+    medeleg_mask: usize,
+    mideleg_mask: usize,
+    // Interrupts enabled in mie/sie at boot, after the trap CSRs are zeroed
+    ie_mask: usize,
+    // Clear mstatus.MPRV/SUM/MXR first thing on reset
+    clear_mprv: bool,
+    default_trap_action: DefaultTrapAction,
+    compressed_code: CompressedCode,
+    trap_vector_mode: TrapVectorMode,
+    cause_dispatch: CauseDispatch,
+    gp_independent: bool,
+    delay_method: Option<DelayMethod>,
+    // Version string printed by print_boot_banner()
+    boot_banner_version: Option<String>,
+    software_interrupt_ack: Option<SoftwareInterruptAck>,
+    timer_compare: Option<TimerCompare>,
+    trap_entry_trace: Option<TraceAction>,
+    // Number of 4-byte NOPs at the start of handle_trap, for a patcher to overwrite
+    trap_entry_patch_slots: usize,
+    // (hart id shift, group count) to keep one BSS init done flag per group of harts
+    bss_init_groups: Option<(usize, usize)>,
+    // Hold non-boot harts until the boot hart calls release_secondaries()
+    secondary_release_gate: bool,
+    // Generate a spinlock to serialize one-time init steps across harts
+    boot_lock: bool,
+    boot_lock_acquire: SpinlockAcquire,
+    // Registers the generated code may use as scratch, DEFAULT_SCRATCH_REGS unless restricted
+    scratch_regs: Vec<GeneralRegister>,
+    // (start symbol, end symbol) of the range whose checksum is verified at boot
+    boot_checksum: Option<(String, String)>,
+    pmp_entries: Vec<PmpEntry>,
+    // Grant full access to all memory with the last PMP entry
+    pmp_grant_all: bool,
+    // Make .text read+execute-only in all modes with the first PMP entries
+    pmp_lock_text: bool,
+    // Trap frame and tp block members whose accessors use the VAddr newtype instead of usize
+    address_members: Vec<String>,
+    asm_output: AsmOutput,
+    // Access the trap frame in descending address order when saving and restoring it
+    reverse_trap_frame_access: bool,
+    // Address the trap frame through a dedicated scratch register (if free) on restore
+    trap_frame_base_reg: bool,
+    // Only reload tp from the trap frame when returning to a lower mode or another context
+    skip_same_context_tp_restore: bool,
+    // Generate a fence_tso() helper
+    fence_tso: bool,
+    // Start global labels with a landing pad (Zicfilp) and advertise it in .note.gnu.property
+    cfi: bool,
+    // Describe the trap frame with DWARF CFI so that debuggers can unwind out of the trap handler
+    unwind_info: bool,
+    // Size of a vector register in bytes (VLEN / 8), 0 if vector support is not enabled
+    vector_register_bytes: usize,
+    banner: String,
 }
 
 impl RtConfig {
@@ -109,8 +411,16 @@ impl RtConfig {
         stack_overflow_detection: bool,
         supports_atomic_extension: bool,
         floating_point_support: bool,
+        float_width: FloatWidth,
         sfence_on_trapframe_restore_feature: bool,
     ) -> Self {
+        assert!(
+            !floating_point_support
+                || float_width == FloatWidth::Single
+                || target_config.rv_xlen() != RvXlen::Rv32,
+            "Double-precision floating point registers don't fit in the RV32 trap frame"
+        );
+
         let mut s = Self {
             entrypoints,
             trap_frame,
@@ -121,44 +431,53 @@ impl RtConfig {
             stack_overflow_detection,
             supports_atomic_extension,
             floating_point_support,
+            float_width,
             sfence_on_trapframe_restore_feature,
+            boot_magic: None,
+            secondary_arg: None,
+            entrypoint_args: false,
+            mcountinhibit: None,
+            menvcfg: None,
+            mseccfg: None,
+            satp: None,
+            medeleg_mask: 0,
+            mideleg_mask: 0,
+            ie_mask: 0,
+            clear_mprv: false,
+            default_trap_action: DefaultTrapAction::default(),
+            compressed_code: CompressedCode::default(),
+            trap_vector_mode: TrapVectorMode::default(),
+            cause_dispatch: CauseDispatch::default(),
+            gp_independent: false,
+            scratch_regs: DEFAULT_SCRATCH_REGS.to_vec(),
+            delay_method: None,
+            boot_banner_version: None,
+            software_interrupt_ack: None,
+            timer_compare: None,
+            trap_entry_trace: None,
+            trap_entry_patch_slots: 0,
+            bss_init_groups: None,
+            secondary_release_gate: false,
+            boot_lock: false,
+            boot_lock_acquire: SpinlockAcquire::default(),
+            boot_checksum: None,
+            pmp_entries: Vec::new(),
+            pmp_grant_all: false,
+            pmp_lock_text: false,
+            address_members: Vec::new(),
+            asm_output: AsmOutput::default(),
+            reverse_trap_frame_access: false,
+            trap_frame_base_reg: false,
+            skip_same_context_tp_restore: false,
+            fence_tso: false,
+            cfi: false,
+            unwind_info: false,
+            vector_register_bytes: 0,
+            banner: auto_generate_banner(),
         };
 
         if floating_point_support {
-            for fr in [
-                FloatingPointRegister::F0,
-                FloatingPointRegister::F1,
-                FloatingPointRegister::F2,
-                FloatingPointRegister::F3,
-                FloatingPointRegister::F4,
-                FloatingPointRegister::F5,
-                FloatingPointRegister::F6,
-                FloatingPointRegister::F7,
-                FloatingPointRegister::F8,
-                FloatingPointRegister::F9,
-                FloatingPointRegister::F10,
-                FloatingPointRegister::F11,
-                FloatingPointRegister::F12,
-                FloatingPointRegister::F13,
-                FloatingPointRegister::F14,
-                FloatingPointRegister::F15,
-                FloatingPointRegister::F16,
-                FloatingPointRegister::F17,
-                FloatingPointRegister::F18,
-                FloatingPointRegister::F19,
-                FloatingPointRegister::F20,
-                FloatingPointRegister::F21,
-                FloatingPointRegister::F22,
-                FloatingPointRegister::F23,
-                FloatingPointRegister::F24,
-                FloatingPointRegister::F25,
-                FloatingPointRegister::F26,
-                FloatingPointRegister::F27,
-                FloatingPointRegister::F28,
-                FloatingPointRegister::F29,
-                FloatingPointRegister::F30,
-                FloatingPointRegister::F31,
-            ] {
+            for fr in FLOATING_POINT_REGISTERS {
                 if !s.trap_frame.floating_point_registers.contains(&fr) {
                     s.trap_frame.floating_point_registers.push(fr);
                 }
@@ -169,11 +488,657 @@ impl RtConfig {
             }
         }
 
+        // The conditional sfence.vma on restore compares against the satp saved in the trap frame
+        if sfence_on_trapframe_restore_feature && !s.trap_frame.csrs.contains(&Csr::Satp) {
+            s.trap_frame.csrs.push(Csr::Satp);
+        }
+
+        if s.target_config.needs_interrupt_stack() {
+            s.tp_block.members.push(TpBlockMember::InterruptModeStack);
+        }
+
+        s.trap_frame.resolve_hot_members();
+        s.trap_frame.validate_member_order();
+        s.validate_entrypoints();
+        assert!(
+            s.target_config.reset_entry_load_region().is_none()
+                || !s.multihart_reset_handling_required()
+                || s.supports_atomic_extension(),
+            "Copying the reset entry with all harts starting at the reset vector requires the atomic extension"
+        );
+
         s
     }
 
+    // Use the builder pattern to validate a boot protocol magic value handed off by the previous
+    // component in `reg`. On mismatch, the abort entrypoint is called with the expected value in
+    // a0 and the actual value in a1.
+    pub fn with_boot_magic(mut self, reg: GeneralRegister, value: usize) -> Self {
+        assert!(
+            !RESET_CLOBBERED_REGS.contains(&reg),
+            "Boot magic register {reg:#} is clobbered by the reset path"
+        );
+        assert!(
+            self.entrypoints.contains_key(&EntrypointType::Abort),
+            "Missing Abort entrypoint, required by the boot magic check"
+        );
+        self.boot_magic = Some((reg, value));
+        self
+    }
+
+    // Use the builder pattern to capture the argument (e.g. a per-hart parameter block pointer)
+    // handed to non-boot harts in `reg`. It is stashed in the thread pointer block early in the
+    // reset path and can be read back with `secondary_arg()`. It reads as 0 on the boot hart.
+    pub fn with_secondary_arg(mut self, reg: GeneralRegister) -> Self {
+        assert!(
+            self.is_multi_hart(),
+            "A secondary argument requires more than one hart"
+        );
+        assert!(
+            !RESET_CLOBBERED_REGS.contains(&reg),
+            "Secondary argument register {reg:#} is clobbered by the reset path"
+        );
+        self.secondary_arg = Some(reg);
+        if !self.tp_block.members.contains(&TpBlockMember::SecondaryArg) {
+            self.tp_block.members.push(TpBlockMember::SecondaryArg);
+        }
+        self
+    }
+
+    // Use the builder pattern to call the boot and non-boot hart Rust entrypoints as
+    // `EntrypointFn`, i.e. with the boot id in a0, the hart id in a1 and the value of a1 at reset
+    // in a2. That is the DTB pointer handed over by the SBI implementation in S-mode, and by the
+    // previous boot stage on most M-mode platforms. A custom reset entrypoint has to preserve a1.
+    pub fn with_entrypoint_args(mut self) -> Self {
+        self.entrypoint_args = true;
+        if !self.tp_block.members.contains(&TpBlockMember::BootArg) {
+            self.tp_block.members.push(TpBlockMember::BootArg);
+        }
+        self
+    }
+
+    // Use the builder pattern to delegate the exceptions set in `mask` to S-mode, e.g. page faults
+    // for an S-mode payload
+    pub fn with_medeleg_mask(mut self, mask: usize) -> Self {
+        assert!(
+            self.rv_mode() == RvMode::MMode,
+            "medeleg can only be written in M-mode"
+        );
+        self.medeleg_mask = mask;
+        self
+    }
+
+    // Use the builder pattern to delegate the interrupts set in `mask` to S-mode
+    pub fn with_mideleg_mask(mut self, mask: usize) -> Self {
+        assert!(
+            self.rv_mode() == RvMode::MMode,
+            "mideleg can only be written in M-mode"
+        );
+        self.mideleg_mask = mask;
+        self
+    }
+
+    // Use the builder pattern to enable the interrupts set in `mask` (e.g. MTIP and MSIP) in mie/sie
+    // at boot. Interrupts stay globally disabled until the consumer sets status.xIE.
+    pub fn with_ie_mask(mut self, mask: usize) -> Self {
+        self.ie_mask = mask;
+        self
+    }
+
+    // Use the builder pattern to clear mstatus.MPRV (along with SUM and MXR) at the very start of
+    // reset, in case the previous boot stage left loads and stores translated or permission
+    // checked as another privilege mode
+    pub fn with_clear_mprv(mut self) -> Self {
+        assert!(
+            self.rv_mode() == RvMode::MMode,
+            "MPRV can only be cleared in M-mode"
+        );
+        self.clear_mprv = true;
+        self
+    }
+
+    // Use the builder pattern to inhibit the counters set in `mask` on every hart during reset.
+    pub fn with_mcountinhibit(mut self, mask: usize) -> Self {
+        assert!(
+            self.rv_mode() == RvMode::MMode,
+            "mcountinhibit can only be written in M-mode"
+        );
+        self.mcountinhibit = Some(mask);
+        self
+    }
+
+    // Use the builder pattern to write `value` to menvcfg on every hart during reset, e.g. to
+    // enable cache-block operations in lower modes. On RV32, the upper 32 bits go to menvcfgh.
+    pub fn with_menvcfg(mut self, value: usize) -> Self {
+        assert!(
+            self.rv_mode() == RvMode::MMode,
+            "menvcfg can only be written in M-mode"
+        );
+        self.menvcfg = Some(value);
+        self
+    }
+
+    // Use the builder pattern to write `value` to mseccfg on every hart during reset, once the PMP
+    // entries are programmed (e.g. so that Smepmp's MML doesn't lock M-mode out of the reset code).
+    // On RV32, the upper 32 bits go to mseccfgh.
+    pub fn with_mseccfg(mut self, value: usize) -> Self {
+        assert!(
+            self.rv_mode() == RvMode::MMode,
+            "mseccfg can only be written in M-mode"
+        );
+        self.mseccfg = Some(value);
+        self
+    }
+
+    // Use the builder pattern to write `satp` (e.g. 0 to start with paging off, or a mode and
+    // ASID) on every hart during reset, followed by an sfence.vma
+    pub fn with_satp(mut self, satp: usize) -> Self {
+        assert!(
+            self.rv_mode() == RvMode::SMode,
+            "satp is only initialized by an S-mode runtime"
+        );
+        self.satp = Some(satp);
+        self
+    }
+
+    // Use the builder pattern to program the given PMP entries, in order, on every hart during
+    // reset. A TOR entry takes up an additional pmpaddr slot for its base address, unless the
+    // base is 0 in the first slot or matches the end of the previous TOR entry.
+    pub fn with_pmp_entries(mut self, entries: Vec<PmpEntry>) -> Self {
+        assert!(
+            self.rv_mode() == RvMode::MMode,
+            "PMP can only be programmed in M-mode"
+        );
+        for entry in &entries {
+            match entry.mode {
+                PmpMode::Napot => {
+                    assert!(
+                        entry.length >= 8 && is_power_of_2(entry.length),
+                        "PMP entry at {:#x} has a NAPOT length {:#x} which is not a power-of-2 of at least 8 bytes",
+                        entry.addr,
+                        entry.length
+                    );
+                    assert!(
+                        entry.addr % entry.length == 0,
+                        "PMP entry at {:#x} is not aligned to its NAPOT length {:#x}",
+                        entry.addr,
+                        entry.length
+                    );
+                }
+                PmpMode::Tor => {
+                    assert!(
+                        entry.length != 0
+                            && entry.addr % 4 == 0
+                            && entry.length % 4 == 0
+                            && entry.addr.checked_add(entry.length).is_some(),
+                        "PMP entry at {:#x} has a TOR range which is empty, not 4-byte aligned or overflows",
+                        entry.addr
+                    );
+                }
+            }
+        }
+        self.pmp_entries = entries;
+        assert!(
+            self.pmp_slots().len() <= PMP_MAX_ENTRIES,
+            "PMP entries need more than {PMP_MAX_ENTRIES} pmpaddr registers"
+        );
+        self
+    }
+
+    // Use the builder pattern to program a PMP entry granting RWX access to all memory on every
+    // hart during reset, so that lower privilege modes can run. It takes the slot after the entries
+    // given to with_pmp_entries(), which have priority over it.
+    pub fn with_pmp_grant_all(mut self) -> Self {
+        assert!(
+            self.rv_mode() == RvMode::MMode,
+            "PMP can only be programmed in M-mode"
+        );
+        self.pmp_grant_all = true;
+        assert!(
+            self.pmp_slots().len() <= PMP_MAX_ENTRIES,
+            "PMP entries need more than {PMP_MAX_ENTRIES} pmpaddr registers"
+        );
+        self
+    }
+
+    // Use the builder pattern to make .text read+execute-only on every hart, including M-mode,
+    // with a locked PMP entry. It takes the first slots so that it has priority over the entries
+    // given to with_pmp_entries(). The entry can't be changed until the next reset.
+    pub fn with_pmp_lock_text(mut self) -> Self {
+        assert!(
+            self.rv_mode() == RvMode::MMode,
+            "PMP can only be programmed in M-mode"
+        );
+        self.pmp_lock_text = true;
+        assert!(
+            self.pmp_slots().len() <= PMP_MAX_ENTRIES,
+            "PMP entries need more than {PMP_MAX_ENTRIES} pmpaddr registers"
+        );
+        self
+    }
+
+    // (pmpaddr value, pmpcfg byte) for each PMP slot in use
+    fn pmp_slots(&self) -> Vec<(PmpSlotAddr, usize)> {
+        let mut slots: Vec<(PmpSlotAddr, usize)> = Vec::new();
+        if self.pmp_lock_text {
+            slots.push((
+                PmpSlotAddr::Symbol(SectionType::Text.section_entry_start_symbol()),
+                0,
+            ));
+            slots.push((
+                PmpSlotAddr::Symbol(SectionType::Text.section_entry_end_symbol()),
+                PMP_CFG_LOCK | PmpMode::Tor.cfg_bits() | MemoryAttribs::rx().pmp_permission_bits(),
+            ));
+        }
+        for entry in &self.pmp_entries {
+            let cfg = entry.mode.cfg_bits() | entry.attribs.pmp_permission_bits();
+            match entry.mode {
+                PmpMode::Napot => {
+                    slots.push((
+                        PmpSlotAddr::Value((entry.addr + (entry.length / 2 - 1)) >> 2),
+                        cfg,
+                    ));
+                }
+                PmpMode::Tor => {
+                    let base = PmpSlotAddr::Value(entry.addr >> 2);
+                    let prev_top = match slots.last() {
+                        Some((addr, cfg))
+                            if (cfg & PmpMode::Napot.cfg_bits()) == PmpMode::Tor.cfg_bits() =>
+                        {
+                            Some(addr.clone())
+                        }
+                        Some(_) => None,
+                        None => Some(PmpSlotAddr::Value(0)),
+                    };
+                    if prev_top != Some(base.clone()) {
+                        // The base of a TOR region is the previous pmpaddr, add an entry which
+                        // doesn't match anything to hold it
+                        slots.push((base, 0));
+                    }
+                    slots.push((PmpSlotAddr::Value((entry.addr + entry.length) >> 2), cfg));
+                }
+            }
+        }
+        if self.pmp_grant_all {
+            // An all ones NAPOT address matches the whole address space
+            let all_ones = match self.rv_xlen() {
+                RvXlen::Rv32 => u32::MAX as usize,
+                _ => usize::MAX,
+            };
+            slots.push((
+                PmpSlotAddr::Value(all_ones),
+                PmpMode::Napot.cfg_bits() | MemoryAttribs::rwx().pmp_permission_bits(),
+            ));
+        }
+        slots
+    }
+
+    // Use the builder pattern to select what the trap handler does on a trap
+    pub fn with_default_trap_action(mut self, action: DefaultTrapAction) -> Self {
+        self.default_trap_action = action;
+        self
+    }
+
+    // Use the builder pattern to control the use of compressed instructions in the runtime code
+    pub fn with_compressed_code(mut self, compressed_code: CompressedCode) -> Self {
+        self.compressed_code = compressed_code;
+        self
+    }
+
+    // Use the builder pattern to keep the generated code from depending on gp, by disabling linker
+    // relaxation for it. The generated helpers can then be called before gp is set up, or from code
+    // built without gp (e.g. with `-mno-relax`).
+    pub fn with_gp_independent_code(mut self) -> Self {
+        self.gp_independent = true;
+        self
+    }
+
+    // Use the builder pattern to restrict the registers used as scratch by the generated code to
+    // `regs`, e.g. to leave a register reserved by the ABI of the component alone. Generation
+    // panics if a routine needs more scratch registers than provided.
+    pub fn with_scratch_regs(mut self, regs: &[GeneralRegister]) -> Self {
+        assert!(!regs.is_empty(), "No scratch registers provided");
+        for reg in regs {
+            assert!(
+                DEFAULT_SCRATCH_REGS.contains(reg),
+                "Scratch register {reg:#} is not one of {DEFAULT_SCRATCH_REGS:?}"
+            );
+        }
+        self.scratch_regs = regs.to_vec();
+        self
+    }
+
+    // Use the builder pattern to generate a `print_boot_banner(f)` helper, printing `version`
+    // along with the hart and boot ids of the calling hart through the consumer's `f`
+    pub fn with_boot_banner(mut self, version: &str) -> Self {
+        self.boot_banner_version = Some(version.to_string());
+        self
+    }
+
+    // Use the builder pattern to generate a `delay_cycles(n)` helper busy-waiting for at least `n`
+    // cycles using `method`
+    pub fn with_delay_cycles(mut self, method: DelayMethod) -> Self {
+        if let DelayMethod::CountedLoop(cycles_per_loop) = method {
+            assert!(cycles_per_loop != 0, "Delay loop can't take 0 cycles");
+        }
+        self.delay_method = Some(method);
+        self
+    }
+
+    // Use the builder pattern to generate a `clear_software_interrupt()` helper acknowledging a
+    // software interrupt using `ack`. It returns whether the interrupt was pending.
+    pub fn with_software_interrupt_ack(mut self, ack: SoftwareInterruptAck) -> Self {
+        if ack == SoftwareInterruptAck::PendingCsr {
+            assert!(
+                self.rv_mode() == RvMode::SMode,
+                "mip.MSIP is read-only, use SoftwareInterruptAck::Msip in M-mode"
+            );
+        }
+        self.software_interrupt_ack = Some(ack);
+        self
+    }
+
+    // Use the builder pattern to generate a `set_timer(deadline)` helper arming the timer interrupt
+    // of the calling hart for when the time counter reaches `deadline`, using `timer`
+    pub fn with_set_timer(mut self, timer: TimerCompare) -> Self {
+        match timer {
+            TimerCompare::Mtimecmp { .. } => assert!(
+                self.rv_mode() == RvMode::MMode,
+                "mtimecmp is not accessible from S-mode, use TimerCompare::Sbi"
+            ),
+            TimerCompare::Sbi => assert!(
+                self.rv_mode() == RvMode::SMode,
+                "SBI calls can only be made from S-mode"
+            ),
+        }
+        self.timer_compare = Some(timer);
+        self
+    }
+
+    // Use the builder pattern to trace every trap taken with `action`, e.g. for a low-overhead view
+    // of the trap frequency without a full handler
+    pub fn with_trap_entry_trace(mut self, action: TraceAction) -> Self {
+        if action == TraceAction::CounterBump
+            && !self.tp_block.members.contains(&TpBlockMember::TrapCount)
+        {
+            self.tp_block.members.push(TpBlockMember::TrapCount);
+        }
+        self.trap_entry_trace = Some(action);
+        self
+    }
+
+    // Use the builder pattern to start handle_trap with `slots` uncompressed NOPs, which live
+    // patching or tracing instrumentation can overwrite with a jump. Each slot is 4-byte aligned.
+    pub fn with_trap_entry_patch_slots(mut self, slots: usize) -> Self {
+        self.trap_entry_patch_slots = slots;
+        self
+    }
+
+    // Use the builder pattern to keep one BSS init done flag per group of harts (e.g. a cluster)
+    // instead of a global one. The group of a hart is its hart id shifted right by
+    // `hart_id_shift`, harts of groups from `group_count` on are parked. The first hart of each
+    // group to start clears the BSS of its group and marks it done, so harts only wait for their
+    // own group. Group 0 owns the main BSS, group N > 0 the optional section `bss_groupN` (e.g. a
+    // Custom section). The boot hart also waits for group 0. Harts of the other groups may enter
+    // Rust before the main BSS, stacks in BSS included, is cleared: they must not use it until
+    // told so, e.g. with `with_secondary_release_gate()`.
+    pub fn with_bss_init_groups(mut self, hart_id_shift: usize, group_count: usize) -> Self {
+        assert!(
+            self.is_multi_hart(),
+            "BSS init groups require a multi-hart configuration"
+        );
+        assert!(
+            !self.skip_bss_clearing,
+            "BSS init groups require BSS clearing"
+        );
+        assert!(
+            self.supports_atomic_extension(),
+            "BSS init groups require the atomic extension"
+        );
+        assert!(group_count != 0, "BSS init group count cannot be 0");
+        self.bss_init_groups = Some((hart_id_shift, group_count));
+        self
+    }
+
+    // Use the builder pattern to hold non-boot harts, once BSS init is done, until the boot hart
+    // calls `release_secondaries()`
+    pub fn with_secondary_release_gate(mut self) -> Self {
+        assert!(
+            self.is_multi_hart(),
+            "Secondary release gate requires a multi-hart configuration"
+        );
+        self.secondary_release_gate = true;
+        self
+    }
+
+    // Use the builder pattern to generate `boot_lock_acquire()` and `boot_lock_release()`, a
+    // spinlock usable as soon as Rust is reached to serialize one-time init steps (e.g. bring-up
+    // logging or shared table setup) across harts
+    pub fn with_boot_lock(mut self) -> Self {
+        assert!(
+            self.is_multi_hart(),
+            "Boot lock requires a multi-hart configuration"
+        );
+        assert!(
+            self.supports_atomic_extension(),
+            "Boot lock requires the atomic extension"
+        );
+        self.boot_lock = true;
+        self
+    }
+
+    // Use the builder pattern to select how `boot_lock_acquire()` tries to take the boot lock
+    pub fn with_boot_lock_acquire(mut self, acquire: SpinlockAcquire) -> Self {
+        assert!(
+            self.boot_lock,
+            "Boot lock acquire sequence requires the boot lock"
+        );
+        self.boot_lock_acquire = acquire;
+        self
+    }
+
+    fn scratch_regs(&self) -> &[GeneralRegister] {
+        &self.scratch_regs
+    }
+
+    fn needs_scoped_options(&self) -> bool {
+        self.compressed_code != CompressedCode::AssemblerDefault || self.gp_independent
+    }
+
+    // Use the builder pattern to verify the checksum (wrapping sum of XLEN words) of the range
+    // [start_symbol, end_symbol) on every hart during reset. The expected value is read from the
+    // `_boot_checksum` placeholder, which has to be patched in the image after linking. On mismatch,
+    // the abort entrypoint is called with the expected value in a0 and the computed one in a1.
+    pub fn with_boot_checksum(mut self, start_symbol: &str, end_symbol: &str) -> Self {
+        assert!(
+            self.entrypoints.contains_key(&EntrypointType::Abort),
+            "Missing Abort entrypoint, required by the boot checksum check"
+        );
+        self.boot_checksum = Some((start_symbol.to_string(), end_symbol.to_string()));
+        self
+    }
+
+    // Use the builder pattern to select direct or vectored trap entry
+    pub fn with_trap_vector_mode(mut self, mode: TrapVectorMode) -> Self {
+        self.trap_vector_mode = mode;
+        self
+    }
+
+    // Use the builder pattern to select how the interrupts with their own entrypoint are routed
+    pub fn with_cause_dispatch(mut self, dispatch: CauseDispatch) -> Self {
+        self.cause_dispatch = dispatch;
+        self
+    }
+
+    // Use the builder pattern to generate a `fence_tso()` helper ordering memory accesses with
+    // total-store-order semantics (fence.tso)
+    pub fn with_fence_tso(mut self) -> Self {
+        self.fence_tso = true;
+        self
+    }
+
+    // Use the builder pattern to start every global entrypoint and function with an unlabeled
+    // landing pad (`lpad 0`) so that they are valid indirect call targets with Zicfilp, and to mark
+    // the object as landing pad aware in its .note.gnu.property section
+    pub fn with_cfi(mut self) -> Self {
+        assert!(
+            self.rv_xlen() != RvXlen::Rv128,
+            "CFI is not supported for RV128"
+        );
+        self.cfi = true;
+        self
+    }
+
+    // Use the builder pattern to emit DWARF call frame information describing the trap frame at
+    // the return address of the Rust trap entrypoint, so that a debugger can unwind from the trap
+    // handler into the interrupted code
+    pub fn with_unwind_info(mut self) -> Self {
+        self.unwind_info = true;
+        self
+    }
+
+    // Use the builder pattern to generate accessors taking and returning a `VAddr` newtype rather
+    // than usize for the given trap frame or tp block members (e.g. "sp", "mepc", "curr_context").
+    // The layout of the structures is unchanged.
+    pub fn with_address_members(mut self, members: &[&str]) -> Self {
+        let trap_frame_members = self.trap_frame_members();
+        let tp_block_members = self.tp_block.members();
+        for member in members {
+            assert!(
+                trap_frame_members.iter().any(|m| m == member)
+                    || tp_block_members.iter().any(|m| m == member),
+                "{member:?} is not a trap frame or tp block member"
+            );
+        }
+        self.address_members = members.iter().map(|m| m.to_string()).collect();
+        self
+    }
+
+    fn is_address_member(&self, member: &str) -> bool {
+        self.address_members.iter().any(|m| m == member)
+    }
+
+    // Use the builder pattern to select whether the assembly goes into boot.S or inline in asm.rs
+    pub fn with_asm_output(mut self, asm_output: AsmOutput) -> Self {
+        self.asm_output = asm_output;
+        self
+    }
+
+    // Use the builder pattern to save and restore the trap frame members in descending address
+    // order, matching stack growth. The layout of the trap frame is unchanged. This is only a
+    // tuning knob for microarchitectures whose store buffer prefers that order.
+    pub fn with_reverse_trap_frame_access(mut self) -> Self {
+        self.reverse_trap_frame_access = true;
+        self
+    }
+
+    // Use the builder pattern to address the trap frame through a dedicated register instead of sp
+    // while saving and restoring the CSRs and runtime state, if a scratch register is free to hold
+    // it. On restore, this keeps sp untouched until the general registers are restored.
+    pub fn with_trap_frame_base_reg(mut self) -> Self {
+        self.trap_frame_base_reg = true;
+        self
+    }
+
+    // Use the builder pattern to skip reloading tp from the trap frame when returning to the
+    // interrupted context in the current mode, where tp already points to the thread pointer
+    // block. tp is still restored when returning to a lower mode or when the rt flags say that
+    // the trap frame belongs to another context. Changes made to the saved tp of such a frame are
+    // ignored.
+    pub fn with_skip_same_context_tp_restore(mut self) -> Self {
+        self.skip_same_context_tp_restore = true;
+        self
+    }
+
+    // Use the builder pattern to enable saving and restoring the vector state (V extension) in
+    // the trap frame. `vlen_bits` is the VLEN of the target harts.
+    pub fn with_vector_support(mut self, vlen_bits: usize) -> Self {
+        assert!(
+            vlen_bits.is_power_of_two() && vlen_bits >= 32,
+            "Invalid VLEN {vlen_bits:#}"
+        );
+        self.vector_register_bytes = vlen_bits / 8;
+
+        for vr in [
+            VectorRegister::V0,
+            VectorRegister::V1,
+            VectorRegister::V2,
+            VectorRegister::V3,
+            VectorRegister::V4,
+            VectorRegister::V5,
+            VectorRegister::V6,
+            VectorRegister::V7,
+            VectorRegister::V8,
+            VectorRegister::V9,
+            VectorRegister::V10,
+            VectorRegister::V11,
+            VectorRegister::V12,
+            VectorRegister::V13,
+            VectorRegister::V14,
+            VectorRegister::V15,
+            VectorRegister::V16,
+            VectorRegister::V17,
+            VectorRegister::V18,
+            VectorRegister::V19,
+            VectorRegister::V20,
+            VectorRegister::V21,
+            VectorRegister::V22,
+            VectorRegister::V23,
+            VectorRegister::V24,
+            VectorRegister::V25,
+            VectorRegister::V26,
+            VectorRegister::V27,
+            VectorRegister::V28,
+            VectorRegister::V29,
+            VectorRegister::V30,
+            VectorRegister::V31,
+        ] {
+            if !self.trap_frame.vector_registers.contains(&vr) {
+                self.trap_frame.vector_registers.push(vr);
+            }
+        }
+
+        self
+    }
+
+    fn vector_support(&self) -> bool {
+        self.vector_register_bytes != 0
+    }
+
+    // Use the builder pattern to replace the banner placed at the top of every generated file
+    pub fn with_banner(mut self, banner: &str) -> Self {
+        self.banner = banner.to_string();
+        self
+    }
+
     fn trap_frame_size(&self) -> isize {
         self.trap_frame.element_count() * self.xlen_bytes()
+            + self.trap_frame.vector_registers.len() as isize * self.vector_register_bytes()
+    }
+
+    fn vector_register_bytes(&self) -> isize {
+        self.vector_register_bytes as isize
+    }
+
+    fn vector_csr_offset(&self, csr: Csr) -> isize {
+        self.trap_frame.vector_csr_idx(csr) * self.xlen_bytes()
+    }
+
+    // Vector registers are VLEN wide, so they are placed at the very end of the trap frame, after
+    // all the XLEN sized elements.
+    fn vector_register_offset(&self, idx: usize) -> isize {
+        self.trap_frame.element_count() * self.xlen_bytes()
+            + idx as isize * self.vector_register_bytes()
+    }
+
+    // fcsr is part of the FP state, so it is only saved and restored along with the FP registers
+    // when FS is dirty
+    fn is_saved_with_fp_state(&self, csr: Csr) -> bool {
+        self.floating_point_support && csr == Csr::Fcsr
+    }
+
+    fn fcsr_offset(&self) -> isize {
+        self.trap_frame.csr_idx(Csr::Fcsr) * self.xlen_bytes()
     }
 
     fn status_reg_offset(&self) -> isize {
@@ -208,26 +1173,97 @@ impl RtConfig {
         self.target_config.per_hart_stack_size()
     }
 
+    // Entrypoints referenced by the generated code have to be provided up front, otherwise the
+    // failure only shows up as an unresolved symbol at link time
+    fn validate_entrypoints(&self) {
+        let required = [
+            (EntrypointType::BootHart, true),
+            (
+                EntrypointType::NonBootHart,
+                self.target_config.is_multi_hart(),
+            ),
+            (EntrypointType::Trap, true),
+            (
+                EntrypointType::CustomReset,
+                self.target_config.needs_custom_reset(),
+            ),
+            (
+                EntrypointType::StackOverflow,
+                self.needs_stack_overflow_detection(),
+            ),
+        ];
+
+        for (ty, needed) in required {
+            if needed {
+                self.required_entrypoint(&ty);
+            }
+        }
+    }
+
+    fn required_entrypoint(&self, ty: &EntrypointType) -> &str {
+        self.entrypoints
+            .get(ty)
+            .unwrap_or_else(|| panic!("Missing {ty:?} entrypoint, required by this configuration"))
+    }
+
     fn boot_hart_rust_entrypoint(&self) -> &str {
-        self.entrypoints.get(&EntrypointType::BootHart).unwrap()
+        self.required_entrypoint(&EntrypointType::BootHart)
     }
 
     fn nonboot_hart_rust_entrypoint(&self) -> &str {
-        self.entrypoints.get(&EntrypointType::NonBootHart).unwrap()
+        self.required_entrypoint(&EntrypointType::NonBootHart)
     }
 
     fn trap_rust_entrypoint(&self) -> &str {
-        self.entrypoints.get(&EntrypointType::Trap).unwrap()
+        self.required_entrypoint(&EntrypointType::Trap)
     }
 
     fn custom_reset_entrypoint(&self) -> &str {
-        self.entrypoints.get(&EntrypointType::CustomReset).unwrap()
+        self.required_entrypoint(&EntrypointType::CustomReset)
     }
 
     fn stack_overflow_handle_entrypoint(&self) -> &str {
+        self.required_entrypoint(&EntrypointType::StackOverflow)
+    }
+
+    fn abort_entrypoint(&self) -> &str {
+        self.required_entrypoint(&EntrypointType::Abort)
+    }
+
+    fn early_console_entrypoint(&self) -> Option<&str> {
         self.entrypoints
-            .get(&EntrypointType::StackOverflow)
-            .unwrap()
+            .get(&EntrypointType::EarlyConsole)
+            .map(|s| s.as_str())
+    }
+
+    // (interrupt code, entrypoint) of the interrupts with their own entrypoint, by code
+    fn interrupt_rust_entrypoints(&self) -> Vec<(usize, &str)> {
+        [
+            (
+                EntrypointType::SoftwareInterrupt,
+                self.software_interrupt_code(),
+            ),
+            (
+                EntrypointType::TimerInterrupt,
+                self.software_interrupt_code() + 4,
+            ),
+            (
+                EntrypointType::ExternalInterrupt,
+                self.software_interrupt_code() + 8,
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(ty, code)| self.entrypoints.get(&ty).map(|e| (code, e.as_str())))
+        .collect()
+    }
+
+    // Exception code reported in cause for a software interrupt targeting the current mode. The
+    // timer and external interrupt codes follow by steps of 4.
+    fn software_interrupt_code(&self) -> usize {
+        match self.rv_mode() {
+            RvMode::MMode => 3,
+            RvMode::SMode => 1,
+        }
     }
 
     fn csr_address_or_name(&self, csr: Csr) -> String {
@@ -267,6 +1303,10 @@ impl RtConfig {
         self.tp_block.current_mode_stack_idx() * self.xlen_bytes()
     }
 
+    fn interrupt_mode_stack_offset(&self) -> isize {
+        self.tp_block.interrupt_mode_stack_idx() * self.xlen_bytes()
+    }
+
     fn priv_ctx_offset(&self) -> isize {
         self.thread_ctx.priv_ctx_idx() * self.xlen_bytes()
     }
@@ -303,6 +1343,22 @@ impl RtConfig {
         self.tp_block.rt_flags_idx() * self.xlen_bytes()
     }
 
+    fn tp_block_started_offset(&self) -> isize {
+        self.tp_block.started_idx() * self.xlen_bytes()
+    }
+
+    fn tp_block_secondary_arg_offset(&self) -> isize {
+        self.tp_block.secondary_arg_idx() * self.xlen_bytes()
+    }
+
+    fn tp_block_boot_arg_offset(&self) -> isize {
+        self.tp_block.boot_arg_idx() * self.xlen_bytes()
+    }
+
+    fn tp_block_trap_count_offset(&self) -> isize {
+        self.tp_block.trap_count_idx() * self.xlen_bytes()
+    }
+
     fn tp_block_size(&self) -> isize {
         self.tp_block.reg_count() * self.xlen_bytes()
     }
@@ -315,23 +1371,50 @@ impl RtConfig {
         self.trap_frame.rust_struct_name()
     }
 
+    // Pairs each of `members` with its offset in the trap frame, in the order that the save and
+    // restore loops access them: list order by default, descending address order if reversed.
+    fn trap_frame_access_order<T: Copy>(
+        &self,
+        members: &[T],
+        idx: impl Fn(T) -> isize,
+    ) -> Vec<(T, isize)> {
+        let mut accesses: Vec<(T, isize)> = members
+            .iter()
+            .map(|m| (*m, idx(*m) * self.xlen_bytes()))
+            .collect();
+        if self.reverse_trap_frame_access {
+            accesses.sort_by_key(|(_, offset)| -offset);
+        }
+        accesses
+    }
+
     fn trap_frame_members(&self) -> Vec<String> {
         let mut members = Vec::new();
-        for gr in &self.trap_frame.general_regs {
-            members.push(gr.to_string());
-        }
-        for fr in &self.trap_frame.floating_point_registers {
-            members.push(fr.to_string());
+        for member in self.trap_frame.ordered_members() {
+            members.push(match member {
+                TrapFrameMember::GeneralRegister(gr) => gr.to_string(),
+                TrapFrameMember::FloatingPointRegister(fr) => fr.to_string(),
+                TrapFrameMember::Csr(csr) => self.csr(csr),
+                TrapFrameMember::SpareSlot(slot) => spare_slot_member_name(slot),
+                TrapFrameMember::RtState(sv) => sv.to_string(),
+            });
         }
-        for csr in &self.trap_frame.csrs {
-            members.push(self.csr(*csr));
-        }
-        for sv in &self.trap_frame.rt_state_values {
-            members.push(sv.to_string());
+        if !self.trap_frame.vector_registers.is_empty() {
+            for csr in VECTOR_CSRS {
+                members.push(self.csr(csr));
+            }
         }
         members
     }
 
+    fn trap_frame_vector_members(&self) -> Vec<(String, usize)> {
+        self.trap_frame
+            .vector_registers
+            .iter()
+            .map(|vr| (vr.to_string(), self.vector_register_bytes))
+            .collect()
+    }
+
     fn is_multi_hart(&self) -> bool {
         self.target_config.is_multi_hart()
     }
@@ -348,8 +1431,9 @@ impl RtConfig {
         self.skip_bss_clearing
     }
 
+    // Page guards catch overflows without any runtime check, so the sentry logic is skipped
     fn needs_stack_overflow_detection(&self) -> bool {
-        self.stack_overflow_detection
+        self.stack_overflow_detection && self.target_config.stack_guard() == StackGuard::Sentry
     }
 
     fn supports_atomic_extension(&self) -> bool {
@@ -369,6 +1453,11 @@ pub enum TpBlockMember {
     ReturnAddr,
     RtFlags,
     TrapCtx,
+    Started,
+    SecondaryArg,
+    TrapCount,
+    InterruptModeStack,
+    BootArg,
 }
 
 impl std::fmt::Display for TpBlockMember {
@@ -384,6 +1473,11 @@ impl std::fmt::Display for TpBlockMember {
             Self::ReturnAddr => "return_addr",
             Self::RtFlags => "rt_flags",
             Self::TrapCtx => "trap_ctx_frame",
+            Self::Started => "started",
+            Self::SecondaryArg => "secondary_arg",
+            Self::TrapCount => "trap_count",
+            Self::InterruptModeStack => "interrupt_mode_sp",
+            Self::BootArg => "boot_arg",
         };
         write!(f, "{print_str}")
     }
@@ -408,6 +1502,7 @@ impl TpBlock {
                 TpBlockMember::ReturnAddr,
                 TpBlockMember::RtFlags,
                 TpBlockMember::TrapCtx,
+                TpBlockMember::Started,
             ],
         }
     }
@@ -461,6 +1556,26 @@ impl TpBlock {
         self.member_idx(TpBlockMember::TrapCtx)
     }
 
+    fn started_idx(&self) -> isize {
+        self.member_idx(TpBlockMember::Started)
+    }
+
+    fn secondary_arg_idx(&self) -> isize {
+        self.member_idx(TpBlockMember::SecondaryArg)
+    }
+
+    fn trap_count_idx(&self) -> isize {
+        self.member_idx(TpBlockMember::TrapCount)
+    }
+
+    fn boot_arg_idx(&self) -> isize {
+        self.member_idx(TpBlockMember::BootArg)
+    }
+
+    fn interrupt_mode_stack_idx(&self) -> isize {
+        self.member_idx(TpBlockMember::InterruptModeStack)
+    }
+
     fn reg_count(&self) -> isize {
         self.members.len() as isize
     }
@@ -525,65 +1640,171 @@ pub struct TrapFrame {
     pub general_regs: Vec<GeneralRegister>,
     pub floating_point_registers: Vec<FloatingPointRegister>,
     pub csrs: Vec<Csr>,
+    // Number of pointer-sized slots reserved after the CSRs for future use. These are zeroed on
+    // trap entry and let consumers add state later without changing the trap frame size.
+    pub spare_csr_slots: usize,
     pub rt_state_values: Vec<RtStateValue>,
+    // Vector registers are saved along with the vector CSRs (see VECTOR_CSRS) only if the VS
+    // state is dirty
+    pub vector_registers: Vec<VectorRegister>,
+    // Explicit order of the XLEN sized members, e.g. to match an externally defined C struct. It
+    // must list every general register, floating point register, CSR, spare slot and runtime-state
+    // value exactly once. Empty keeps the default grouping. Vector state is always placed at the
+    // end of the trap frame.
+    pub member_order: Vec<TrapFrameMember>,
+    // Members placed first, in this order, e.g. the ones touched on every trap so that they share
+    // a cache line. The other members keep their order. Resolved into member_order by
+    // RtConfig::new(), once the floating point state is part of the trap frame.
+    pub hot_members: Vec<TrapFrameMember>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TrapFrameMember {
+    GeneralRegister(GeneralRegister),
+    FloatingPointRegister(FloatingPointRegister),
+    Csr(Csr),
+    SpareSlot(usize),
+    RtState(RtStateValue),
 }
 
+// Vector CSRs stashed in the trap frame along with the vector registers. Order matters on restore:
+// vl/vtype are restored via vsetvl after the register loads, vstart is restored last since vector
+// instructions reset it.
+const VECTOR_CSRS: [Csr; 4] = [Csr::Vl, Csr::Vtype, Csr::Vcsr, Csr::Vstart];
+
 impl TrapFrame {
     fn element_count(&self) -> isize {
         (self.general_regs.len()
             + self.floating_point_registers.len()
             + self.csrs.len()
-            + self.rt_state_values.len()) as isize
+            + self.spare_csr_slots
+            + self.rt_state_values.len()
+            + self.vector_csr_count()) as isize
+    }
+
+    fn vector_csr_count(&self) -> usize {
+        if self.vector_registers.is_empty() {
+            0
+        } else {
+            VECTOR_CSRS.len()
+        }
     }
 
-    fn gr_start_idx(&self) -> isize {
-        // General registers are stashed at the beginning of trap frame
-        0
+    fn vector_csr_start_idx(&self) -> isize {
+        // Vector CSRs are placed after all the other XLEN sized members in trap frame
+        self.ordered_members().len() as isize
     }
 
-    fn fr_start_idx(&self) -> isize {
-        // Floating point registers are stashed after the general purpose registers
-        self.general_regs.len() as isize
+    fn vector_csr_idx(&self, reg: Csr) -> isize {
+        for (idx, csr) in VECTOR_CSRS.iter().enumerate() {
+            if *csr == reg {
+                return idx as isize + self.vector_csr_start_idx();
+            }
+        }
+        unreachable!()
     }
 
-    fn csr_start_idx(&self) -> isize {
-        // CSRs are placed after general regs and floating point regs in trap frame
-        (self.general_regs.len() + self.floating_point_registers.len()) as isize
+    // Default grouping: general registers, floating point registers, CSRs, spare slots and then
+    // runtime-state data
+    fn default_member_order(&self) -> Vec<TrapFrameMember> {
+        let mut members = Vec::new();
+        for gr in &self.general_regs {
+            members.push(TrapFrameMember::GeneralRegister(*gr));
+        }
+        for fr in &self.floating_point_registers {
+            members.push(TrapFrameMember::FloatingPointRegister(*fr));
+        }
+        for csr in &self.csrs {
+            members.push(TrapFrameMember::Csr(*csr));
+        }
+        for slot in 0..self.spare_csr_slots {
+            members.push(TrapFrameMember::SpareSlot(slot));
+        }
+        for sv in &self.rt_state_values {
+            members.push(TrapFrameMember::RtState(*sv));
+        }
+        members
     }
 
-    fn rt_state_start_idx(&self) -> isize {
-        // runtime-state data is placed after csr regs in trap frame
-        (self.general_regs.len() + self.floating_point_registers.len() + self.csrs.len()) as isize
+    fn ordered_members(&self) -> Vec<TrapFrameMember> {
+        if self.member_order.is_empty() {
+            self.default_member_order()
+        } else {
+            self.member_order.clone()
+        }
     }
 
-    fn gr_idx(&self, reg: GeneralRegister) -> isize {
-        for (idx, gr) in self.general_regs.iter().enumerate() {
-            if *gr == reg {
-                return idx as isize + self.gr_start_idx();
-            }
+    fn resolve_hot_members(&mut self) {
+        if self.hot_members.is_empty() {
+            return;
         }
-        unreachable!()
+
+        let expected = self.default_member_order();
+        for member in &self.hot_members {
+            assert!(
+                expected.contains(member),
+                "Hot trap frame member {member:?} is not part of the trap frame"
+            );
+        }
+        let mut member_order = self.hot_members.clone();
+        member_order.extend(
+            self.ordered_members()
+                .into_iter()
+                .filter(|m| !self.hot_members.contains(m)),
+        );
+        self.member_order = member_order;
     }
 
-    fn csr_idx(&self, reg: Csr) -> isize {
-        for (idx, csr) in self.csrs.iter().enumerate() {
-            if *csr == reg {
-                return idx as isize + self.csr_start_idx();
-            }
+    fn validate_member_order(&self) {
+        if self.member_order.is_empty() {
+            return;
         }
 
-        unreachable!();
+        let expected = self.default_member_order();
+        for member in &expected {
+            let count = self.member_order.iter().filter(|m| *m == member).count();
+            assert!(
+                count == 1,
+                "Trap frame member {member:?} is listed {count:#} times in the member order"
+            );
+        }
+        for member in &self.member_order {
+            assert!(
+                expected.contains(member),
+                "Trap frame member {member:?} in the member order is not part of the trap frame"
+            );
+        }
     }
 
-    fn rt_state_idx(&self, val: RtStateValue) -> isize {
-        for (idx, sv) in self.rt_state_values.iter().enumerate() {
-            if *sv == val {
-                return idx as isize + self.rt_state_start_idx();
+    fn member_idx(&self, member: TrapFrameMember) -> isize {
+        for (idx, m) in self.ordered_members().iter().enumerate() {
+            if *m == member {
+                return idx as isize;
             }
         }
         unreachable!()
     }
 
+    fn gr_idx(&self, reg: GeneralRegister) -> isize {
+        self.member_idx(TrapFrameMember::GeneralRegister(reg))
+    }
+
+    fn fr_idx(&self, reg: FloatingPointRegister) -> isize {
+        self.member_idx(TrapFrameMember::FloatingPointRegister(reg))
+    }
+
+    fn csr_idx(&self, reg: Csr) -> isize {
+        self.member_idx(TrapFrameMember::Csr(reg))
+    }
+
+    fn spare_slot_idx(&self, slot: usize) -> isize {
+        self.member_idx(TrapFrameMember::SpareSlot(slot))
+    }
+
+    fn rt_state_idx(&self, val: RtStateValue) -> isize {
+        self.member_idx(TrapFrameMember::RtState(val))
+    }
+
     fn status_reg_idx(&self) -> isize {
         self.csr_idx(Csr::Status)
     }
@@ -645,10 +1866,14 @@ impl TrapFrame {
             ],
             floating_point_registers: vec![],
             csrs: vec![Csr::Status, Csr::Epc, Csr::Tval, Csr::Cause],
+            spare_csr_slots: 0,
             rt_state_values: vec![
                 RtStateValue::RtFlags,
                 RtStateValue::InterruptedTrapFrameAddr,
             ],
+            vector_registers: vec![],
+            member_order: vec![],
+            hot_members: vec![],
         }
     }
 
@@ -657,7 +1882,7 @@ impl TrapFrame {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum RtStateValue {
     RtFlags,
     InterruptedTrapFrameAddr,
@@ -666,8 +1891,11 @@ pub enum RtStateValue {
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum Csr {
     Ie,
+    Ip,
     Mcounteren,
+    Mcountinhibit,
     Menvcfg,
+    Mseccfg,
     Mideleg,
     Medeleg,
     Mhartid,
@@ -679,6 +1907,40 @@ pub enum Csr {
     Tvec,
     Satp,
     Fcsr,
+    // Hypervisor extension CSRs. These are only accessible from HS-mode (or M-mode).
+    Hstatus,
+    Hedeleg,
+    Hideleg,
+    Hie,
+    Hip,
+    Hvip,
+    Hcounteren,
+    Henvcfg,
+    Htval,
+    Htinst,
+    Hgatp,
+    Vsstatus,
+    Vsie,
+    Vsip,
+    Vstvec,
+    Vsscratch,
+    Vsepc,
+    Vscause,
+    Vstval,
+    Vsatp,
+    // Vector extension CSRs
+    Vstart,
+    Vxsat,
+    Vxrm,
+    Vcsr,
+    Vl,
+    Vtype,
+    Vlenb,
+    // Unprivileged counters
+    Cycle,
+    // Physical memory protection CSRs, indexed by register number
+    PmpAddr(usize),
+    PmpCfg(usize),
     // The address and name of the CSR
     Other(usize, &'static str),
 }
@@ -692,9 +1954,42 @@ impl Csr {
             | Self::Medeleg
             | Self::Satp
             | Self::Menvcfg
+            | Self::Mseccfg
             | Self::Mcounteren
-            | Self::Fcsr => false,
+            | Self::Mcountinhibit
+            | Self::Fcsr
+            | Self::Hstatus
+            | Self::Hedeleg
+            | Self::Hideleg
+            | Self::Hie
+            | Self::Hip
+            | Self::Hvip
+            | Self::Hcounteren
+            | Self::Henvcfg
+            | Self::Htval
+            | Self::Htinst
+            | Self::Hgatp
+            | Self::Vsstatus
+            | Self::Vsie
+            | Self::Vsip
+            | Self::Vstvec
+            | Self::Vsscratch
+            | Self::Vsepc
+            | Self::Vscause
+            | Self::Vstval
+            | Self::Vsatp
+            | Self::Vstart
+            | Self::Vxsat
+            | Self::Vxrm
+            | Self::Vcsr
+            | Self::Vl
+            | Self::Vtype
+            | Self::Vlenb
+            | Self::Cycle
+            | Self::PmpAddr(_)
+            | Self::PmpCfg(_) => false,
             Self::Ie
+            | Self::Ip
             | Self::Status
             | Self::Epc
             | Self::Scratch
@@ -704,13 +1999,85 @@ impl Csr {
         }
     }
 
+    // CSR number of this CSR when accessed from `mode`
+    fn address(&self, mode: RvMode) -> usize {
+        let m_mode_address = match self {
+            Self::Ie => 0x304,
+            Self::Ip => 0x344,
+            Self::Mcounteren => 0x306,
+            Self::Mcountinhibit => 0x320,
+            Self::Menvcfg => 0x30a,
+            Self::Mseccfg => 0x747,
+            Self::Mideleg => 0x303,
+            Self::Medeleg => 0x302,
+            Self::Mhartid => 0xf14,
+            Self::Status => 0x300,
+            Self::Epc => 0x341,
+            Self::Scratch => 0x340,
+            Self::Tval => 0x343,
+            Self::Cause => 0x342,
+            Self::Tvec => 0x305,
+            Self::Satp => 0x180,
+            Self::Fcsr => 0x003,
+            Self::Hstatus => 0x600,
+            Self::Hedeleg => 0x602,
+            Self::Hideleg => 0x603,
+            Self::Hie => 0x604,
+            Self::Hip => 0x644,
+            Self::Hvip => 0x645,
+            Self::Hcounteren => 0x606,
+            Self::Henvcfg => 0x60a,
+            Self::Htval => 0x643,
+            Self::Htinst => 0x64a,
+            Self::Hgatp => 0x680,
+            Self::Vsstatus => 0x200,
+            Self::Vsie => 0x204,
+            Self::Vsip => 0x244,
+            Self::Vstvec => 0x205,
+            Self::Vsscratch => 0x240,
+            Self::Vsepc => 0x241,
+            Self::Vscause => 0x242,
+            Self::Vstval => 0x243,
+            Self::Vsatp => 0x280,
+            Self::Vstart => 0x008,
+            Self::Vxsat => 0x009,
+            Self::Vxrm => 0x00a,
+            Self::Vcsr => 0x00f,
+            Self::Vl => 0xc20,
+            Self::Vtype => 0xc21,
+            Self::Vlenb => 0xc22,
+            Self::Cycle => 0xc00,
+            Self::PmpAddr(n) => 0x3b0 + n,
+            Self::PmpCfg(n) => 0x3a0 + n,
+            Self::Other(addr, _) => *addr,
+        };
+
+        // The supervisor copies of the mode dependent CSRs sit 0x200 below the machine ones
+        if self.is_mode_dependent() && mode == RvMode::SMode {
+            m_mode_address - 0x200
+        } else {
+            m_mode_address
+        }
+    }
+
     fn restore_from_trap_frame(&self) -> bool {
         // matches! macro returns whether the given expression matches any of
         // the given patterns. In our case, Xcause and Xtval don't need to be
         // restored from trap frame because they are set on every entry into
         // that mode, restoring those CSRs isn't required when returning back
-        // from the trap handler
-        !matches!(self, Self::Cause | Self::Tval)
+        // from the trap handler. Same applies to htval and htinst which are
+        // written on every trap into HS-mode.
+        // vl, vtype and vlenb are read-only and can't be written with csrw.
+        !matches!(
+            self,
+            Self::Cause
+                | Self::Tval
+                | Self::Htval
+                | Self::Htinst
+                | Self::Vl
+                | Self::Vtype
+                | Self::Vlenb
+        )
     }
 }
 
@@ -718,8 +2085,11 @@ impl std::fmt::Display for Csr {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let print_str = match self {
             Self::Ie => "ie",
+            Self::Ip => "ip",
             Self::Mcounteren => "mcounteren",
+            Self::Mcountinhibit => "mcountinhibit",
             Self::Menvcfg => "menvcfg",
+            Self::Mseccfg => "mseccfg",
             Self::Mideleg => "mideleg",
             Self::Medeleg => "medeleg",
             Self::Mhartid => "mhartid",
@@ -731,6 +2101,36 @@ impl std::fmt::Display for Csr {
             Self::Cause => "cause",
             Self::Tvec => "tvec",
             Self::Fcsr => "fcsr",
+            Self::Hstatus => "hstatus",
+            Self::Hedeleg => "hedeleg",
+            Self::Hideleg => "hideleg",
+            Self::Hie => "hie",
+            Self::Hip => "hip",
+            Self::Hvip => "hvip",
+            Self::Hcounteren => "hcounteren",
+            Self::Henvcfg => "henvcfg",
+            Self::Htval => "htval",
+            Self::Htinst => "htinst",
+            Self::Hgatp => "hgatp",
+            Self::Vsstatus => "vsstatus",
+            Self::Vsie => "vsie",
+            Self::Vsip => "vsip",
+            Self::Vstvec => "vstvec",
+            Self::Vsscratch => "vsscratch",
+            Self::Vsepc => "vsepc",
+            Self::Vscause => "vscause",
+            Self::Vstval => "vstval",
+            Self::Vsatp => "vsatp",
+            Self::Vstart => "vstart",
+            Self::Vxsat => "vxsat",
+            Self::Vxrm => "vxrm",
+            Self::Vcsr => "vcsr",
+            Self::Vl => "vl",
+            Self::Vtype => "vtype",
+            Self::Vlenb => "vlenb",
+            Self::Cycle => "cycle",
+            Self::PmpAddr(n) => return write!(f, "pmpaddr{n}"),
+            Self::PmpCfg(n) => return write!(f, "pmpcfg{n}"),
             Self::Other(_addr, name) => name,
         };
         write!(f, "{print_str}")
@@ -783,6 +2183,14 @@ pub enum GeneralRegister {
     T6,
 }
 
+impl GeneralRegister {
+    // Architectural register number (x0-x31). The enum variants are declared in register number
+    // order, so the discriminant is the register number.
+    fn number(&self) -> usize {
+        *self as usize
+    }
+}
+
 impl std::fmt::Display for GeneralRegister {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         let print_str = match self {
@@ -823,6 +2231,42 @@ impl std::fmt::Display for GeneralRegister {
     }
 }
 
+// All floating point registers, in the order they are saved in the default trap frame layout
+pub const FLOATING_POINT_REGISTERS: [FloatingPointRegister; 32] = [
+    FloatingPointRegister::F0,
+    FloatingPointRegister::F1,
+    FloatingPointRegister::F2,
+    FloatingPointRegister::F3,
+    FloatingPointRegister::F4,
+    FloatingPointRegister::F5,
+    FloatingPointRegister::F6,
+    FloatingPointRegister::F7,
+    FloatingPointRegister::F8,
+    FloatingPointRegister::F9,
+    FloatingPointRegister::F10,
+    FloatingPointRegister::F11,
+    FloatingPointRegister::F12,
+    FloatingPointRegister::F13,
+    FloatingPointRegister::F14,
+    FloatingPointRegister::F15,
+    FloatingPointRegister::F16,
+    FloatingPointRegister::F17,
+    FloatingPointRegister::F18,
+    FloatingPointRegister::F19,
+    FloatingPointRegister::F20,
+    FloatingPointRegister::F21,
+    FloatingPointRegister::F22,
+    FloatingPointRegister::F23,
+    FloatingPointRegister::F24,
+    FloatingPointRegister::F25,
+    FloatingPointRegister::F26,
+    FloatingPointRegister::F27,
+    FloatingPointRegister::F28,
+    FloatingPointRegister::F29,
+    FloatingPointRegister::F30,
+    FloatingPointRegister::F31,
+];
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum FloatingPointRegister {
     F0,
@@ -899,11 +2343,91 @@ impl std::fmt::Display for FloatingPointRegister {
     }
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VectorRegister {
+    V0,
+    V1,
+    V2,
+    V3,
+    V4,
+    V5,
+    V6,
+    V7,
+    V8,
+    V9,
+    V10,
+    V11,
+    V12,
+    V13,
+    V14,
+    V15,
+    V16,
+    V17,
+    V18,
+    V19,
+    V20,
+    V21,
+    V22,
+    V23,
+    V24,
+    V25,
+    V26,
+    V27,
+    V28,
+    V29,
+    V30,
+    V31,
+}
+
+impl std::fmt::Display for VectorRegister {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let print_str = match self {
+            Self::V0 => "v0",
+            Self::V1 => "v1",
+            Self::V2 => "v2",
+            Self::V3 => "v3",
+            Self::V4 => "v4",
+            Self::V5 => "v5",
+            Self::V6 => "v6",
+            Self::V7 => "v7",
+            Self::V8 => "v8",
+            Self::V9 => "v9",
+            Self::V10 => "v10",
+            Self::V11 => "v11",
+            Self::V12 => "v12",
+            Self::V13 => "v13",
+            Self::V14 => "v14",
+            Self::V15 => "v15",
+            Self::V16 => "v16",
+            Self::V17 => "v17",
+            Self::V18 => "v18",
+            Self::V19 => "v19",
+            Self::V20 => "v20",
+            Self::V21 => "v21",
+            Self::V22 => "v22",
+            Self::V23 => "v23",
+            Self::V24 => "v24",
+            Self::V25 => "v25",
+            Self::V26 => "v26",
+            Self::V27 => "v27",
+            Self::V28 => "v28",
+            Self::V29 => "v29",
+            Self::V30 => "v30",
+            Self::V31 => "v31",
+        };
+        write!(f, "{print_str}")
+    }
+}
+
 #[derive(Debug)]
 pub enum LinkerOption {
     Push,
     Pop,
     NoRelax,
+    Rvc,
+    NoRvc,
+    // Enable an ISA extension on top of the arch attribute
+    ArchExtension(&'static str),
 }
 
 impl std::fmt::Display for LinkerOption {
@@ -912,6 +2436,9 @@ impl std::fmt::Display for LinkerOption {
             Self::Push => "push",
             Self::Pop => "pop",
             Self::NoRelax => "norelax",
+            Self::Rvc => "rvc",
+            Self::NoRvc => "norvc",
+            Self::ArchExtension(ext) => return write!(f, "arch, +{ext:#}"),
         };
         write!(f, "{print_str}")
     }
@@ -921,11 +2448,13 @@ impl std::fmt::Display for LinkerOption {
 enum AsmSentence {
     Section(String, Option<String>),              // (section name, flags)
     GlobalEntrypoint(String),                     // (entrypoint name)
+    Weak(String),                                 // (symbol)
     Csrw(Csr, GeneralRegister),                   // (csr, rs)
     Csrr(GeneralRegister, Csr),                   // (rd, csr)
     Csrrw(GeneralRegister, Csr, GeneralRegister), // (rd, csr, rs)
     Csrc(Csr, GeneralRegister),                   // (csr, rs)
     Csrs(Csr, GeneralRegister),                   // (csr, rs)
+    Csrci(Csr, usize),                            // (csr, uimm)
     LinkerOption(LinkerOption),                   // (option)
     La(GeneralRegister, String),                  // (rd, symbol)
     Li(GeneralRegister, usize),                   // (rd, imm)
@@ -935,27 +2464,51 @@ enum AsmSentence {
     Bne(GeneralRegister, GeneralRegister, String), // (rs1, rs2, label)
     Beqz(GeneralRegister, String),                // (rs, label)
     Bnez(GeneralRegister, String),                // (rs, label)
+    Bgez(GeneralRegister, String),                // (rs, label)
     Label(String),                                // (label)
     Sfence(GeneralRegister, GeneralRegister),     // (rs1, rs2)
+    Fence,
+    FenceI,
     Store(GeneralRegister, GeneralRegister, isize), // (rs2, rs1, offset)
-    Load(GeneralRegister, GeneralRegister, isize), // (rd, rs, offset)
-    Addi(GeneralRegister, GeneralRegister, isize), // (rd, rs, imm)
-    Xori(GeneralRegister, GeneralRegister, isize), // (rd, rs, imm)
+    Load(GeneralRegister, GeneralRegister, isize),  // (rd, rs, offset)
+    Addi(GeneralRegister, GeneralRegister, isize),  // (rd, rs, imm)
+    Xori(GeneralRegister, GeneralRegister, isize),  // (rd, rs, imm)
     Or(GeneralRegister, GeneralRegister, GeneralRegister),
     FloatStore(FloatingPointRegister, GeneralRegister, isize), // (rs2, rs1, offset)
     FloatLoad(FloatingPointRegister, GeneralRegister, isize),  // (rd, rs, offset)
     MoveToFloat(FloatingPointRegister, GeneralRegister),       // (fd, rs)
+    VectorStore(VectorRegister, GeneralRegister),              // (vs3, rs1)
+    VectorLoad(VectorRegister, GeneralRegister),               // (vd, rs1)
+    VectorMoveZero(VectorRegister),                            // (vd)
+    Vsetvl(GeneralRegister, GeneralRegister, GeneralRegister), // (rd, rs1, rs2)
+    VsetvliMax(GeneralRegister),                               // (rd)
     Wfi,
-    J(String),                                              // (label)
-    Jal(String),                                            // (label)
-    Jr(GeneralRegister),                                    // (rs)
-    Jalr(GeneralRegister, GeneralRegister, isize),          // (rd, rs1, offset)
-    Comment(String),                                        // (comment)
-    Add(GeneralRegister, GeneralRegister, GeneralRegister), // (rd, rs1, rs2)
-    Sub(GeneralRegister, GeneralRegister, GeneralRegister), // (rd, rs1, rs2)
-    Mul(GeneralRegister, GeneralRegister, GeneralRegister), // (rd, rs1, rs2)
-    Dword(u64),                                             // (val)
-    Word(u32),                                              // (val)
+    Ebreak,
+    J(String),                                               // (label)
+    Jal(String),                                             // (label)
+    Jr(GeneralRegister),                                     // (rs)
+    Jalr(GeneralRegister, GeneralRegister, isize),           // (rd, rs1, offset)
+    Comment(String),                                         // (comment)
+    Add(GeneralRegister, GeneralRegister, GeneralRegister),  // (rd, rs1, rs2)
+    Sub(GeneralRegister, GeneralRegister, GeneralRegister),  // (rd, rs1, rs2)
+    Mul(GeneralRegister, GeneralRegister, GeneralRegister),  // (rd, rs1, rs2)
+    Divu(GeneralRegister, GeneralRegister, GeneralRegister), // (rd, rs1, rs2)
+    Slli(GeneralRegister, GeneralRegister, usize),           // (rd, rs, shamt)
+    Srli(GeneralRegister, GeneralRegister, usize),           // (rd, rs, shamt)
+    Dword(u64),                                              // (val)
+    Word(u32),                                               // (val)
+    DwordSymbol(String),                                     // (symbol)
+    WordSymbol(String),                                      // (symbol)
+    Asciz(String),                                           // (string)
+    Lpad(u32),                                               // (label)
+    Nop,
+    CfiSections(String), // (sections)
+    CfiStartProc,
+    CfiEndProc,
+    CfiSignalFrame,
+    CfiReturnColumn(usize),            // (DWARF register)
+    CfiDefCfa(GeneralRegister, isize), // (reg, offset)
+    CfiOffset(usize, isize),           // (DWARF register, offset from CFA)
     EndSection,
     Amoadd(GeneralRegister, GeneralRegister, GeneralRegister), // (rd, rs1, rs2)
     Ret,
@@ -964,9 +2517,36 @@ enum AsmSentence {
     EndRept,
     And(GeneralRegister, GeneralRegister, GeneralRegister), // (rd, rs1, rs2)
     Andi(GeneralRegister, GeneralRegister, isize),          // (rd, rs1, imm)
+    Ori(GeneralRegister, GeneralRegister, isize),           // (rd, rs1, imm)
     Align(usize),                                           // (alignment in bytes)
+    Balign(usize),                                          // (alignment in bytes)
     Attribute(String, String),                              // (name, value)
     Sc(GeneralRegister, GeneralRegister, GeneralRegister),  // (rd, rs2, rs1)
+    Lr(GeneralRegister, GeneralRegister, AmoOrdering),      // (rd, rs1, ordering)
+    // (rd, rs1, rs2, ordering)
+    Amoswap(
+        GeneralRegister,
+        GeneralRegister,
+        GeneralRegister,
+        AmoOrdering,
+    ),
+}
+
+// Ordering bits of an atomic memory operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AmoOrdering {
+    Acquire,
+    Release,
+}
+
+impl std::fmt::Display for AmoOrdering {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let print_str = match self {
+            Self::Acquire => ".aq",
+            Self::Release => ".rl",
+        };
+        write!(f, "{print_str}")
+    }
 }
 
 impl AsmSentence {
@@ -984,6 +2564,7 @@ impl AsmSentence {
                 fw.add_line(&format!(".global {entrypoint_name:#}"));
                 fw.label(entrypoint_name);
             }
+            Self::Weak(symbol) => fw.add_line(&format!(".weak {symbol:#}")),
             Self::Csrw(csr, rs) => fw.add_line(&format!(
                 "csrw {:#}, {:#}",
                 rt_config.csr_address_or_name(*csr),
@@ -999,6 +2580,11 @@ impl AsmSentence {
                 rt_config.csr_address_or_name(*csr),
                 rs
             )),
+            Self::Csrci(csr, uimm) => fw.add_line(&format!(
+                "csrci {:#}, {:#}",
+                rt_config.csr_address_or_name(*csr),
+                uimm
+            )),
             Self::Csrr(rd, csr) => fw.add_line(&format!(
                 "csrr {:#}, {:#}",
                 rd,
@@ -1023,8 +2609,11 @@ impl AsmSentence {
             Self::Bne(rs1, rs2, label) => fw.add_line(&format!("bne {rs1:#}, {rs2:#}, {label:#}")),
             Self::Beqz(rs, label) => fw.add_line(&format!("beqz {rs:#}, {label:#}")),
             Self::Bnez(rs, label) => fw.add_line(&format!("bnez {rs:#}, {label:#}")),
+            Self::Bgez(rs, label) => fw.add_line(&format!("bgez {rs:#}, {label:#}")),
             Self::Label(label) => fw.label(&format!("{label:#}")),
             Self::Sfence(rs1, rs2) => fw.add_line(&format!("sfence.vma {rs1:#}, {rs2:#}")),
+            Self::Fence => fw.add_line("fence"),
+            Self::FenceI => fw.add_line("fence.i"),
             Self::Store(rs2, rs1, offset) => {
                 if *offset == 0 {
                     fw.add_line(&format!(
@@ -1068,14 +2657,14 @@ impl AsmSentence {
                 if *offset == 0 {
                     fw.add_line(&format!(
                         "fs{:#} {:#}, ({:#})",
-                        rt_config.word_prefix(),
+                        rt_config.float_width.suffix(),
                         rs2,
                         rs1
                     ));
                 } else {
                     fw.add_line(&format!(
                         "fs{:#} {:#}, {:#}({:#})",
-                        rt_config.word_prefix(),
+                        rt_config.float_width.suffix(),
                         rs2,
                         offset,
                         rs1
@@ -1086,22 +2675,34 @@ impl AsmSentence {
                 if *offset == 0 {
                     fw.add_line(&format!(
                         "fl{:#} {:#}, ({:#})",
-                        rt_config.word_prefix(),
+                        rt_config.float_width.suffix(),
                         rd,
                         rs
                     ));
                 } else {
                     fw.add_line(&format!(
                         "fl{:#} {:#}, {:#}({:#})",
-                        rt_config.word_prefix(),
+                        rt_config.float_width.suffix(),
                         rd,
                         offset,
                         rs
                     ));
                 }
             }
-            Self::MoveToFloat(fd, rs) => fw.add_line(&format!("fmv.d.x {fd:#}, {rs:#}")),
+            Self::MoveToFloat(fd, rs) => fw.add_line(&format!(
+                "fmv.{:#}.x {fd:#}, {rs:#}",
+                rt_config.float_width.suffix()
+            )),
+            // Whole register loads/stores don't depend on vl/vtype
+            Self::VectorStore(vs3, rs1) => fw.add_line(&format!("vs1r.v {vs3:#}, ({rs1:#})")),
+            Self::VectorLoad(vd, rs1) => fw.add_line(&format!("vl1re8.v {vd:#}, ({rs1:#})")),
+            Self::VectorMoveZero(vd) => fw.add_line(&format!("vmv.v.i {vd:#}, 0")),
+            Self::Vsetvl(rd, rs1, rs2) => {
+                fw.add_line(&format!("vsetvl {rd:#}, {rs1:#}, {rs2:#}"));
+            }
+            Self::VsetvliMax(rd) => fw.add_line(&format!("vsetvli {rd:#}, zero, e8, m1, ta, ma")),
             Self::Wfi => fw.add_line("wfi"),
+            Self::Ebreak => fw.add_line("ebreak"),
             Self::J(label) => fw.add_line(&format!("j {label:#}")),
             Self::Jal(label) => fw.add_line(&format!("jal {label:#}")),
             Self::Jr(rs) => fw.add_line(&format!("jr {rs:#}")),
@@ -1112,8 +2713,27 @@ impl AsmSentence {
             Self::Add(rd, rs1, rs2) => fw.add_line(&format!("add {rd:#}, {rs1:#}, {rs2:#}")),
             Self::Sub(rd, rs1, rs2) => fw.add_line(&format!("sub {rd:#}, {rs1:#}, {rs2:#}")),
             Self::Mul(rd, rs1, rs2) => fw.add_line(&format!("mul {rd:#}, {rs1:#}, {rs2:#}")),
+            Self::Divu(rd, rs1, rs2) => fw.add_line(&format!("divu {rd:#}, {rs1:#}, {rs2:#}")),
+            Self::Slli(rd, rs, shamt) => fw.add_line(&format!("slli {rd:#}, {rs:#}, {shamt:#}")),
+            Self::Srli(rd, rs, shamt) => fw.add_line(&format!("srli {rd:#}, {rs:#}, {shamt:#}")),
             Self::Dword(val) => fw.add_line(&format!(".dword {val:#}")),
             Self::Word(val) => fw.add_line(&format!(".word {val:#}")),
+            Self::DwordSymbol(symbol) => fw.add_line(&format!(".dword {symbol:#}")),
+            Self::WordSymbol(symbol) => fw.add_line(&format!(".word {symbol:#}")),
+            Self::Asciz(string) => fw.add_line(&format!(".asciz {string:?}")),
+            Self::Lpad(label) => fw.add_line(&format!("lpad {label:#}")),
+            Self::Nop => fw.add_line("nop"),
+            Self::CfiSections(sections) => fw.add_line(&format!(".cfi_sections {sections:#}")),
+            Self::CfiStartProc => fw.add_line(".cfi_startproc"),
+            Self::CfiEndProc => fw.add_line(".cfi_endproc"),
+            Self::CfiSignalFrame => fw.add_line(".cfi_signal_frame"),
+            Self::CfiReturnColumn(reg) => fw.add_line(&format!(".cfi_return_column {reg:#}")),
+            Self::CfiDefCfa(reg, offset) => {
+                fw.add_line(&format!(".cfi_def_cfa {reg:#}, {offset:#}"))
+            }
+            Self::CfiOffset(reg, offset) => {
+                fw.add_line(&format!(".cfi_offset {reg:#}, {offset:#}"))
+            }
             Self::Amoadd(rd, rs1, rs2) => fw.add_line(&format!(
                 "amoadd.{:#} {:#}, {:#}, ({:#})",
                 rt_config.word_prefix(),
@@ -1129,10 +2749,14 @@ impl AsmSentence {
             Self::Andi(rd, rs, imm) => {
                 fw.add_line(&format!("andi {rd:#}, {rs:#}, {imm:#}"));
             }
+            Self::Ori(rd, rs, imm) => {
+                fw.add_line(&format!("ori {rd:#}, {rs:#}, {imm:#}"));
+            }
             Self::Align(alignment) => {
                 fw.goto_next_line();
                 fw.add_line(&format!(".align {alignment:#}"));
             }
+            Self::Balign(alignment) => fw.add_line(&format!(".balign {alignment:#}")),
             Self::Attribute(name, value) => {
                 fw.add_line(&format!(".attribute {name:#}, {value:?}"));
             }
@@ -1145,6 +2769,21 @@ impl AsmSentence {
                     rs1
                 ));
             }
+            Self::Lr(rd, rs1, ordering) => fw.add_line(&format!(
+                "lr.{:#}{:#} {:#}, ({:#})",
+                rt_config.word_prefix(),
+                ordering,
+                rd,
+                rs1
+            )),
+            Self::Amoswap(rd, rs1, rs2, ordering) => fw.add_line(&format!(
+                "amoswap.{:#}{:#} {:#}, {:#}, ({:#})",
+                rt_config.word_prefix(),
+                ordering,
+                rd,
+                rs2,
+                rs1
+            )),
         }
     }
 }
@@ -1154,6 +2793,8 @@ pub enum LabelType {
     ParkHart,
     SecondaryStart,
     BootIdxVariable,
+    BootIdChoosing,
+    BootIdTicket,
     ResetStart,
     RestoreTrapFrame,
     CreateTrapFrame,
@@ -1161,15 +2802,23 @@ pub enum LabelType {
     ThreadPointerBlock,
     JumpToRustEntrypoint,
     BssInitDone,
+    BssInitClaim,
+    SecondaryRelease,
+    BootLock,
+    ResetEntryCopy,
     CustomResetEntryPoint,
     ProtectStack,
     GetTrapAddr,
+    TrapVectorTable,
+    BootChecksum,
+    InterruptEntrypointTable,
 }
 
 #[derive(Debug, Hash, Eq, PartialEq)]
 pub enum NamedReg {
     BootId,
     HartId,
+    TrapFrameBase,
 }
 
 #[derive(Debug)]
@@ -1192,7 +2841,9 @@ impl<'a> AsmBuilder<'a> {
             label_map: RefCell::new(HashMap::new()),
             named_regs: RefCell::new(HashMap::new()),
         };
-        ab.comment(&auto_generate_banner());
+        for line in rt_config.banner.lines() {
+            ab.comment(line);
+        }
         ab
     }
 
@@ -1206,15 +2857,7 @@ impl<'a> AsmBuilder<'a> {
 
     fn init_default_free_reg_pool(&self) {
         self.drain_free_reg_pool();
-        self.assign_free_reg_pool(&[
-            GeneralRegister::T0,
-            GeneralRegister::T1,
-            GeneralRegister::T2,
-            GeneralRegister::T3,
-            GeneralRegister::T4,
-            GeneralRegister::T5,
-            GeneralRegister::T6,
-        ]);
+        self.assign_free_reg_pool(self.rt_config.scratch_regs());
     }
 
     fn add_named_reg(&self, name: NamedReg, reg: GeneralRegister) {
@@ -1269,11 +2912,50 @@ impl<'a> AsmBuilder<'a> {
         self.get_named_reg(NamedReg::HartId)
     }
 
+    // Copy the trap frame address (sp) into a dedicated register, if enabled and one is free
+    fn reserve_trap_frame_base_reg(&self) {
+        if !self.rt_config.trap_frame_base_reg {
+            return;
+        }
+        if let Some(reg) = self.try_get_free_reg() {
+            self.comment("Address the trap frame through a dedicated register");
+            self.mov(reg, GeneralRegister::Sp);
+            self.add_named_reg(NamedReg::TrapFrameBase, reg);
+        }
+    }
+
+    fn release_trap_frame_base_reg(&self) {
+        if self
+            .named_regs
+            .borrow()
+            .contains_key(&NamedReg::TrapFrameBase)
+        {
+            let reg = self.get_named_reg(NamedReg::TrapFrameBase);
+            self.release_reg(reg);
+            self.remove_named_reg(NamedReg::TrapFrameBase);
+        }
+    }
+
+    // Register holding the trap frame address: the reserved one if any, sp otherwise
+    fn get_trap_frame_base_reg(&self) -> GeneralRegister {
+        self.named_regs
+            .borrow()
+            .get(&NamedReg::TrapFrameBase)
+            .copied()
+            .unwrap_or(GeneralRegister::Sp)
+    }
+
     fn add_label_to_map(&self, ty: LabelType, label: &str) {
         self.label_map.borrow_mut().insert(ty, label.to_string());
     }
 
     fn add_labels(&self, labels: &[(LabelType, &str)]) {
+        for (idx, (_, label_name)) in labels.iter().enumerate() {
+            assert!(
+                labels[..idx].iter().all(|(_, name)| name != label_name),
+                "Label {label_name:?} is used more than once"
+            );
+        }
         labels
             .iter()
             .for_each(|(label_ty, label_name)| self.add_label_to_map(*label_ty, label_name));
@@ -1285,12 +2967,19 @@ impl<'a> AsmBuilder<'a> {
 
     fn get_free_reg(&self) -> GeneralRegister {
         if self.free_general_regs.borrow().is_empty() {
-            panic!("out of free general registers!");
+            panic!(
+                "out of free general registers! The scratch registers {:?} may be too few for the generated code",
+                self.rt_config.scratch_regs()
+            );
         }
 
         self.free_general_regs.borrow_mut().pop().unwrap()
     }
 
+    fn try_get_free_reg(&self) -> Option<GeneralRegister> {
+        self.free_general_regs.borrow_mut().pop()
+    }
+
     fn release_reg(&self, reg: GeneralRegister) {
         self.free_general_regs.borrow_mut().push(reg);
     }
@@ -1322,11 +3011,50 @@ impl<'a> AsmBuilder<'a> {
         self.add_sentence(AsmSentence::GlobalEntrypoint(
             self.get_label_from_map(LabelType::ResetStart),
         ));
+        self.landing_pad();
+    }
+
+    // Declare `symbol` weak, so that it resolves to 0 when nothing defines it
+    fn weak(&self, symbol: &str) {
+        self.add_sentence(AsmSentence::Weak(symbol.to_string()));
     }
 
     fn global_function(&self, fn_name: &str) {
         self.section(&text_default_section(), Some(self.text_section_flags()));
         self.add_sentence(AsmSentence::GlobalEntrypoint(fn_name.to_string()));
+        self.landing_pad();
+    }
+
+    fn landing_pad(&self) {
+        if self.rt_config.cfi {
+            self.add_sentence(AsmSentence::Lpad(0));
+        }
+    }
+
+    // Mark the object as using unlabeled landing pads, see the RISC-V ELF psABI
+    fn gnu_property_note(&self) {
+        const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+        const GNU_PROPERTY_RISCV_FEATURE_1_AND: u32 = 0xc000_0000;
+        const GNU_PROPERTY_RISCV_FEATURE_1_CFI_LP_UNLABELED: u32 = 1;
+
+        // Property descriptors are padded to the ELF class word size
+        let align = match self.rt_config.rv_xlen() {
+            RvXlen::Rv32 => 4,
+            _ => 8,
+        };
+
+        self.section(".note.gnu.property", Some("a".to_string()));
+        self.balign(align);
+        self.comment("namesz, descsz, type");
+        self.word(4);
+        self.word(align as u32 + 8);
+        self.word(NT_GNU_PROPERTY_TYPE_0);
+        self.add_sentence(AsmSentence::Asciz("GNU".to_string()));
+        self.comment("pr_type, pr_datasz, pr_data");
+        self.word(GNU_PROPERTY_RISCV_FEATURE_1_AND);
+        self.word(4);
+        self.word(GNU_PROPERTY_RISCV_FEATURE_1_CFI_LP_UNLABELED);
+        self.balign(align);
     }
 
     fn section(&self, section: &str, flags: Option<String>) {
@@ -1345,6 +3073,11 @@ impl<'a> AsmBuilder<'a> {
         self.add_sentence(AsmSentence::Csrc(csr, rs));
     }
 
+    fn csrci(&self, csr: Csr, uimm: usize) {
+        assert!(uimm < 32, "csrci immediate out of range");
+        self.add_sentence(AsmSentence::Csrci(csr, uimm));
+    }
+
     fn csrw_zero(&self, csr: Csr) {
         self.add_sentence(AsmSentence::Csrw(csr, GeneralRegister::Zero));
     }
@@ -1409,6 +3142,10 @@ impl<'a> AsmBuilder<'a> {
         self.add_sentence(AsmSentence::Bnez(rs, label.to_string()));
     }
 
+    fn bgez(&self, rs: GeneralRegister, label: &str) {
+        self.add_sentence(AsmSentence::Bgez(rs, label.to_string()));
+    }
+
     fn label(
         &self,
         label: &str,
@@ -1425,12 +3162,88 @@ impl<'a> AsmBuilder<'a> {
         self.add_sentence(AsmSentence::Label(label.to_string()));
     }
 
+    // Loads and stores encode a 12-bit offset. For larger offsets, the address is computed in a free
+    // register (or the destination register for loads) and accessed with a zero offset. If no
+    // register is free, the base register is temporarily moved instead, which is only possible if
+    // the access doesn't write `dest` over it.
+    fn with_offset_base(
+        &self,
+        base: GeneralRegister,
+        offset: isize,
+        preferred_reg: Option<GeneralRegister>,
+        dest: Option<GeneralRegister>,
+        access: impl Fn(GeneralRegister),
+    ) {
+        if let Some(reg) = preferred_reg {
+            // A scratch register that is also the base can't hold the offset, so step it instead
+            let scratch = (reg != base).then_some(reg);
+            self.addi_unconstrained(reg, base, offset, scratch);
+            access(reg);
+        } else if let Some(reg) = self.try_get_free_reg() {
+            self.addi_unconstrained(reg, base, offset, Some(reg));
+            access(reg);
+            self.release_reg(reg);
+        } else {
+            assert!(
+                dest != Some(base),
+                "Can't move {base:#} back after loading into it, pass a scratch register"
+            );
+            self.addi_unconstrained(base, base, offset, None);
+            access(base);
+            self.addi_unconstrained(base, base, -offset, None);
+        }
+    }
+
     fn load(&self, rd: GeneralRegister, rs: GeneralRegister, offset: isize) {
-        self.add_sentence(AsmSentence::Load(rd, rs, offset));
+        let preferred_reg = (rd != rs && rd != GeneralRegister::Zero).then_some(rd);
+        self.load_with_scratch(rd, rs, offset, preferred_reg);
+    }
+
+    // Load with `scratch` holding the address if the offset is large. It is clobbered unless it is
+    // `rd`, which may also be the base register.
+    fn load_with_scratch(
+        &self,
+        rd: GeneralRegister,
+        rs: GeneralRegister,
+        offset: isize,
+        scratch: Option<GeneralRegister>,
+    ) {
+        if is_imm12(offset) {
+            self.add_sentence(AsmSentence::Load(rd, rs, offset));
+            return;
+        }
+
+        self.with_offset_base(rs, offset, scratch, Some(rd), |base| {
+            self.add_sentence(AsmSentence::Load(rd, base, 0));
+        });
     }
 
     fn store(&self, rs2: GeneralRegister, rs1: GeneralRegister, offset: isize) {
-        self.add_sentence(AsmSentence::Store(rs2, rs1, offset));
+        if is_imm12(offset) {
+            self.add_sentence(AsmSentence::Store(rs2, rs1, offset));
+            return;
+        }
+
+        assert!(
+            rs2 != rs1,
+            "Can't store the base register at a large offset"
+        );
+        self.with_offset_base(rs1, offset, None, None, |base| {
+            self.add_sentence(AsmSentence::Store(rs2, base, 0));
+        });
+    }
+
+    fn fence(&self) {
+        self.add_sentence(AsmSentence::Fence);
+    }
+
+    fn fence_i(&self) {
+        self.option_push();
+        self.add_sentence(AsmSentence::LinkerOption(LinkerOption::ArchExtension(
+            "zifencei",
+        )));
+        self.add_sentence(AsmSentence::FenceI);
+        self.option_pop();
     }
 
     fn sfence(&self, rs1: GeneralRegister, rs2: GeneralRegister) {
@@ -1438,7 +3251,14 @@ impl<'a> AsmBuilder<'a> {
     }
 
     fn fload(&self, rd: FloatingPointRegister, rs: GeneralRegister, offset: isize) {
-        self.add_sentence(AsmSentence::FloatLoad(rd, rs, offset));
+        if is_imm12(offset) {
+            self.add_sentence(AsmSentence::FloatLoad(rd, rs, offset));
+            return;
+        }
+
+        self.with_offset_base(rs, offset, None, None, |base| {
+            self.add_sentence(AsmSentence::FloatLoad(rd, base, 0));
+        });
     }
 
     fn move_to_float(&self, fd: FloatingPointRegister, rs1: GeneralRegister) {
@@ -1446,7 +3266,41 @@ impl<'a> AsmBuilder<'a> {
     }
 
     fn fstore(&self, rs2: FloatingPointRegister, rs1: GeneralRegister, offset: isize) {
-        self.add_sentence(AsmSentence::FloatStore(rs2, rs1, offset));
+        if is_imm12(offset) {
+            self.add_sentence(AsmSentence::FloatStore(rs2, rs1, offset));
+            return;
+        }
+
+        self.with_offset_base(rs1, offset, None, None, |base| {
+            self.add_sentence(AsmSentence::FloatStore(rs2, base, 0));
+        });
+    }
+
+    fn vstore(&self, vs3: VectorRegister, rs1: GeneralRegister) {
+        self.add_sentence(AsmSentence::VectorStore(vs3, rs1));
+    }
+
+    fn vload(&self, vd: VectorRegister, rs1: GeneralRegister) {
+        self.add_sentence(AsmSentence::VectorLoad(vd, rs1));
+    }
+
+    fn vmv_zero(&self, vd: VectorRegister) {
+        self.add_sentence(AsmSentence::VectorMoveZero(vd));
+    }
+
+    fn vsetvl(&self, rd: GeneralRegister, rs1: GeneralRegister, rs2: GeneralRegister) {
+        self.add_sentence(AsmSentence::Vsetvl(rd, rs1, rs2));
+    }
+
+    fn vsetvli_max(&self, rd: GeneralRegister) {
+        self.add_sentence(AsmSentence::VsetvliMax(rd));
+    }
+
+    // Vector instructions are only accepted by the assembler if the V extension is enabled, and the
+    // arch attribute doesn't include it. These have to be paired with option_pop().
+    fn option_enable_vector(&self) {
+        self.option_push();
+        self.add_sentence(AsmSentence::LinkerOption(LinkerOption::ArchExtension("v")));
     }
 
     fn store_zero(&self, rs1: GeneralRegister) {
@@ -1461,6 +3315,44 @@ impl<'a> AsmBuilder<'a> {
         self.add_sentence(AsmSentence::Addi(rd, rs, imm));
     }
 
+    // Same as addi, but `imm` can be outside of the 12-bit immediate range. In that case `imm` is
+    // materialized in `scratch` if one is available, else it is added in steps of 12-bit immediates
+    // which requires rd and rs to be the same register.
+    fn addi_unconstrained(
+        &self,
+        rd: GeneralRegister,
+        rs: GeneralRegister,
+        imm: isize,
+        scratch: Option<GeneralRegister>,
+    ) {
+        if (-2048..=2047).contains(&imm) {
+            self.addi(rd, rs, imm);
+            return;
+        }
+
+        if let Some(scratch) = scratch {
+            self.li_unconstrained(scratch, imm.unsigned_abs());
+            if imm < 0 {
+                self.sub(rd, rs, scratch);
+            } else {
+                self.add(rd, rs, scratch);
+            }
+            return;
+        }
+
+        assert!(
+            rd == rs,
+            "Adding a large immediate without a scratch register requires rd == rs"
+        );
+        let step: isize = if imm < 0 { -2048 } else { 2047 };
+        let mut remaining = imm;
+        while !(-2048..=2047).contains(&remaining) {
+            self.addi(rd, rd, step);
+            remaining -= step;
+        }
+        self.addi(rd, rd, remaining);
+    }
+
     fn xori(&self, rd: GeneralRegister, rs: GeneralRegister, imm: isize) {
         assert!(
             (-2048..=2047).contains(&imm),
@@ -1477,6 +3369,14 @@ impl<'a> AsmBuilder<'a> {
         self.add_sentence(AsmSentence::Wfi);
     }
 
+    fn ebreak(&self) {
+        self.add_sentence(AsmSentence::Ebreak);
+    }
+
+    fn nop(&self) {
+        self.add_sentence(AsmSentence::Nop);
+    }
+
     fn j(&self, label: &str) {
         self.add_sentence(AsmSentence::J(label.to_string()));
     }
@@ -1513,6 +3413,33 @@ impl<'a> AsmBuilder<'a> {
         self.add_sentence(AsmSentence::Mul(rd, rs1, rs2));
     }
 
+    fn slli(&self, rd: GeneralRegister, rs: GeneralRegister, shamt: usize) {
+        self.add_sentence(AsmSentence::Slli(rd, rs, shamt));
+    }
+
+    fn srli(&self, rd: GeneralRegister, rs: GeneralRegister, shamt: usize) {
+        self.add_sentence(AsmSentence::Srli(rd, rs, shamt));
+    }
+
+    fn li_sentry_value(&self, rd: GeneralRegister) {
+        match self.rt_config.rv_xlen() {
+            RvXlen::Rv32 => self.li_unconstrained(rd, SENTRY_VALUE_RV32 as usize),
+            RvXlen::Rv64 => self.li_unconstrained(rd, SENTRY_VALUE_RV64),
+            RvXlen::Rv128 => {
+                // The 64-bit pattern in both halves
+                let tmp = self.get_free_reg();
+                self.li_unconstrained(rd, SENTRY_VALUE_RV64);
+                self.slli(tmp, rd, 64);
+                self.or(rd, rd, tmp);
+                self.release_reg(tmp);
+            }
+        }
+    }
+
+    fn divu(&self, rd: GeneralRegister, rs1: GeneralRegister, rs2: GeneralRegister) {
+        self.add_sentence(AsmSentence::Divu(rd, rs1, rs2));
+    }
+
     fn dword(&self, val: u64) {
         self.add_sentence(AsmSentence::Dword(val));
     }
@@ -1522,10 +3449,26 @@ impl<'a> AsmBuilder<'a> {
     }
 
     fn xword(&self, val: usize) {
-        if self.rt_config.xlen_bytes() == 8 {
-            self.dword(val as u64);
-        } else {
-            self.word(val as u32);
+        match self.rt_config.rv_xlen() {
+            RvXlen::Rv32 => self.word(val as u32),
+            RvXlen::Rv64 => self.dword(val as u64),
+            RvXlen::Rv128 => {
+                // Assemblers lack .qword, so emit the little-endian halves
+                self.dword(val as u64);
+                self.dword(0);
+            }
+        }
+    }
+
+    // XLEN-wide word holding the address of `symbol`
+    fn xword_symbol(&self, symbol: &str) {
+        match self.rt_config.rv_xlen() {
+            RvXlen::Rv32 => self.add_sentence(AsmSentence::WordSymbol(symbol.to_string())),
+            RvXlen::Rv64 => self.add_sentence(AsmSentence::DwordSymbol(symbol.to_string())),
+            RvXlen::Rv128 => {
+                self.add_sentence(AsmSentence::DwordSymbol(symbol.to_string()));
+                self.dword(0);
+            }
         }
     }
 
@@ -1549,15 +3492,73 @@ impl<'a> AsmBuilder<'a> {
         self.add_sentence(AsmSentence::Sc(rd, rs2, rs1));
     }
 
+    fn lr(&self, rd: GeneralRegister, rs1: GeneralRegister, ordering: AmoOrdering) {
+        self.add_sentence(AsmSentence::Lr(rd, rs1, ordering));
+    }
+
+    fn amoswap(
+        &self,
+        rd: GeneralRegister,
+        rs1: GeneralRegister,
+        rs2: GeneralRegister,
+        ordering: AmoOrdering,
+    ) {
+        self.add_sentence(AsmSentence::Amoswap(rd, rs1, rs2, ordering));
+    }
+
+    // Test-and-test-and-set acquire of the XLEN-wide lock at the address in `lock_reg`. While the
+    // lock is held, it is only read with plain loads (so that the waiting harts share the cache
+    // line instead of stealing it from the holder) until it looks free again.
+    fn spinlock_acquire(&self, lock_reg: GeneralRegister, acquire: SpinlockAcquire) {
+        let val_reg = self.get_free_reg();
+        let one_reg = self.get_free_reg();
+        let try_label = self.next_label();
+        let wait_label = self.next_label();
+        let acquired_label = self.next_label();
+
+        self.comment("Acquire spinlock");
+        self.li_constrained(one_reg, 1);
+        self.label(&try_label, None, None, None);
+        match acquire {
+            SpinlockAcquire::Amoswap => {
+                self.amoswap(val_reg, lock_reg, one_reg, AmoOrdering::Acquire);
+                self.beqz(val_reg, &forward_label(&acquired_label));
+            }
+            SpinlockAcquire::LrSc => {
+                self.lr(val_reg, lock_reg, AmoOrdering::Acquire);
+                self.bnez(val_reg, &forward_label(&wait_label));
+                self.comment("Lock looks free, retry if the reservation was lost");
+                self.sc(val_reg, one_reg, lock_reg);
+                self.beqz(val_reg, &forward_label(&acquired_label));
+                self.j(&backward_label(&try_label));
+            }
+        }
+        self.label(&wait_label, None, None, None);
+        self.load(val_reg, lock_reg, 0);
+        self.bnez(val_reg, &backward_label(&wait_label));
+        self.j(&backward_label(&try_label));
+        self.label(&acquired_label, None, None, None);
+
+        self.release_reg(val_reg);
+        self.release_reg(one_reg);
+    }
+
+    // Release the lock at the address in `lock_reg`, ordering the critical region before it
+    fn spinlock_release(&self, lock_reg: GeneralRegister) {
+        self.comment("Release spinlock");
+        self.amoswap(
+            GeneralRegister::Zero,
+            lock_reg,
+            GeneralRegister::Zero,
+            AmoOrdering::Release,
+        );
+    }
+
     fn rept(&self, count: usize, val: usize) {
         self.add_sentence(AsmSentence::Rept(
             count / self.rt_config.xlen_bytes() as usize,
         ));
-        if self.rt_config.xlen_bytes() == 8 {
-            self.dword(val as u64);
-        } else {
-            self.word(val as u32);
-        }
+        self.xword(val);
         self.add_sentence(AsmSentence::EndRept);
     }
 
@@ -1573,40 +3574,155 @@ impl<'a> AsmBuilder<'a> {
         self.add_sentence(AsmSentence::Andi(rd, rs, imm));
     }
 
+    fn ori(&self, rd: GeneralRegister, rs: GeneralRegister, imm: isize) {
+        assert!(
+            (-2048..=2047).contains(&imm),
+            "Immediate value out of range"
+        );
+        self.add_sentence(AsmSentence::Ori(rd, rs, imm));
+    }
+
     fn align(&self, alignment_bytes: usize) {
         self.add_sentence(AsmSentence::Align(alignment_bytes));
     }
 
-    fn preamble(&self) {
-        if self.rt_config.rv_xlen() == RvXlen::Rv64 {
-            // Workaround required to silence the compiler warnings for the generated code.
-            // Since we are using AMO instructions, the compiler is incorrectly printing out non-fatal errors.
-            // See https://github.com/rust-lang/rust/issues/80608. Defaulting to rv64gc on rv64 platforms
-            // seems to silence these prints. Adding this workaround here until the compiler bug gets fixed.
-            self.add_sentence(AsmSentence::Attribute(
-                "arch".to_string(),
-                "rv64gc".to_string(),
-            ));
-        }
+    // Alignment of the runtime code labels. Compressed instructions only need the 2-byte alignment
+    // the assembler keeps anyway, so no padding is added when compression is allowed. Labels that
+    // the hardware requires to be 4-byte aligned (i.e. the trap vector) use
+    // RV_INSTRUCTION_ALIGNMENT_BYTES directly.
+    fn code_alignment(&self) -> Option<usize> {
+        (self.rt_config.compressed_code != CompressedCode::Allow)
+            .then_some(RV_INSTRUCTION_ALIGNMENT_BYTES)
     }
 
-    // Set a bit (corresponding to passed flag) in given register `reg`.
-    // All other rt_flag bits set in `reg` are cleared.
-    fn set_rt_flag_bit(&self, reg: GeneralRegister, flag: RtFlagBit) {
-        self.addi(reg, GeneralRegister::Zero, flag.as_mask());
+    fn align_code(&self) {
+        if let Some(alignment) = self.code_alignment() {
+            self.align(alignment);
+        }
     }
 
-    // Write value in given register `reg` to rt_flags in tpblock.
-    fn write_rt_flags_to_tpblock(&self, reg: GeneralRegister) {
-        self.store(
-            reg,
-            GeneralRegister::Tp,
-            self.rt_config.tp_block_rt_flags_offset(),
-        );
+    fn balign(&self, alignment_bytes: usize) {
+        self.add_sentence(AsmSentence::Balign(alignment_bytes));
     }
 
-    // Clear out value of rt_flags in tpblock by writing zeros to it.
-    fn clear_rt_flags_in_tpblock(&self) {
+    fn preamble(&self) {
+        // Recording the ISA also silences the compiler warnings for the AMO instructions in the
+        // generated code, see https://github.com/rust-lang/rust/issues/80608. The ISA string
+        // depends on the instructions that end up being used, so it is filled in by postamble().
+        if self.rt_config.rv_xlen() != RvXlen::Rv128 {
+            self.add_sentence(AsmSentence::Attribute("arch".to_string(), String::new()));
+        }
+
+        // .eh_frame is discarded by the linker script
+        if self.rt_config.unwind_info {
+            self.add_sentence(AsmSentence::CfiSections(".debug_frame".to_string()));
+        }
+
+        // The compression and relaxation settings are scoped to the runtime code, see postamble()
+        if !self.rt_config.needs_scoped_options() {
+            return;
+        }
+        self.option_push();
+        match self.rt_config.compressed_code {
+            CompressedCode::AssemblerDefault => {}
+            CompressedCode::Allow => {
+                self.add_sentence(AsmSentence::LinkerOption(LinkerOption::Rvc))
+            }
+            CompressedCode::Disallow => {
+                self.add_sentence(AsmSentence::LinkerOption(LinkerOption::NoRvc))
+            }
+        }
+        if self.rt_config.gp_independent {
+            // Without relaxation, `la` and `call` are never turned into gp-relative accesses
+            self.option_norelax();
+        }
+    }
+
+    fn postamble(&self) {
+        if self.rt_config.needs_scoped_options() {
+            self.option_pop();
+        }
+
+        if self.rt_config.cfi {
+            self.gnu_property_note();
+        }
+
+        let arch = self.arch_string();
+        for sentence in self.sentences.borrow_mut().iter_mut() {
+            if let AsmSentence::Attribute(name, value) = sentence {
+                if name == "arch" {
+                    *value = arch.clone();
+                }
+            }
+        }
+    }
+
+    // ISA string matching the enabled features and the instructions emitted so far
+    fn arch_string(&self) -> String {
+        let rt_config = self.rt_config;
+        let mut arch = match rt_config.rv_xlen() {
+            RvXlen::Rv32 => "rv32i",
+            RvXlen::Rv64 => "rv64i",
+            RvXlen::Rv128 => "rv128i",
+        }
+        .to_string();
+
+        let sentences = self.sentences.borrow();
+        let uses_mul_div = sentences
+            .iter()
+            .any(|sentence| matches!(sentence, AsmSentence::Mul(..) | AsmSentence::Divu(..)));
+        let uses_fence_i = sentences
+            .iter()
+            .any(|sentence| matches!(sentence, AsmSentence::FenceI));
+        if uses_mul_div {
+            arch.push('m');
+        }
+        if rt_config.supports_atomic_extension() {
+            arch.push('a');
+        }
+        if rt_config.floating_point_support {
+            arch.push('f');
+            if rt_config.float_width == FloatWidth::Double {
+                arch.push('d');
+            }
+        }
+        // The assembler default doesn't tell whether the target has the C extension, so it is only
+        // claimed if compressed code was explicitly allowed
+        if rt_config.compressed_code == CompressedCode::Allow {
+            arch.push('c');
+        }
+        if rt_config.vector_support() {
+            arch.push('v');
+        }
+
+        if rt_config.cfi {
+            arch.push_str("_zicfilp1p0");
+        }
+        // CSR accesses are used throughout the runtime
+        arch.push_str("_zicsr");
+        if uses_fence_i {
+            arch.push_str("_zifencei");
+        }
+        arch
+    }
+
+    // Set a bit (corresponding to passed flag) in given register `reg`.
+    // All other rt_flag bits set in `reg` are cleared.
+    fn set_rt_flag_bit(&self, reg: GeneralRegister, flag: RtFlagBit) {
+        self.addi(reg, GeneralRegister::Zero, flag.as_mask());
+    }
+
+    // Write value in given register `reg` to rt_flags in tpblock.
+    fn write_rt_flags_to_tpblock(&self, reg: GeneralRegister) {
+        self.store(
+            reg,
+            GeneralRegister::Tp,
+            self.rt_config.tp_block_rt_flags_offset(),
+        );
+    }
+
+    // Clear out value of rt_flags in tpblock by writing zeros to it.
+    fn clear_rt_flags_in_tpblock(&self) {
         self.comment("Clear out RT state (flags) in tpblock");
         self.write_rt_flags_to_tpblock(GeneralRegister::Zero);
     }
@@ -1624,7 +3740,7 @@ impl<'a> AsmBuilder<'a> {
     fn store_rt_flags_to_trapframe(&self, reg: GeneralRegister) {
         self.store(
             reg,
-            GeneralRegister::Sp,
+            self.get_trap_frame_base_reg(),
             self.rt_config.rt_state_addr_offset(),
         );
     }
@@ -1633,7 +3749,7 @@ impl<'a> AsmBuilder<'a> {
     fn load_rt_flags_from_trapframe(&self, reg: GeneralRegister) {
         self.load(
             reg,
-            GeneralRegister::Sp,
+            self.get_trap_frame_base_reg(),
             self.rt_config.rt_state_addr_offset(),
         );
     }
@@ -1661,9 +3777,39 @@ fn zero_trap_csrs(asm: &AsmBuilder) {
     asm.comment("Zero out interrupt/exception CSRs");
     asm.csrw_zero(Csr::Ie);
     if asm.rt_config.rv_mode() == RvMode::MMode {
-        asm.csrw_zero(Csr::Mideleg);
-        asm.csrw_zero(Csr::Medeleg);
+        write_deleg_csr(asm, Csr::Mideleg, asm.rt_config.mideleg_mask);
+        write_deleg_csr(asm, Csr::Medeleg, asm.rt_config.medeleg_mask);
+    }
+}
+
+// Nothing is enabled unless a mask was configured, zero_trap_csrs() already cleared the rest
+fn write_ie(asm: &AsmBuilder) {
+    if asm.rt_config.ie_mask == 0 {
+        return;
+    }
+
+    let reg = asm.get_free_reg();
+    asm.comment(&format!(
+        "Enable the configured interrupts in {:#}",
+        asm.rt_config.csr(Csr::Ie)
+    ));
+    asm.li_unconstrained(reg, asm.rt_config.ie_mask);
+    asm.csrs(Csr::Ie, reg);
+    asm.release_reg(reg);
+}
+
+// Nothing is delegated unless a mask was configured
+fn write_deleg_csr(asm: &AsmBuilder, csr: Csr, mask: usize) {
+    if mask == 0 {
+        asm.csrw_zero(csr);
+        return;
     }
+
+    let reg = asm.get_free_reg();
+    asm.comment(&format!("Delegate the configured traps via {csr:#}"));
+    asm.li_unconstrained(reg, mask);
+    asm.csrw(csr, reg);
+    asm.release_reg(reg);
 }
 
 fn write_gp(asm: &AsmBuilder) {
@@ -1682,26 +3828,42 @@ fn backward_label(label: &str) -> String {
     format!("{label:#}b")
 }
 
+// Zero the xwords from the address in `start_reg` up to the one in `end_reg`, clobbering
+// `start_reg`
+fn zero_range(asm: &AsmBuilder, start_reg: GeneralRegister, end_reg: GeneralRegister) {
+    let loop_label = asm.next_label();
+    let exit_label = asm.next_label();
+
+    asm.bgeu(start_reg, end_reg, &forward_label(&exit_label));
+    asm.label(&loop_label, None, None, None);
+    asm.store_zero(start_reg);
+    asm.addi(start_reg, start_reg, asm.rt_config.xlen_bytes());
+    asm.bltu(start_reg, end_reg, &backward_label(&loop_label));
+    asm.label(&exit_label, None, None, None);
+}
+
 fn zero_bss(asm: &AsmBuilder) {
     if asm.rt_config.is_skip_bss_clearing() {
         return;
     }
+    if asm.rt_config.bss_init_groups.is_some() {
+        init_bss_group(asm);
+        let addr_reg = asm.get_free_reg();
+        let val_reg = asm.get_free_reg();
+        asm.comment("The boot hart also needs the main BSS, owned by group 0");
+        asm.la(addr_reg, &asm.get_label_from_map(LabelType::BssInitDone));
+        wait_for_flag(asm, addr_reg, val_reg);
+        asm.release_reg(addr_reg);
+        asm.release_reg(val_reg);
+        return;
+    }
     asm.comment("Zero out BSS");
     let start_reg = asm.get_free_reg();
     let end_reg = asm.get_free_reg();
 
     asm.la(start_reg, &SectionType::Bss.section_entry_start_symbol());
     asm.la(end_reg, &SectionType::Bss.section_entry_end_symbol());
-
-    let loop_label = asm.next_label();
-    let exit_label = asm.next_label();
-
-    asm.bgeu(start_reg, end_reg, &forward_label(&exit_label));
-    asm.label(&loop_label, None, None, None);
-    asm.store_zero(start_reg);
-    asm.addi(start_reg, start_reg, asm.rt_config.xlen_bytes());
-    asm.bltu(start_reg, end_reg, &backward_label(&loop_label));
-    asm.label(&exit_label, None, None, None);
+    zero_range(asm, start_reg, end_reg);
 
     asm.release_reg(start_reg);
     asm.release_reg(end_reg);
@@ -1711,7 +3873,7 @@ fn zero_bss(asm: &AsmBuilder) {
         let val_reg = asm.get_free_reg();
 
         asm.comment("Mark BSS init done");
-        asm.la(addr_reg, &asm.get_label_from_map(LabelType::BssInitDone));
+        bss_init_done_addr(asm, addr_reg);
         asm.li_constrained(val_reg, 1);
         asm.store(val_reg, addr_reg, 0);
 
@@ -1724,7 +3886,7 @@ fn init_stack_pointer_using_boot_id(asm: &AsmBuilder) {
     asm.comment("Initialize stack pointer using boot id");
 
     let sub = asm.get_free_reg();
-    asm.li_unconstrained(sub, asm.rt_config.hart_stack_size());
+    asm.li_unconstrained(sub, asm.rt_config.target_config.per_hart_stack_stride());
     asm.mul(sub, sub, asm.get_boot_id_reg());
 
     let sp = GeneralRegister::Sp;
@@ -1762,11 +3924,7 @@ fn protect_stack(asm: &AsmBuilder) {
 
     let sentry_value = asm.get_free_reg();
 
-    if asm.rt_config.target_config.hart_config.rv_xlen == RvXlen::Rv32 {
-        asm.li_unconstrained(sentry_value, SENTRY_VALUE_RV32 as usize);
-    } else {
-        asm.li_unconstrained(sentry_value, SENTRY_VALUE_RV64);
-    }
+    asm.li_sentry_value(sentry_value);
     asm.store(sentry_value, stack_bottom, 0);
 
     asm.release_reg(sentry_value);
@@ -1776,7 +3934,7 @@ fn protect_stack(asm: &AsmBuilder) {
 fn switch_to(asm: &AsmBuilder) {
     // Drain free reg pool. We don't have any free regs at this point.
     asm.drain_free_reg_pool();
-    asm.align(RV_INSTRUCTION_ALIGNMENT_BYTES);
+    asm.align_code();
     asm.global_function(&GEN_FUNC_MAP.asm_fn(GeneratedFunc::SwitchTo));
     asm.comment("input: a0 contains address of the thread block to switch to");
     let sp = GeneralRegister::Sp;
@@ -1837,7 +3995,7 @@ fn switch_to(asm: &AsmBuilder) {
 fn goto_rust_entrypoint(asm: &AsmBuilder) {
     asm.label(
         &asm.get_label_from_map(LabelType::JumpToRustEntrypoint),
-        Some(RV_INSTRUCTION_ALIGNMENT_BYTES),
+        asm.code_alignment(),
         Some(&text_default_section()),
         Some(asm.text_section_flags()),
     );
@@ -1870,12 +4028,20 @@ fn goto_rust_entrypoint(asm: &AsmBuilder) {
     asm.load(reg, tp, asm.rt_config.rust_entrypoint_offset());
     asm.la(GeneralRegister::Ra, &restore_trap_frame_label);
 
+    let started_reg = asm.get_free_reg();
+    asm.comment("Mark this hart as started for other harts to observe");
+    asm.li_constrained(started_reg, 1);
+    asm.store(started_reg, tp, asm.rt_config.tp_block_started_offset());
+    asm.release_reg(started_reg);
+
     asm.jr(reg);
     asm.release_reg(reg);
 }
 
+// Only used on the reset path, the trap entrypoints are called without arguments
 fn jump_to_rust_entrypoint(asm: &AsmBuilder, entrypoint: &str) {
     write_entrypoint_in_tp(asm, entrypoint);
+    write_entrypoint_args(asm);
     if asm.rt_config.needs_stack_overflow_detection() {
         asm.j(&asm.get_label_from_map(LabelType::ProtectStack));
     } else {
@@ -1883,10 +4049,28 @@ fn jump_to_rust_entrypoint(asm: &AsmBuilder, entrypoint: &str) {
     }
 }
 
+// The argument registers are neither clobbered by the stack protection nor by the trap frame
+// creation on the way to the entrypoint
+fn write_entrypoint_args(asm: &AsmBuilder) {
+    if !asm.rt_config.entrypoint_args {
+        return;
+    }
+
+    let tp = GeneralRegister::Tp;
+    asm.comment("Pass boot id, hart id and the a1 value at reset to the Rust entrypoint");
+    asm.load(GeneralRegister::A0, tp, asm.rt_config.boot_id_offset());
+    asm.load(GeneralRegister::A1, tp, asm.rt_config.hart_id_offset());
+    asm.load(
+        GeneralRegister::A2,
+        tp,
+        asm.rt_config.tp_block_boot_arg_offset(),
+    );
+}
+
 fn protect_stack_section(asm: &AsmBuilder) {
     asm.label(
         &asm.get_label_from_map(LabelType::ProtectStack),
-        Some(RV_INSTRUCTION_ALIGNMENT_BYTES),
+        asm.code_alignment(),
         Some(&text_default_section()),
         Some(asm.text_section_flags()),
     );
@@ -1897,11 +4081,12 @@ fn protect_stack_section(asm: &AsmBuilder) {
 fn nonboot_hart_call_rust_entrypoint(asm: &AsmBuilder) {
     asm.label(
         &asm.get_label_from_map(LabelType::SecondaryStart),
-        Some(RV_INSTRUCTION_ALIGNMENT_BYTES),
+        asm.code_alignment(),
         None,
         None,
     );
     wait_for_bss_init_done(asm);
+    wait_for_secondary_release(asm);
     asm.comment("Jump to Rust entrypoint on non-boot hart");
     jump_to_rust_entrypoint(asm, asm.rt_config.nonboot_hart_rust_entrypoint());
 }
@@ -1912,7 +4097,7 @@ fn boothart_call_rust_entrypoint(asm: &AsmBuilder) {
 }
 
 fn park_hart(asm: &AsmBuilder) {
-    asm.align(RV_INSTRUCTION_ALIGNMENT_BYTES);
+    asm.align_code();
     let park_label = asm.get_label_from_map(LabelType::ParkHart);
     asm.global_function(&park_label);
     asm.wfi();
@@ -1929,6 +4114,33 @@ fn define_hart_idx_variable(asm: &AsmBuilder) {
     asm.comment("Variable for determining boot id");
     asm.xword(0);
     asm.end_section();
+
+    if asm.rt_config.supports_atomic_extension() {
+        return;
+    }
+
+    let slots_size =
+        asm.rt_config.target_config.hart_id_limit() * asm.rt_config.xlen_bytes() as usize;
+    for (label, comment) in [
+        (
+            LabelType::BootIdChoosing,
+            "Per hart id flags of the boot id lock, set while picking a ticket",
+        ),
+        (
+            LabelType::BootIdTicket,
+            "Per hart id tickets of the boot id lock, 0 when not waiting",
+        ),
+    ] {
+        asm.label(
+            &asm.get_label_from_map(label),
+            None,
+            Some(&data_default_section()),
+            None,
+        );
+        asm.comment(comment);
+        asm.rept(slots_size, 0);
+        asm.end_section();
+    }
 }
 
 // Defining a default thread pointer block. This can be used by projects that don't care about
@@ -1961,20 +4173,256 @@ fn define_bss_init_done(asm: &AsmBuilder) {
         None,
     );
     asm.comment("Variable for indicating bss clearing status");
-    asm.xword(0);
+    match asm.rt_config.bss_init_groups {
+        Some((_, group_count)) => asm.rept(group_count * asm.rt_config.xlen_bytes() as usize, 0),
+        None => asm.xword(0),
+    }
+    asm.end_section();
+
+    let Some((_, group_count)) = asm.rt_config.bss_init_groups else {
+        return;
+    };
+    asm.label(
+        &asm.get_label_from_map(LabelType::BssInitClaim),
+        None,
+        Some(&data_default_section()),
+        None,
+    );
+    asm.comment("Claimed by the hart clearing the BSS of each group");
+    asm.rept(group_count * asm.rt_config.xlen_bytes() as usize, 0);
     asm.end_section();
 }
 
+// BSS of the hart group `group`, see RtConfig::with_bss_init_groups()
+fn bss_group_section(group: usize) -> SectionType {
+    match group {
+        0 => SectionType::Bss,
+        _ => SectionType::Custom(format!("bss_group{group}"), 0),
+    }
+}
+
+// Park the harts whose group is out of the configured ones, before they take a boot id
+fn park_harts_outside_bss_init_groups(asm: &AsmBuilder) {
+    let Some((hart_id_shift, group_count)) = asm.rt_config.bss_init_groups else {
+        return;
+    };
+    let group_reg = asm.get_free_reg();
+    let count_reg = asm.get_free_reg();
+    let in_range_label = asm.next_label();
+
+    asm.comment("Park hart if its BSS init group is not below the group count");
+    match asm.rt_config.rv_mode() {
+        RvMode::MMode => asm.csrr(group_reg, Csr::Mhartid),
+        RvMode::SMode => asm.mov(group_reg, GeneralRegister::A0),
+    }
+    asm.srli(group_reg, group_reg, hart_id_shift);
+    asm.li_unconstrained(count_reg, group_count);
+    asm.bltu(group_reg, count_reg, &forward_label(&in_range_label));
+    asm.la(group_reg, &asm.get_label_from_map(LabelType::ParkHart));
+    asm.jr(group_reg);
+    asm.label(&in_range_label, None, None, None);
+
+    asm.release_reg(group_reg);
+    asm.release_reg(count_reg);
+}
+
+// The first hart of the group to get here clears the BSS of the group and marks it done, the
+// others wait for it. Expects tp to point to the current hart's thread pointer block.
+fn init_bss_group(asm: &AsmBuilder) {
+    let Some((hart_id_shift, group_count)) = asm.rt_config.bss_init_groups else {
+        return;
+    };
+    let group_reg = asm.get_free_reg();
+    let start_reg = asm.get_free_reg();
+    let end_reg = asm.get_free_reg();
+    let val_reg = asm.get_free_reg();
+    let clear_label = asm.next_label();
+    let wait_label = asm.next_label();
+
+    asm.comment("Claim the BSS init of this hart's group");
+    asm.load(
+        group_reg,
+        GeneralRegister::Tp,
+        asm.rt_config.hart_id_offset(),
+    );
+    asm.srli(group_reg, group_reg, hart_id_shift);
+    asm.la(start_reg, &asm.get_label_from_map(LabelType::BssInitClaim));
+    asm.slli(
+        val_reg,
+        group_reg,
+        asm.rt_config.xlen_bytes().trailing_zeros() as usize,
+    );
+    asm.add(start_reg, start_reg, val_reg);
+    asm.li_constrained(val_reg, 1);
+    asm.amoswap(val_reg, start_reg, val_reg, AmoOrdering::Acquire);
+    asm.bnez(val_reg, &forward_label(&wait_label));
+
+    asm.comment("Zero out the BSS of this hart's group");
+    for group in 0..group_count {
+        let section = bss_group_section(group);
+        // Harts of the other groups are parked, so the last group needs no check
+        let next_label = (group + 1 < group_count).then(|| asm.next_label());
+        if let Some(next_label) = &next_label {
+            asm.li_unconstrained(val_reg, group);
+            asm.bne(group_reg, val_reg, &forward_label(next_label));
+        }
+        if group != 0 {
+            asm.weak(&section.section_entry_start_symbol());
+            asm.weak(&section.section_entry_end_symbol());
+        }
+        asm.la(start_reg, &section.section_entry_start_symbol());
+        asm.la(end_reg, &section.section_entry_end_symbol());
+        if let Some(next_label) = &next_label {
+            asm.j(&forward_label(&clear_label));
+            asm.label(next_label, None, None, None);
+        }
+    }
+    asm.label(&clear_label, None, None, None);
+    zero_range(asm, start_reg, end_reg);
+    asm.release_reg(end_reg);
+
+    asm.comment("Order the clear before the done flag of the group, which releases its harts");
+    asm.fence();
+    bss_init_done_addr(asm, start_reg);
+    asm.li_constrained(val_reg, 1);
+    asm.store(val_reg, start_reg, 0);
+
+    asm.label(&wait_label, None, None, None);
+    asm.comment("Wait for the BSS init of this hart's group");
+    bss_init_done_addr(asm, start_reg);
+    wait_for_flag(asm, start_reg, val_reg);
+
+    asm.release_reg(group_reg);
+    asm.release_reg(start_reg);
+    asm.release_reg(val_reg);
+}
+
+// Spin until the xword at the address in `addr_reg` is set, and order the accesses following it
+// after the ones preceding the write
+fn wait_for_flag(asm: &AsmBuilder, addr_reg: GeneralRegister, val_reg: GeneralRegister) {
+    let loopback_label = asm.next_label();
+
+    asm.label(&loopback_label, None, None, None);
+    asm.load(val_reg, addr_reg, 0);
+    asm.beqz(val_reg, &backward_label(&loopback_label));
+    asm.fence();
+}
+
+// Load the address of the BSS init done flag of the current hart's group in `addr_reg`. Expects tp
+// to point to the current hart's thread pointer block.
+fn bss_init_done_addr(asm: &AsmBuilder, addr_reg: GeneralRegister) {
+    asm.la(addr_reg, &asm.get_label_from_map(LabelType::BssInitDone));
+
+    let Some((hart_id_shift, _)) = asm.rt_config.bss_init_groups else {
+        return;
+    };
+    let group_reg = asm.get_free_reg();
+    asm.comment("Select the flag of this hart's group");
+    asm.load(
+        group_reg,
+        GeneralRegister::Tp,
+        asm.rt_config.hart_id_offset(),
+    );
+    asm.srli(group_reg, group_reg, hart_id_shift);
+    asm.slli(
+        group_reg,
+        group_reg,
+        asm.rt_config.xlen_bytes().trailing_zeros() as usize,
+    );
+    asm.add(addr_reg, addr_reg, group_reg);
+    asm.release_reg(group_reg);
+}
+
 fn wait_for_bss_init_done(asm: &AsmBuilder) {
     if asm.rt_config.is_skip_bss_clearing() {
         return;
     }
+    if asm.rt_config.bss_init_groups.is_some() {
+        init_bss_group(asm);
+        return;
+    }
     let addr_reg = asm.get_free_reg();
     let val_reg = asm.get_free_reg();
 
     let loopback_label = asm.next_label();
     asm.comment("Wait for BSS init done");
-    asm.la(addr_reg, &asm.get_label_from_map(LabelType::BssInitDone));
+    bss_init_done_addr(asm, addr_reg);
+    asm.label(&loopback_label, None, None, None);
+    asm.load(val_reg, addr_reg, 0);
+    asm.beqz(val_reg, &backward_label(&loopback_label));
+
+    asm.release_reg(addr_reg);
+    asm.release_reg(val_reg);
+}
+
+fn define_secondary_release_gate(asm: &AsmBuilder) {
+    if !asm.rt_config.secondary_release_gate {
+        return;
+    }
+    asm.label(
+        &asm.get_label_from_map(LabelType::SecondaryRelease),
+        None,
+        Some(&data_default_section()),
+        None,
+    );
+    asm.comment("Variable set by the boot hart to release the non-boot harts");
+    asm.xword(0);
+    asm.end_section();
+}
+
+fn define_boot_lock(asm: &AsmBuilder) {
+    if !asm.rt_config.boot_lock {
+        return;
+    }
+    asm.label(
+        &asm.get_label_from_map(LabelType::BootLock),
+        None,
+        Some(&data_default_section()),
+        None,
+    );
+    asm.comment("Spinlock serializing one-time init steps across harts");
+    asm.xword(0);
+    asm.end_section();
+}
+
+// Only harts which all start at the reset vector race to copy the reset entry
+fn reset_entry_copy_needs_election(asm: &AsmBuilder) -> bool {
+    asm.rt_config
+        .target_config
+        .reset_entry_load_region()
+        .is_some()
+        && asm.rt_config.multihart_reset_handling_required()
+}
+
+fn define_reset_entry_copy(asm: &AsmBuilder) {
+    if !reset_entry_copy_needs_election(asm) {
+        return;
+    }
+    asm.label(
+        &asm.get_label_from_map(LabelType::ResetEntryCopy),
+        None,
+        Some(&data_default_section()),
+        None,
+    );
+    asm.comment("Claimed by the hart copying the reset entry, then set once the copy is done");
+    asm.xword(0);
+    asm.xword(0);
+    asm.end_section();
+}
+
+fn wait_for_secondary_release(asm: &AsmBuilder) {
+    if !asm.rt_config.secondary_release_gate {
+        return;
+    }
+    let addr_reg = asm.get_free_reg();
+    let val_reg = asm.get_free_reg();
+
+    let loopback_label = asm.next_label();
+    asm.comment("Wait for the boot hart to release the non-boot harts");
+    asm.la(
+        addr_reg,
+        &asm.get_label_from_map(LabelType::SecondaryRelease),
+    );
     asm.label(&loopback_label, None, None, None);
     asm.load(val_reg, addr_reg, 0);
     asm.beqz(val_reg, &backward_label(&loopback_label));
@@ -1989,7 +4437,7 @@ fn hart_count_error_handling(asm: &AsmBuilder) {
     let park_addr_reg = asm.get_free_reg();
 
     asm.comment("Park hart if boot id is greater than max hart count defined in configuration");
-    asm.li_constrained(max_hart_count, asm.rt_config.max_hart_count());
+    asm.li_unconstrained(max_hart_count, asm.rt_config.max_hart_count());
     asm.bltu(
         asm.get_boot_id_reg(),
         max_hart_count,
@@ -2017,7 +4465,13 @@ fn read_hart_id(asm: &AsmBuilder) {
 fn determine_boot_id(asm: &AsmBuilder) {
     let boot_id = asm.get_boot_id_reg();
 
-    if asm.rt_config.is_multi_hart() {
+    if asm.rt_config.is_multi_hart() && !asm.rt_config.supports_atomic_extension() {
+        // Without the A extension (no AMOs and no LR/SC), the boot id variable is incremented
+        // under a lock made of plain loads and stores
+        boot_id_bakery_lock(asm);
+
+        hart_count_error_handling(asm);
+    } else if asm.rt_config.is_multi_hart() {
         asm.comment("Determine boot id");
         asm.la(boot_id, &asm.get_label_from_map(LabelType::BootIdxVariable));
 
@@ -2036,6 +4490,118 @@ fn determine_boot_id(asm: &AsmBuilder) {
     }
 }
 
+// Lamport's bakery lock, with the slots indexed by hart id. Each hart takes a ticket above all the
+// ones it sees, then waits for the harts holding a lower ticket (or the same ticket and a lower
+// hart id) before reading and incrementing the boot id variable into the boot id register. Every
+// step is separated by a full fence, as the algorithm relies on sequentially consistent accesses.
+fn boot_id_bakery_lock(asm: &AsmBuilder) {
+    let boot_id = asm.get_boot_id_reg();
+    let xlen_bytes = asm.rt_config.xlen_bytes();
+    let last_slot_offset = (asm.rt_config.target_config.hart_id_limit() - 1) * xlen_bytes as usize;
+
+    // The hart id register is only filled in after the boot id is known, so it holds the byte
+    // offset of the slot of this hart
+    let slot = asm.get_hart_id_reg();
+    let addr = asm.get_free_reg();
+    let ticket = asm.get_free_reg();
+    // Byte offset of the slot of the other hart being looked at, walking down to 0
+    let other = asm.get_free_reg();
+    // Likewise, the boot id register holds the loaded values until the lock is held
+    let val = boot_id;
+
+    let slot_label = asm.next_label();
+    let max_label = asm.next_label();
+    let not_max_label = asm.next_label();
+    let next_hart_label = asm.next_label();
+    let choosing_label = asm.next_label();
+    let ticket_label = asm.next_label();
+    let done_label = asm.next_label();
+
+    asm.comment("Determine boot id under a bakery lock, there are no atomics to use");
+    match asm.rt_config.rv_mode() {
+        RvMode::MMode => asm.csrr(slot, Csr::Mhartid),
+        RvMode::SMode => asm.mov(slot, GeneralRegister::A0),
+    }
+    asm.comment("Park hart if its hart id has no slot in the boot id lock");
+    asm.li_unconstrained(addr, asm.rt_config.target_config.hart_id_limit());
+    asm.bltu(slot, addr, &forward_label(&slot_label));
+    asm.la(addr, &asm.get_label_from_map(LabelType::ParkHart));
+    asm.jr(addr);
+    asm.label(&slot_label, None, None, None);
+    asm.slli(slot, slot, xlen_bytes.trailing_zeros() as usize);
+
+    asm.comment("Flag this hart as choosing a ticket");
+    asm.la(addr, &asm.get_label_from_map(LabelType::BootIdChoosing));
+    asm.add(addr, addr, slot);
+    asm.li_constrained(val, 1);
+    asm.store(val, addr, 0);
+    asm.fence();
+
+    asm.comment("Take a ticket above all the ones taken");
+    asm.mov(ticket, GeneralRegister::Zero);
+    asm.li_unconstrained(other, last_slot_offset);
+    asm.label(&max_label, None, None, None);
+    asm.la(addr, &asm.get_label_from_map(LabelType::BootIdTicket));
+    asm.add(addr, addr, other);
+    asm.load(val, addr, 0);
+    asm.bgeu(ticket, val, &forward_label(&not_max_label));
+    asm.mov(ticket, val);
+    asm.label(&not_max_label, None, None, None);
+    asm.addi(other, other, -xlen_bytes);
+    asm.bgez(other, &backward_label(&max_label));
+    asm.addi(ticket, ticket, 1);
+    asm.la(addr, &asm.get_label_from_map(LabelType::BootIdTicket));
+    asm.add(addr, addr, slot);
+    asm.store(ticket, addr, 0);
+    asm.fence();
+
+    asm.comment("Done choosing");
+    asm.la(addr, &asm.get_label_from_map(LabelType::BootIdChoosing));
+    asm.add(addr, addr, slot);
+    asm.store(GeneralRegister::Zero, addr, 0);
+    asm.fence();
+
+    asm.comment("Wait for the harts which are ahead in the queue");
+    asm.li_unconstrained(other, last_slot_offset);
+    asm.label(&next_hart_label, None, None, None);
+    asm.la(addr, &asm.get_label_from_map(LabelType::BootIdChoosing));
+    asm.add(addr, addr, other);
+    asm.label(&choosing_label, None, None, None);
+    asm.load(val, addr, 0);
+    asm.bnez(val, &backward_label(&choosing_label));
+    asm.fence();
+    asm.la(addr, &asm.get_label_from_map(LabelType::BootIdTicket));
+    asm.add(addr, addr, other);
+    asm.label(&ticket_label, None, None, None);
+    asm.load(val, addr, 0);
+    asm.beqz(val, &forward_label(&done_label));
+    asm.bltu(ticket, val, &forward_label(&done_label));
+    asm.bne(ticket, val, &backward_label(&ticket_label));
+    asm.comment("Same ticket, the lower hart id goes first");
+    asm.bgeu(other, slot, &forward_label(&done_label));
+    asm.j(&backward_label(&ticket_label));
+    asm.label(&done_label, None, None, None);
+    asm.addi(other, other, -xlen_bytes);
+    asm.bgez(other, &backward_label(&next_hart_label));
+    asm.fence();
+
+    asm.comment("Lock held, take the next boot id");
+    asm.la(addr, &asm.get_label_from_map(LabelType::BootIdxVariable));
+    asm.load(boot_id, addr, 0);
+    asm.addi(ticket, boot_id, 1);
+    asm.store(ticket, addr, 0);
+    asm.fence();
+
+    asm.comment("Release the lock");
+    asm.la(addr, &asm.get_label_from_map(LabelType::BootIdTicket));
+    asm.add(addr, addr, slot);
+    asm.store(GeneralRegister::Zero, addr, 0);
+
+    asm.release_reg(addr);
+    asm.release_reg(ticket);
+    asm.release_reg(other);
+}
+
 fn get_stack_bottom(stack_bottom_reg: GeneralRegister, asm: &AsmBuilder) {
     asm.comment("Get stack bottom using boot id");
 
@@ -2060,21 +4626,47 @@ fn get_stack_bottom(stack_bottom_reg: GeneralRegister, asm: &AsmBuilder) {
     asm.release_reg(sub);
 }
 
+// Switch sp to the current hart's abort stack before calling into an abort handler. Expects tp to
+// point to the current hart's thread pointer block.
+fn switch_to_abort_stack(asm: &AsmBuilder) {
+    if !asm.rt_config.target_config.needs_abort_stack() {
+        return;
+    }
+
+    asm.comment("Switch to the abort stack of this hart since the current stack cannot be trusted");
+    // sp is overwritten anyway, so it holds the offset into the abort stacks to save a register
+    let sp = GeneralRegister::Sp;
+    asm.load(sp, GeneralRegister::Tp, asm.rt_config.boot_id_offset());
+    let reg = asm.get_free_reg();
+    asm.li_unconstrained(reg, asm.rt_config.target_config.per_hart_abort_stack_size());
+    asm.mul(sp, sp, reg);
+    asm.la(reg, &abort_stack_top_symbol());
+    asm.sub(sp, reg, sp);
+    asm.release_reg(reg);
+}
+
 fn check_stack(asm: &AsmBuilder) {
     asm.comment("Perform stack overflow detection");
 
     let stack_bottom_reg = asm.get_free_reg();
     get_stack_bottom(stack_bottom_reg, asm);
+    check_stack_sentry(asm, stack_bottom_reg);
+
+    if asm.rt_config.target_config.needs_interrupt_stack() {
+        asm.comment("Perform stack overflow detection on the interrupt stack");
+        get_interrupt_stack_bottom(stack_bottom_reg, asm);
+        check_stack_sentry(asm, stack_bottom_reg);
+    }
+
+    asm.release_reg(stack_bottom_reg);
+}
 
+fn check_stack_sentry(asm: &AsmBuilder, stack_bottom_reg: GeneralRegister) {
     let value_reg = asm.get_free_reg();
     asm.load(value_reg, stack_bottom_reg, 0);
 
     let sentry_value = asm.get_free_reg();
-    if asm.rt_config.target_config.hart_config.rv_xlen == RvXlen::Rv32 {
-        asm.li_unconstrained(sentry_value, SENTRY_VALUE_RV32 as usize);
-    } else {
-        asm.li_unconstrained(sentry_value, SENTRY_VALUE_RV64);
-    }
+    asm.li_sentry_value(sentry_value);
 
     let next_label = asm.next_label();
     asm.comment("If stack overflow is detected, jump to stack overflow handler");
@@ -2093,12 +4685,12 @@ fn check_stack(asm: &AsmBuilder) {
     asm.mov(GeneralRegister::A0, sentry_value);
     asm.comment("Actual current value in a1");
     asm.mov(GeneralRegister::A1, value_reg);
+    switch_to_abort_stack(asm);
     asm.jr(rs);
     asm.release_reg(rs);
 
     asm.label(&next_label, None, None, None);
 
-    asm.release_reg(stack_bottom_reg);
     asm.release_reg(value_reg);
     asm.release_reg(sentry_value);
 }
@@ -2108,33 +4700,88 @@ fn align_up(val: usize, align_to: usize) -> usize {
     (val + align_to - 1) & !(align_to - 1)
 }
 
+fn is_imm12(imm: isize) -> bool {
+    (-2048..=2047).contains(&imm)
+}
+
 fn aligned_trap_frame_size(trap_frame_size: usize) -> usize {
     align_up(trap_frame_size, 16)
 }
 
+// DWARF register number of the alternate frame return column, see the RISC-V psABI
+const DWARF_ALT_FRAME_RETURN_COLUMN: usize = 64;
+
+// The Rust trap entrypoint returns to restore_trap_frame with sp pointing to the trap frame. Debuggers
+// look up the unwind information of the caller at the return address - 1, so it covers a nop placed
+// right before the label. The interrupted pc is the saved epc, in the alternate return column.
+fn trap_frame_unwind_info(asm: &AsmBuilder) {
+    let tf = &asm.rt_config.trap_frame;
+
+    asm.section(&trap_code_section(), Some(asm.text_section_flags()));
+    asm.align_code();
+    asm.comment("Unwind information describing the trap frame for debuggers");
+    asm.add_sentence(AsmSentence::CfiStartProc);
+    asm.add_sentence(AsmSentence::CfiSignalFrame);
+    asm.add_sentence(AsmSentence::CfiReturnColumn(DWARF_ALT_FRAME_RETURN_COLUMN));
+    asm.add_sentence(AsmSentence::CfiDefCfa(GeneralRegister::Sp, 0));
+    asm.add_sentence(AsmSentence::CfiOffset(
+        DWARF_ALT_FRAME_RETURN_COLUMN,
+        tf.csr_idx(Csr::Epc) * asm.rt_config.xlen_bytes(),
+    ));
+    for (gr, offset) in asm
+        .rt_config
+        .trap_frame_access_order(&tf.general_regs, |gr| tf.gr_idx(gr))
+    {
+        asm.add_sentence(AsmSentence::CfiOffset(gr.number(), offset));
+    }
+    asm.nop();
+}
+
 fn restore_trap_frame(asm: &AsmBuilder) {
     let sp = GeneralRegister::Sp;
     let tp = GeneralRegister::Tp;
-    let reg_size = asm.rt_config.xlen_bytes();
 
-    asm.label(
-        &asm.get_label_from_map(LabelType::RestoreTrapFrame),
-        Some(RV_INSTRUCTION_ALIGNMENT_BYTES),
-        Some(&text_default_section()),
-        Some(asm.text_section_flags()),
-    );
+    if asm.rt_config.unwind_info {
+        trap_frame_unwind_info(asm);
+        asm.label(
+            &asm.get_label_from_map(LabelType::RestoreTrapFrame),
+            None,
+            None,
+            None,
+        );
+        asm.add_sentence(AsmSentence::CfiEndProc);
+    } else {
+        // Switch to the trap code section first, so that the alignment applies to it
+        asm.section(&trap_code_section(), Some(asm.text_section_flags()));
+        asm.label(
+            &asm.get_label_from_map(LabelType::RestoreTrapFrame),
+            asm.code_alignment(),
+            None,
+            None,
+        );
+    }
 
     if asm.rt_config.needs_stack_overflow_detection() {
         check_stack(asm);
     }
 
+    asm.reserve_trap_frame_base_reg();
+    let base = asm.get_trap_frame_base_reg();
+
+    // Nonzero if tp has to be reloaded from the trap frame
+    let restore_tp = asm.rt_config.skip_same_context_tp_restore.then(|| {
+        let reg = asm.get_free_reg();
+        asm.mov(reg, GeneralRegister::Zero);
+        reg
+    });
+
     // Unwind current mode stack if returning to lower privilege mode
     let pp = asm.get_free_reg();
     let status = asm.get_free_reg();
     let restore_label = asm.next_label();
 
     asm.comment("Check if returning to lower privilege mode");
-    asm.load(status, sp, asm.rt_config.status_reg_offset());
+    asm.load(status, base, asm.rt_config.status_reg_offset());
     // pp bits are shifted into place as the bitfields themselves and the value
     // can be either 6144 or 256 in decimal. So we are using li_unconstrained()
     // here
@@ -2155,13 +4802,16 @@ fn restore_trap_frame(asm: &AsmBuilder) {
         total_size, asm.rt_config.trap_frame_size()
     );
     asm.comment(comment.as_str());
-    asm.addi(temp_reg, sp, total_size as isize);
+    asm.addi_unconstrained(temp_reg, sp, total_size as isize, Some(temp_reg));
     asm.store(temp_reg, tp, asm.rt_config.current_mode_stack_offset());
 
     asm.csrw(Csr::Scratch, tp);
+    if let Some(restore_tp) = restore_tp {
+        asm.li_constrained(restore_tp, 1);
+    }
 
     asm.label(&restore_label, None, None, None);
-    let restore_csr_label = asm.next_label();
+    let restore_state_label = asm.next_label();
 
     // Restore trapframe address only if rt_flags say so.
     asm.comment(&format!(
@@ -2174,9 +4824,16 @@ fn restore_trap_frame(asm: &AsmBuilder) {
         temp_reg,
         RtFlagBit::RestoreTrapFrameInTpBlock.as_mask(),
     );
-    asm.beqz(temp_reg, &forward_label(&restore_csr_label));
+    if let Some(restore_tp) = restore_tp {
+        asm.or(restore_tp, restore_tp, temp_reg);
+    }
+    asm.beqz(temp_reg, &forward_label(&restore_state_label));
 
-    asm.load(temp_reg, sp, asm.rt_config.interrupted_frame_addr_offset());
+    asm.load(
+        temp_reg,
+        base,
+        asm.rt_config.interrupted_frame_addr_offset(),
+    );
     asm.store_trap_frame_address_to_tpblock(temp_reg);
 
     if asm.rt_config.sfence_on_trapframe_restore_feature {
@@ -2194,6 +4851,10 @@ fn restore_trap_frame(asm: &AsmBuilder) {
         asm.label(&no_sfence, None, None, None);
     }
 
+    // The register state of the frame is restored on every return, whatever the rt_flags say about
+    // the thread pointer block
+    asm.label(&restore_state_label, None, None, None);
+
     // First restore the floating point registers
     if asm.rt_config.floating_point_support {
         asm.comment("Now restore floating point registers if required");
@@ -2203,17 +4864,15 @@ fn restore_trap_frame(asm: &AsmBuilder) {
         asm.andi(temp_reg, temp_reg, RtFlagBit::FsStateWasDirty.as_mask());
         asm.beqz(temp_reg, &forward_label(&fs_clean));
 
-        let fr_start_idx = asm.rt_config.trap_frame.fr_start_idx();
-        for (idx, fr) in asm
+        let tf = &asm.rt_config.trap_frame;
+        for (fr, offset) in asm
             .rt_config
-            .trap_frame
-            .floating_point_registers
-            .iter()
-            .enumerate()
+            .trap_frame_access_order(&tf.floating_point_registers, |fr| tf.fr_idx(fr))
         {
-            let offset = (idx as isize + fr_start_idx) * reg_size;
-            asm.fload(*fr, sp, offset);
+            asm.fload(fr, base, offset);
         }
+        asm.load(temp_reg, base, asm.rt_config.fcsr_offset());
+        asm.csrw(Csr::Fcsr, temp_reg);
 
         // The state is now clean
         asm.load_rt_flags_from_trapframe(temp_reg);
@@ -2223,44 +4882,219 @@ fn restore_trap_frame(asm: &AsmBuilder) {
         asm.label(&fs_clean, None, None, None);
     }
 
+    if asm.rt_config.vector_support() {
+        restore_vector_state(asm, temp_reg);
+    }
+
     // Now restore the CSRs using general registers and then restore general registers.
-    asm.label(&restore_csr_label, None, None, None);
     asm.comment("Restore all CSRs first since they require a general register for csrw");
-    let csr_start_idx = asm.rt_config.trap_frame.csr_start_idx();
-    for (idx, csr) in asm.rt_config.trap_frame.csrs.iter().enumerate() {
-        if csr.restore_from_trap_frame() {
-            asm.load(temp_reg, sp, (idx as isize + csr_start_idx) * reg_size);
-            asm.csrw(*csr, temp_reg);
+    let tf = &asm.rt_config.trap_frame;
+    for (csr, offset) in asm
+        .rt_config
+        .trap_frame_access_order(&tf.csrs, |csr| tf.csr_idx(csr))
+    {
+        if asm.rt_config.is_saved_with_fp_state(csr) {
+            continue;
+        } else if csr == Csr::Satp {
+            restore_satp(asm, temp_reg, offset);
+        } else if csr.restore_from_trap_frame() {
+            asm.load(temp_reg, base, offset);
+            asm.csrw(csr, temp_reg);
         }
     }
 
+    // Reservations are cleared by an sc that rewrites the first slot of the trap frame with the
+    // value restored from it. That is done along with the general register restored from the
+    // slot, or here if the slot holds anything else.
+    let clear_with_first_gr = matches!(
+        tf.ordered_members()[0],
+        TrapFrameMember::GeneralRegister(gr) if gr != sp && (gr != tp || restore_tp.is_none())
+    );
+    if asm.rt_config.supports_atomic_extension() && !clear_with_first_gr {
+        asm.comment("Clear any reservations before performing a context switch");
+        asm.load(temp_reg, base, 0);
+        asm.sc(GeneralRegister::Zero, temp_reg, base);
+    }
+
+    if let Some(restore_tp) = restore_tp {
+        let skip_tp_label = asm.next_label();
+        asm.comment("Restore tp only if returning to a lower mode or to another context");
+        asm.beqz(restore_tp, &forward_label(&skip_tp_label));
+        asm.load(tp, base, asm.rt_config.tp_reg_offset());
+        asm.label(&skip_tp_label, None, None, None);
+        asm.release_reg(restore_tp);
+    }
+
     asm.release_reg(temp_reg);
+    // The general registers are restored from sp, so the dedicated register is no longer needed
+    asm.release_trap_frame_base_reg();
 
     asm.comment("Now restore all general registers except sp - sp is restored last");
-    let gr_start_idx = asm.rt_config.trap_frame.gr_start_idx();
-    for (idx, gr) in asm.rt_config.trap_frame.general_regs.iter().enumerate() {
-        if *gr == sp {
-            // SP is restored just before performing ret
-            assert!(idx != 0, "sp is at idx 0");
+    for (gr, offset) in asm
+        .rt_config
+        .trap_frame_access_order(&tf.general_regs, |gr| tf.gr_idx(gr))
+    {
+        if gr == sp || (gr == tp && restore_tp.is_some()) {
+            // SP is restored just before performing ret, tp has been restored above if needed
             continue;
         }
 
-        let offset = (idx as isize + gr_start_idx) * reg_size;
-        asm.load(*gr, sp, offset);
+        asm.load(gr, sp, offset);
 
-        if asm.rt_config.supports_atomic_extension() && idx == 0 {
+        if asm.rt_config.supports_atomic_extension() && offset == 0 {
             asm.comment("Clear any reservations before performing a context switch");
-            asm.sc(GeneralRegister::Zero, *gr, sp);
+            asm.sc(GeneralRegister::Zero, gr, sp);
         }
     }
 
+    // Every other register now holds its restored value, so only sp itself can hold the address of
+    // a large offset
     asm.comment("Restore sp and perform return from mode");
-    asm.load(sp, sp, asm.rt_config.sp_reg_offset());
+    asm.load_with_scratch(sp, sp, asm.rt_config.sp_reg_offset(), Some(sp));
     asm.mode_ret();
 }
 
+// satp is only written back (and the translation caches flushed) if the frame being restored uses
+// a different address space than the current one. Whether that happened is recorded in the
+// TranslationRegChanged rt flag of the frame.
+fn restore_satp(asm: &AsmBuilder, temp_reg: GeneralRegister, offset: isize) {
+    let base = asm.get_trap_frame_base_reg();
+    let curr_reg = asm.get_free_reg();
+    let unchanged_label = asm.next_label();
+    let done_label = asm.next_label();
+
+    asm.comment("Restore satp and sfence.vma only if it differs from the current satp");
+    asm.load(temp_reg, base, offset);
+    asm.csrr(curr_reg, Csr::Satp);
+    asm.beq(temp_reg, curr_reg, &forward_label(&unchanged_label));
+
+    asm.csrw(Csr::Satp, temp_reg);
+    asm.sfence(GeneralRegister::Zero, GeneralRegister::Zero);
+    asm.load_rt_flags_from_trapframe(temp_reg);
+    asm.ori(
+        temp_reg,
+        temp_reg,
+        RtFlagBit::TranslationRegChanged.as_mask(),
+    );
+    asm.store_rt_flags_to_trapframe(temp_reg);
+    asm.j(&forward_label(&done_label));
+
+    asm.label(&unchanged_label, None, None, None);
+    asm.load_rt_flags_from_trapframe(temp_reg);
+    asm.andi(
+        temp_reg,
+        temp_reg,
+        !RtFlagBit::TranslationRegChanged.as_mask(),
+    );
+    asm.store_rt_flags_to_trapframe(temp_reg);
+
+    asm.label(&done_label, None, None, None);
+    asm.release_reg(curr_reg);
+}
+
+fn write_mcountinhibit(asm: &AsmBuilder) {
+    let Some(mask) = asm.rt_config.mcountinhibit else {
+        return;
+    };
+
+    let reg = asm.get_free_reg();
+    asm.comment("Inhibit the configured counters");
+    asm.li_unconstrained(reg, mask);
+    asm.csrw(Csr::Mcountinhibit, reg);
+    asm.release_reg(reg);
+}
+
+// Write a 64-bit M-mode configuration CSR, using its `high` counterpart for the upper 32 bits on
+// RV32. Both halves are always written, since the reset value of the upper one is unspecified.
+fn write_cfg_csr(asm: &AsmBuilder, csr: Csr, high: Csr, value: usize) {
+    let reg = asm.get_free_reg();
+    asm.comment(&format!("Program {csr:#}"));
+    if asm.rt_config.rv_xlen() == RvXlen::Rv32 {
+        let high_value = (value as u64 >> 32) as usize;
+        if high_value == 0 {
+            asm.csrw_zero(high);
+        } else {
+            asm.li_unconstrained(reg, high_value);
+            asm.csrw(high, reg);
+        }
+        asm.li_unconstrained(reg, value & u32::MAX as usize);
+    } else {
+        asm.li_unconstrained(reg, value);
+    }
+    asm.csrw(csr, reg);
+    asm.release_reg(reg);
+}
+
+fn write_menvcfg(asm: &AsmBuilder) {
+    if let Some(value) = asm.rt_config.menvcfg {
+        write_cfg_csr(asm, Csr::Menvcfg, Csr::Other(0x31a, "menvcfgh"), value);
+    }
+}
+
+fn write_mseccfg(asm: &AsmBuilder) {
+    if let Some(value) = asm.rt_config.mseccfg {
+        write_cfg_csr(asm, Csr::Mseccfg, Csr::Other(0x757, "mseccfgh"), value);
+    }
+}
+
+fn write_satp(asm: &AsmBuilder) {
+    let Some(satp) = asm.rt_config.satp else {
+        return;
+    };
+
+    asm.comment("Initialize address translation");
+    if satp == 0 {
+        asm.csrw_zero(Csr::Satp);
+    } else {
+        let reg = asm.get_free_reg();
+        asm.li_unconstrained(reg, satp);
+        asm.csrw(Csr::Satp, reg);
+        asm.release_reg(reg);
+    }
+    asm.sfence(GeneralRegister::Zero, GeneralRegister::Zero);
+}
+
+fn write_pmp(asm: &AsmBuilder) {
+    let slots = asm.rt_config.pmp_slots();
+    if slots.is_empty() {
+        return;
+    }
+
+    // Each pmpcfg register holds XLEN / 8 entries. On RV64, only the even pmpcfg registers exist.
+    let entries_per_cfg = asm.rt_config.xlen_bytes() as usize;
+    let cfg_reg_stride = match asm.rt_config.rv_xlen() {
+        RvXlen::Rv32 => 1,
+        RvXlen::Rv64 => 2,
+        RvXlen::Rv128 => 4,
+    };
+
+    let reg = asm.get_free_reg();
+    asm.comment("Program the PMP addresses before enabling the entries");
+    for (idx, (addr, _cfg)) in slots.iter().enumerate() {
+        match addr {
+            PmpSlotAddr::Value(addr) => asm.li_unconstrained(reg, *addr),
+            PmpSlotAddr::Symbol(symbol) => {
+                asm.la(reg, symbol);
+                asm.srli(reg, reg, 2);
+            }
+        }
+        asm.csrw(Csr::PmpAddr(idx), reg);
+    }
+
+    asm.comment("Enable the PMP entries");
+    for (cfg_idx, cfgs) in slots.chunks(entries_per_cfg).enumerate() {
+        let value = cfgs
+            .iter()
+            .enumerate()
+            .fold(0, |value, (byte, (_addr, cfg))| value | (cfg << (byte * 8)));
+        asm.li_unconstrained(reg, value);
+        asm.csrw(Csr::PmpCfg(cfg_idx * cfg_reg_stride), reg);
+    }
+    asm.release_reg(reg);
+}
+
 fn write_epc(asm: &AsmBuilder) {
-    // Configure EPC to point to _park_hart so that a return to assembly code
+    // Configure EPC to point to the park hart loop so that a return to assembly code
     // back from the hart rust entrypoint results in hart going into wfi loop.
     let reg = asm.get_free_reg();
     asm.comment("Default action is to park hart on return from Rust code, unless epc is changed by the called code");
@@ -2269,6 +5103,18 @@ fn write_epc(asm: &AsmBuilder) {
     asm.release_reg(reg);
 }
 
+fn clear_mprv(asm: &AsmBuilder) {
+    if !asm.rt_config.clear_mprv {
+        return;
+    }
+
+    let reg = asm.get_free_reg();
+    asm.comment("Make sure loads and stores use the current privilege mode");
+    asm.li_unconstrained(reg, STATUS_MEM_ACCESS_MASK);
+    asm.csrc(Csr::Status, reg);
+    asm.release_reg(reg);
+}
+
 fn write_status(asm: &AsmBuilder) {
     let reg = asm.get_free_reg();
     asm.comment("Default action is to return back to current mode on return from Rust code, unless changed by called code");
@@ -2284,7 +5130,115 @@ fn write_status(asm: &AsmBuilder) {
 }
 
 fn text_reset_section(asm: &AsmBuilder) {
-    asm.global_entrypoint(&reset_section());
+    let reset_section = asm.rt_config.target_config.reset_section();
+    if asm
+        .rt_config
+        .target_config
+        .reset_entry_load_region()
+        .is_none()
+    {
+        asm.global_entrypoint(reset_section);
+        return;
+    }
+
+    asm.global_entrypoint(&reset_entry_copy_section());
+    copy_reset_entry(asm);
+    asm.section(reset_section, Some(asm.text_section_flags()));
+    asm.landing_pad();
+}
+
+// The reset entry is loaded apart from where it runs. Copy it from its load address with
+// PC-relative addressing, since nothing runs from its link address yet, and jump to it. When all
+// harts start here, the first one to claim the copy does it while the others wait for it to be
+// done, so that the run copy isn't rewritten once the boot hart has started using it.
+fn copy_reset_entry(asm: &AsmBuilder) {
+    let src_reg = asm.get_free_reg();
+    let dst_reg = asm.get_free_reg();
+    let end_reg = asm.get_free_reg();
+    let val_reg = asm.get_free_reg();
+    // (wait label, done label) of the copy election
+    let election =
+        reset_entry_copy_needs_election(asm).then(|| (asm.next_label(), asm.next_label()));
+    // The address of the election state is loaded in src_reg while it isn't used for the copy
+    let la_state = || {
+        asm.option_push();
+        asm.option_norelax();
+        asm.la(src_reg, &asm.get_label_from_map(LabelType::ResetEntryCopy));
+        asm.option_pop();
+    };
+
+    if let Some((wait_label, _)) = &election {
+        asm.comment("Claim the copy of the reset entry");
+        la_state();
+        asm.li_constrained(val_reg, 1);
+        asm.amoswap(val_reg, src_reg, val_reg, AmoOrdering::Acquire);
+        asm.bnez(val_reg, &forward_label(wait_label));
+    }
+
+    asm.comment("Copy the reset entry to its run address");
+    asm.option_push();
+    asm.option_norelax();
+    asm.la(src_reg, &reset_entry_load_symbol());
+    asm.la(dst_reg, &reset_entry_start_symbol());
+    asm.la(end_reg, &reset_entry_end_symbol());
+    asm.option_pop();
+
+    let loop_label = asm.next_label();
+    let exit_label = asm.next_label();
+
+    asm.bgeu(dst_reg, end_reg, &forward_label(&exit_label));
+    asm.label(&loop_label, None, None, None);
+    asm.load(val_reg, src_reg, 0);
+    asm.store(val_reg, dst_reg, 0);
+    asm.addi(src_reg, src_reg, asm.rt_config.xlen_bytes());
+    asm.addi(dst_reg, dst_reg, asm.rt_config.xlen_bytes());
+    asm.bltu(dst_reg, end_reg, &backward_label(&loop_label));
+    asm.label(&exit_label, None, None, None);
+
+    if let Some((wait_label, done_label)) = election {
+        asm.comment("Order the copy before the done flag, which releases the waiting harts");
+        la_state();
+        asm.fence();
+        asm.li_constrained(val_reg, 1);
+        asm.store(val_reg, src_reg, asm.rt_config.xlen_bytes());
+        asm.j(&forward_label(&done_label));
+
+        asm.comment("Wait for the copy of the reset entry to be done");
+        asm.label(&wait_label, None, None, None);
+        asm.load(val_reg, src_reg, asm.rt_config.xlen_bytes());
+        asm.beqz(val_reg, &backward_label(&wait_label));
+        asm.fence();
+        asm.label(&done_label, None, None, None);
+    }
+
+    asm.comment("Make the copied code visible to instruction fetch and jump to it");
+    asm.fence_i();
+    asm.option_push();
+    asm.option_norelax();
+    asm.la(dst_reg, &reset_entry_start_symbol());
+    asm.option_pop();
+    asm.jr(dst_reg);
+
+    asm.release_reg(src_reg);
+    asm.release_reg(dst_reg);
+    asm.release_reg(end_reg);
+    asm.release_reg(val_reg);
+}
+
+// Call the early console hook with a valid stack, before BSS is cleared. The hook can't rely on
+// zero-initialized statics, which are cleared once it returns. The call clobbers the caller-saved
+// registers, so only the state kept in the thread pointer block may be live across it.
+fn call_early_console_entrypoint(asm: &AsmBuilder) {
+    let Some(entrypoint) = asm.rt_config.early_console_entrypoint() else {
+        return;
+    };
+
+    write_gp(asm);
+    let rs = asm.get_free_reg();
+    asm.comment("Call the early console hook before BSS is cleared");
+    asm.la(rs, entrypoint);
+    asm.jalr(GeneralRegister::Ra, rs, 0);
+    asm.release_reg(rs);
 }
 
 fn call_custom_reset_entrypoint(asm: &AsmBuilder) {
@@ -2304,15 +5258,18 @@ fn create_trap_frame(asm: &AsmBuilder) {
     let tp = GeneralRegister::Tp;
     let ra = GeneralRegister::Ra;
     let scratch = Csr::Scratch;
-    let reg_size = asm.rt_config.xlen_bytes();
     asm.comment("Create new trapframe");
+    // Switch to the trap code section first, so that the alignment applies to it
+    asm.section(&trap_code_section(), Some(asm.text_section_flags()));
     asm.label(
         &asm.get_label_from_map(LabelType::CreateTrapFrame),
-        Some(RV_INSTRUCTION_ALIGNMENT_BYTES),
-        Some(&text_default_section()),
-        Some(asm.text_section_flags()),
+        asm.code_alignment(),
+        None,
+        None,
     );
-    asm.addi(sp, sp, -asm.rt_config.trap_frame_size());
+    // None of the general registers have been stashed yet (and ra holds the return address), so
+    // there is no scratch register to materialize a large frame size in.
+    asm.addi_unconstrained(sp, sp, -asm.rt_config.trap_frame_size(), None);
 
     asm.comment("Align sp down to ensure it is 16-byte aligned by performing andi sp, sp, ~0xf. This is required by the spec");
     asm.comment("We are doing this in two steps with the following andi instruction(instead of sub the aligned size directly)");
@@ -2323,16 +5280,22 @@ fn create_trap_frame(asm: &AsmBuilder) {
     // First stash the general registers(except SP, TP and RA). Stashed general registers can then be used to read CSRs.
     // SP and TP are saved later since these are stashed from elsewhere: SP <- thread pointer block, TP <- scratch register
     asm.comment("First stash away all the general registers in trap frame except SP, TP and RA - those are stashed from elsewhere");
-    let gr_start_idx = asm.rt_config.trap_frame.gr_start_idx();
-    for (idx, gr) in asm.rt_config.trap_frame.general_regs.iter().enumerate() {
-        if *gr != sp && *gr != tp && *gr != ra {
-            asm.store(*gr, sp, (idx as isize + gr_start_idx) * reg_size);
+    let tf = &asm.rt_config.trap_frame;
+    for (gr, offset) in asm
+        .rt_config
+        .trap_frame_access_order(&tf.general_regs, |gr| tf.gr_idx(gr))
+    {
+        if gr != sp && gr != tp && gr != ra {
+            asm.store(gr, sp, offset);
         }
     }
 
     // All general-purpose registers (except sp, tp) are stashed. So, initialize free reg pool
     asm.init_default_free_reg_pool();
 
+    asm.reserve_trap_frame_base_reg();
+    let base = asm.get_trap_frame_base_reg();
+
     // Save floating point registers if required
     if asm.rt_config.floating_point_support {
         asm.comment("Check if FS is dirty and if so, stash the floating-point registers");
@@ -2349,16 +5312,14 @@ fn create_trap_frame(asm: &AsmBuilder) {
         asm.bne(temp_reg, mask_reg, &forward_label(&fs_clean));
 
         // It is dirty, so stash the FP registers
-        let fr_start_idx = asm.rt_config.trap_frame.fr_start_idx();
-        for (idx, fr) in asm
+        for (fr, offset) in asm
             .rt_config
-            .trap_frame
-            .floating_point_registers
-            .iter()
-            .enumerate()
+            .trap_frame_access_order(&tf.floating_point_registers, |fr| tf.fr_idx(fr))
         {
-            asm.fstore(*fr, sp, (idx as isize + fr_start_idx) * reg_size);
+            asm.fstore(fr, base, offset);
         }
+        asm.csrr(temp_reg, Csr::Fcsr);
+        asm.store(temp_reg, base, asm.rt_config.fcsr_offset());
 
         // Set FS state to Clean
         asm.comment("Now that the FP registers are stashed, set the FS state to Clean");
@@ -2388,6 +5349,11 @@ fn create_trap_frame(asm: &AsmBuilder) {
         asm.label(&fs_clean, None, None, None);
     }
 
+    // Save vector state if required
+    if asm.rt_config.vector_support() {
+        save_vector_state(asm);
+    }
+
     let temp_reg = asm.get_free_reg();
 
     // Stash SP from thread pointer block
@@ -2395,26 +5361,42 @@ fn create_trap_frame(asm: &AsmBuilder) {
         "Stash SP in trap frame using the interrupted mode stack value in thread pointer block",
     );
     asm.load(temp_reg, tp, asm.rt_config.interrupted_mode_stack_offset());
-    asm.store(temp_reg, sp, asm.rt_config.sp_reg_offset());
+    asm.store(temp_reg, base, asm.rt_config.sp_reg_offset());
 
     asm.comment("get ra from thread pointer block and save");
     asm.load(temp_reg, tp, asm.rt_config.return_addr_offset());
-    asm.store(temp_reg, sp, asm.rt_config.ra_reg_offset());
+    asm.store(temp_reg, base, asm.rt_config.ra_reg_offset());
 
     // Stash TP from scratch register
     asm.comment("Stash TP in trap frame using the scratch register value");
     asm.load(temp_reg, tp, asm.rt_config.interrupted_mode_tp_offset());
-    asm.store(temp_reg, sp, asm.rt_config.tp_reg_offset());
+    asm.store(temp_reg, base, asm.rt_config.tp_reg_offset());
 
     // Write 0 to scratch register so that nested traps know that we were already in current mode
     asm.comment("Write 0 to scratch register so that trap entry path knows if we encounter a nested trap in current mode");
     asm.csrw(scratch, GeneralRegister::Zero);
 
     asm.comment("Stash all the CSRs in trap frame");
-    let csr_start_idx = asm.rt_config.trap_frame.csr_start_idx();
-    for (idx, csr) in asm.rt_config.trap_frame.csrs.iter().enumerate() {
-        asm.csrr(temp_reg, *csr);
-        asm.store(temp_reg, sp, (idx as isize + csr_start_idx) * reg_size);
+    for (csr, offset) in asm
+        .rt_config
+        .trap_frame_access_order(&tf.csrs, |csr| tf.csr_idx(csr))
+    {
+        if asm.rt_config.is_saved_with_fp_state(csr) {
+            continue;
+        }
+        asm.csrr(temp_reg, csr);
+        asm.store(temp_reg, base, offset);
+    }
+
+    if tf.spare_csr_slots > 0 {
+        asm.comment("Zero out the spare slots in trap frame");
+        let slots: Vec<usize> = (0..tf.spare_csr_slots).collect();
+        for (_, offset) in asm
+            .rt_config
+            .trap_frame_access_order(&slots, |slot| tf.spare_slot_idx(slot))
+        {
+            asm.store(GeneralRegister::Zero, base, offset);
+        }
     }
 
     // Store rt flags from thread pointer block to trapframe and zero-out flags from thread pointer block
@@ -2426,12 +5408,126 @@ fn create_trap_frame(asm: &AsmBuilder) {
     // Stash trap context frame from thread pointer block
     asm.comment("Stash trap ctx frame address in current trapframe");
     asm.load_trap_frame_address_from_tpblock(temp_reg);
-    asm.store(temp_reg, sp, asm.rt_config.interrupted_frame_addr_offset());
+    asm.store(
+        temp_reg,
+        base,
+        asm.rt_config.interrupted_frame_addr_offset(),
+    );
 
     asm.release_reg(temp_reg);
+    asm.release_trap_frame_base_reg();
     asm.ret();
 }
 
+// Same as the FP state, the vector state is only stashed if VS is dirty. This includes the vector
+// CSRs since these can't be accessed if VS is off.
+fn save_vector_state(asm: &AsmBuilder) {
+    let base = asm.get_trap_frame_base_reg();
+
+    asm.comment("Check if VS is dirty and if so, stash the vector registers");
+    let vs_clean = asm.next_label();
+
+    let status_reg = asm.get_free_reg();
+    let temp_reg = asm.get_free_reg();
+    let mask_reg = asm.get_free_reg();
+
+    // Check for VS != Dirty
+    asm.csrr(status_reg, Csr::Status);
+    asm.li_unconstrained(mask_reg, STATUS_VS_MASK_DIRTY);
+    asm.and(temp_reg, status_reg, mask_reg);
+    asm.bne(temp_reg, mask_reg, &forward_label(&vs_clean));
+
+    // It is dirty, so stash the vector CSRs and registers
+    asm.option_enable_vector();
+    for csr in VECTOR_CSRS {
+        asm.csrr(temp_reg, csr);
+        asm.store(temp_reg, base, asm.rt_config.vector_csr_offset(csr));
+    }
+    asm.comment("Whole register stores honour vstart, so clear it before stashing the registers");
+    asm.csrw(Csr::Vstart, GeneralRegister::Zero);
+    for (idx, vr) in asm.rt_config.trap_frame.vector_registers.iter().enumerate() {
+        asm.addi_unconstrained(
+            temp_reg,
+            base,
+            asm.rt_config.vector_register_offset(idx),
+            Some(temp_reg),
+        );
+        asm.vstore(*vr, temp_reg);
+    }
+    asm.option_pop();
+
+    // Set VS state to Clean
+    asm.comment("Now that the vector registers are stashed, set the VS state to Clean");
+    // Invert the mask
+    asm.xori(mask_reg, mask_reg, -1);
+    // Clear the VS bits
+    asm.and(temp_reg, mask_reg, status_reg);
+    // Write Clean state into VS
+    asm.li_unconstrained(mask_reg, STATUS_VS_CLEAN);
+    asm.or(status_reg, temp_reg, mask_reg);
+    asm.csrw(Csr::Status, status_reg);
+    asm.release_reg(status_reg);
+
+    // Indicate that the vector state needs to be restored as well
+    asm.comment("Record the fact that the vector registers will need to be restored in RT flags");
+    asm.read_rt_flags_from_tpblock(temp_reg);
+    asm.li_unconstrained(
+        mask_reg,
+        RtFlagBit::VsStateWasDirty.as_mask().try_into().unwrap(),
+    );
+    asm.or(temp_reg, temp_reg, mask_reg);
+    asm.write_rt_flags_to_tpblock(temp_reg);
+
+    asm.release_reg(mask_reg);
+    asm.release_reg(temp_reg);
+
+    asm.label(&vs_clean, None, None, None);
+}
+
+fn restore_vector_state(asm: &AsmBuilder, temp_reg: GeneralRegister) {
+    let base = asm.get_trap_frame_base_reg();
+
+    asm.comment("Now restore vector registers if required");
+    let vs_clean = asm.next_label();
+
+    asm.load_rt_flags_from_trapframe(temp_reg);
+    asm.andi(temp_reg, temp_reg, RtFlagBit::VsStateWasDirty.as_mask());
+    asm.beqz(temp_reg, &forward_label(&vs_clean));
+
+    asm.option_enable_vector();
+    for (idx, vr) in asm.rt_config.trap_frame.vector_registers.iter().enumerate() {
+        asm.addi_unconstrained(
+            temp_reg,
+            base,
+            asm.rt_config.vector_register_offset(idx),
+            Some(temp_reg),
+        );
+        asm.vload(*vr, temp_reg);
+    }
+
+    let vtype_reg = asm.get_free_reg();
+    asm.comment("vl and vtype can only be restored using vsetvl");
+    asm.load(temp_reg, base, asm.rt_config.vector_csr_offset(Csr::Vl));
+    asm.load(vtype_reg, base, asm.rt_config.vector_csr_offset(Csr::Vtype));
+    asm.vsetvl(GeneralRegister::Zero, temp_reg, vtype_reg);
+    asm.release_reg(vtype_reg);
+
+    for csr in VECTOR_CSRS {
+        if csr.restore_from_trap_frame() {
+            asm.load(temp_reg, base, asm.rt_config.vector_csr_offset(csr));
+            asm.csrw(csr, temp_reg);
+        }
+    }
+    asm.option_pop();
+
+    // The state is now clean
+    asm.load_rt_flags_from_trapframe(temp_reg);
+    asm.andi(temp_reg, temp_reg, !RtFlagBit::VsStateWasDirty.as_mask());
+    asm.store_rt_flags_to_trapframe(temp_reg);
+
+    asm.label(&vs_clean, None, None, None);
+}
+
 fn handle_trap(asm: &AsmBuilder) {
     let sp = GeneralRegister::Sp;
     let tp = GeneralRegister::Tp;
@@ -2440,31 +5536,77 @@ fn handle_trap(asm: &AsmBuilder) {
     let not_nested_label = asm.next_label();
     let jump_ahead_label = asm.next_label();
 
+    // Switch to the trap code section first, so that the alignment applies to it. The trap vector
+    // base has to be 4-byte aligned even if compressed code is allowed.
+    asm.section(&trap_code_section(), Some(asm.text_section_flags()));
     asm.label(
         &asm.get_label_from_map(LabelType::HandleTrap),
         Some(RV_INSTRUCTION_ALIGNMENT_BYTES),
-        Some(&text_default_section()),
-        Some(asm.text_section_flags()),
+        None,
+        None,
     );
+
+    if asm.rt_config.trap_entry_patch_slots != 0 {
+        asm.comment("Patch slots, each one can be overwritten with a single instruction");
+        asm.option_push();
+        asm.add_sentence(AsmSentence::LinkerOption(LinkerOption::NoRvc));
+        for _ in 0..asm.rt_config.trap_entry_patch_slots {
+            asm.nop();
+        }
+        asm.option_pop();
+    }
+
+    trap_entry_trace(asm);
+
+    match asm.rt_config.default_trap_action {
+        DefaultTrapAction::CallRust => {}
+        DefaultTrapAction::Ebreak => {
+            let ebreak_label = asm.next_label();
+            asm.comment("Trap handling is configured to stop in the debugger");
+            asm.label(&ebreak_label, None, None, None);
+            asm.ebreak();
+            asm.j(&backward_label(&ebreak_label));
+            return;
+        }
+        DefaultTrapAction::Park => {
+            asm.comment("Trap handling is configured to park the hart");
+            asm.j(&asm.get_label_from_map(LabelType::ParkHart));
+            return;
+        }
+    }
+
     asm.comment("Check if this is a nested trap. If yes, then scratch would be 0");
     asm.csrrw(tp, scratch, tp);
     asm.bnez(tp, &forward_label(&not_nested_label));
     asm.comment("For nested trap, read back tp from scratch");
     asm.csrr(tp, scratch);
-    asm.comment("Store current stack pointer as current mode stack to use");
-    asm.store(sp, tp, asm.rt_config.current_mode_stack_offset());
+    let sp_stash_offset = if asm.rt_config.target_config.needs_interrupt_stack() {
+        nested_trap_interrupt_stack(asm);
+        asm.rt_config.interrupted_mode_stack_offset()
+    } else {
+        asm.comment("Store current stack pointer as current mode stack to use");
+        asm.store(sp, tp, asm.rt_config.current_mode_stack_offset());
+        asm.rt_config.current_mode_stack_offset()
+    };
     asm.comment("Set rt state(flags) to indicate we are in nested mode. No free reg to use. So, let's use sp and restore it back from tpblock.");
     // Set up RT flags in `sp` which is the only free register to use
     asm.set_rt_flag_bit(sp, RtFlagBit::RestoreTrapFrameInTpBlock);
     // Write RT flags to tpblock so that they can be correctly updated in trapframe later
     asm.write_rt_flags_to_tpblock(sp);
     // Restore sp back from the stashed storage in tpblock.
-    asm.load(sp, tp, asm.rt_config.current_mode_stack_offset());
+    asm.load(sp, tp, sp_stash_offset);
     asm.j(&forward_label(&jump_ahead_label));
 
     asm.label(&not_nested_label, None, None, None);
     asm.comment("Not in recursive trap. Clear out rt flags in tp block");
     asm.clear_rt_flags_in_tpblock();
+    if asm.rt_config.target_config.needs_interrupt_stack() {
+        asm.comment("Run the trap handler on the interrupt stack of this hart. No free reg to use. So, let's use sp and restore it back from tpblock.");
+        asm.store(sp, tp, asm.rt_config.interrupted_mode_stack_offset());
+        asm.load(sp, tp, asm.rt_config.interrupt_mode_stack_offset());
+        asm.store(sp, tp, asm.rt_config.current_mode_stack_offset());
+        asm.load(sp, tp, asm.rt_config.interrupted_mode_stack_offset());
+    }
 
     asm.label(&jump_ahead_label, None, None, None);
     asm.comment(
@@ -2481,7 +5623,7 @@ fn handle_trap(asm: &AsmBuilder) {
     asm.release_reg(reg);
 
     asm.comment("We only have SP register available to use as temp reg to stash Rust entrypoint");
-    write_entrypoint_in_tp(asm, asm.rt_config.trap_rust_entrypoint());
+    dispatch_interrupt_entrypoints(asm);
 
     // We will be using SP now, so don't treat it as a free reg anymore
     asm.drain_free_reg_pool();
@@ -2492,13 +5634,104 @@ fn handle_trap(asm: &AsmBuilder) {
     asm.j(&asm.get_label_from_map(LabelType::JumpToRustEntrypoint));
 }
 
+// A trap taken in the current mode is nested, but it only runs on the stack it interrupted if that
+// is the interrupt stack already. Traps taken while running on the hart stack (e.g. from the Rust
+// entrypoints, which run with scratch 0) switch to the interrupt stack like non-nested ones. The
+// interrupted SP is left stashed in the tp block. SP and TP are the only registers to work with,
+// TP being restored from scratch, which holds the interrupted TP, i.e. the tp block.
+fn nested_trap_interrupt_stack(asm: &AsmBuilder) {
+    let sp = GeneralRegister::Sp;
+    let tp = GeneralRegister::Tp;
+    let interrupted_sp_offset = asm.rt_config.interrupted_mode_stack_offset();
+    let interrupt_stack_offset = asm.rt_config.interrupt_mode_stack_offset();
+    let on_interrupt_stack_label = asm.next_label();
+    let stack_selected_label = asm.next_label();
+
+    asm.comment("Switch to the interrupt stack unless the nested trap was taken on it");
+    asm.store(sp, tp, interrupted_sp_offset);
+    asm.load(sp, tp, interrupt_stack_offset);
+    asm.load(tp, tp, interrupted_sp_offset);
+    asm.comment("Depth of the interrupted sp below the top of the interrupt stack, unsigned");
+    asm.sub(sp, sp, tp);
+    asm.li_unconstrained(
+        tp,
+        asm.rt_config.target_config.per_hart_interrupt_stack_size(),
+    );
+    asm.bltu(sp, tp, &forward_label(&on_interrupt_stack_label));
+    asm.csrr(tp, Csr::Scratch);
+    asm.load(sp, tp, interrupt_stack_offset);
+    asm.j(&forward_label(&stack_selected_label));
+    asm.label(&on_interrupt_stack_label, None, None, None);
+    asm.csrr(tp, Csr::Scratch);
+    asm.load(sp, tp, interrupted_sp_offset);
+    asm.label(&stack_selected_label, None, None, None);
+    asm.store(sp, tp, asm.rt_config.current_mode_stack_offset());
+}
+
+// Runs first in handle_trap, when every register still holds the interrupted state. TP is swapped
+// with scratch to reach the tp block, where SP (and RA) are stashed in the interrupted mode slots,
+// which the bookkeeping that follows writes anyway. On a nested trap, scratch is 0 and the
+// interrupted TP is already the tp block, so the swap is undone. Each case restores the state on
+// its own path.
+fn trap_entry_trace(asm: &AsmBuilder) {
+    if asm.rt_config.trap_entry_trace.is_none() {
+        return;
+    }
+    let tp = GeneralRegister::Tp;
+    let not_nested_label = asm.next_label();
+    let traced_label = asm.next_label();
+
+    asm.comment("Trace the trap before any bookkeeping, reaching the tp block through scratch");
+    asm.csrrw(tp, Csr::Scratch, tp);
+    asm.bnez(tp, &forward_label(&not_nested_label));
+    asm.csrrw(tp, Csr::Scratch, tp);
+    trap_entry_trace_action(asm);
+    asm.j(&forward_label(&traced_label));
+    asm.label(&not_nested_label, None, None, None);
+    trap_entry_trace_action(asm);
+    asm.csrrw(tp, Csr::Scratch, tp);
+    asm.label(&traced_label, None, None, None);
+}
+
+// Emit the trace action with TP pointing to the tp block, preserving all the other registers
+fn trap_entry_trace_action(asm: &AsmBuilder) {
+    let Some(action) = asm.rt_config.trap_entry_trace else {
+        return;
+    };
+    let sp = GeneralRegister::Sp;
+    let ra = GeneralRegister::Ra;
+    let tp = GeneralRegister::Tp;
+    let sp_offset = asm.rt_config.interrupted_mode_stack_offset();
+    let ra_offset = asm.rt_config.interrupted_mode_tp_offset();
+
+    asm.store(sp, tp, sp_offset);
+    match action {
+        TraceAction::MmioWrite(addr) => {
+            asm.comment("Trace the trap by writing the cause to the trace register");
+            asm.store(ra, tp, ra_offset);
+            asm.li_unconstrained(ra, addr);
+            asm.csrr(sp, Csr::Cause);
+            asm.store(sp, ra, 0);
+            asm.load(ra, tp, ra_offset);
+        }
+        TraceAction::CounterBump => {
+            let offset = asm.rt_config.tp_block_trap_count_offset();
+            asm.comment("Trace the trap by bumping the trap counter in tp block");
+            asm.load(sp, tp, offset);
+            asm.addi(sp, sp, 1);
+            asm.store(sp, tp, offset);
+        }
+    }
+    asm.load(sp, tp, sp_offset);
+}
+
 fn write_scratch(asm: &AsmBuilder) {
     let tp = GeneralRegister::Tp;
     asm.comment("Initialize scratch pointer with thread pointer block storage to make the return path same as trap return");
     asm.la(tp, &asm.get_label_from_map(LabelType::ThreadPointerBlock));
 
     let reg = asm.get_free_reg();
-    asm.li_constrained(reg, asm.rt_config.tp_block_size() as usize);
+    asm.li_unconstrained(reg, asm.rt_config.tp_block_size() as usize);
     asm.mul(reg, reg, asm.get_boot_id_reg());
     asm.add(tp, tp, reg);
     asm.release_reg(reg);
@@ -2516,30 +5749,215 @@ fn write_sptp(asm: &AsmBuilder) {
     asm.store(sp, tp, asm.rt_config.current_mode_stack_offset());
 }
 
-fn write_init_rtflags(asm: &AsmBuilder) {
-    // Clear out RT flags in tpblock for the init path
-    asm.clear_rt_flags_in_tpblock();
-}
+fn write_interrupt_sp(asm: &AsmBuilder) {
+    if !asm.rt_config.target_config.needs_interrupt_stack() {
+        return;
+    }
 
-fn write_entrypoint_in_tp(asm: &AsmBuilder, entrypoint: &str) {
-    let reg = asm.get_free_reg();
-    let tp = GeneralRegister::Tp;
+    let top_reg = asm.get_free_reg();
+    let offset_reg = asm.get_free_reg();
+    asm.comment("Store the top of this hart's interrupt stack in thread pointer block");
+    asm.la(top_reg, &interrupt_stack_top_symbol());
+    asm.li_unconstrained(
+        offset_reg,
+        asm.rt_config
+            .target_config
+            .per_hart_interrupt_stack_stride(),
+    );
+    asm.mul(offset_reg, offset_reg, asm.get_boot_id_reg());
+    asm.sub(top_reg, top_reg, offset_reg);
+    asm.store(
+        top_reg,
+        GeneralRegister::Tp,
+        asm.rt_config.interrupt_mode_stack_offset(),
+    );
 
+    if asm.rt_config.needs_stack_overflow_detection() {
+        asm.comment("Place a sentry value at the bottom of this hart's interrupt stack as well");
+        asm.addi_unconstrained(
+            top_reg,
+            top_reg,
+            -(asm.rt_config.target_config.per_hart_interrupt_stack_size() as isize),
+            Some(offset_reg),
+        );
+        asm.li_sentry_value(offset_reg);
+        asm.store(offset_reg, top_reg, 0);
+    }
+
+    asm.release_reg(offset_reg);
+    asm.release_reg(top_reg);
+}
+
+// The top of the current hart's interrupt stack is kept in the thread pointer block
+fn get_interrupt_stack_bottom(stack_bottom_reg: GeneralRegister, asm: &AsmBuilder) {
+    asm.load(
+        stack_bottom_reg,
+        GeneralRegister::Tp,
+        asm.rt_config.interrupt_mode_stack_offset(),
+    );
+    asm.addi_unconstrained(
+        stack_bottom_reg,
+        stack_bottom_reg,
+        -(asm.rt_config.target_config.per_hart_interrupt_stack_size() as isize),
+        None,
+    );
+}
+
+fn write_init_rtflags(asm: &AsmBuilder) {
+    // Clear out RT flags in tpblock for the init path
+    asm.clear_rt_flags_in_tpblock();
+}
+
+fn write_entrypoint_in_tp(asm: &AsmBuilder, entrypoint: &str) {
+    let reg = asm.get_free_reg();
+    store_entrypoint_in_tp(asm, entrypoint, reg);
+    asm.release_reg(reg);
+}
+
+fn store_entrypoint_in_tp(asm: &AsmBuilder, entrypoint: &str, reg: GeneralRegister) {
     asm.comment("Write out the Rust entrypoint address in thread pointer block");
     asm.la(reg, entrypoint);
-    asm.store(reg, tp, asm.rt_config.rust_entrypoint_offset());
+    asm.store(
+        reg,
+        GeneralRegister::Tp,
+        asm.rt_config.rust_entrypoint_offset(),
+    );
+}
 
+// Interrupts with their own entrypoint are routed to it, all the other traps go to the Trap
+// entrypoint. Only SP is free, plus scratch since the interrupted TP has been stashed.
+fn dispatch_interrupt_entrypoints(asm: &AsmBuilder) {
+    let entrypoints = asm.rt_config.interrupt_rust_entrypoints();
+    let Some(&(max_code, _)) = entrypoints.last() else {
+        write_entrypoint_in_tp(asm, asm.rt_config.trap_rust_entrypoint());
+        return;
+    };
+    let tp = GeneralRegister::Tp;
+    let reg = asm.get_free_reg();
+    let trap_label = asm.next_label();
+    let done_label = asm.next_label();
+
+    asm.csrr(reg, Csr::Cause);
+    asm.comment("Interrupt bit is the MSB of cause, so non-negative cause is an exception");
+    asm.bgez(reg, &forward_label(&trap_label));
+    asm.comment("Drop the interrupt bit, leaving the code shifted left by one");
+    asm.slli(reg, reg, 1);
+
+    match asm.rt_config.cause_dispatch {
+        CauseDispatch::CompareChain => {
+            let mut prev_code = 0;
+            for (code, entrypoint) in entrypoints {
+                let next_label = asm.next_label();
+                asm.comment(&format!("Interrupt {code} is dispatched to {entrypoint}"));
+                asm.addi(reg, reg, -(((code - prev_code) << 1) as isize));
+                asm.bnez(reg, &forward_label(&next_label));
+                store_entrypoint_in_tp(asm, entrypoint, reg);
+                asm.j(&forward_label(&done_label));
+                asm.label(&next_label, None, None, None);
+                prev_code = code;
+            }
+        }
+        CauseDispatch::JumpTable => {
+            let table_len = (max_code + 1) as isize;
+            asm.comment("Interrupts from the end of the entrypoint table on go to the Trap one");
+            asm.addi(reg, reg, -(table_len << 1));
+            asm.bgez(reg, &forward_label(&trap_label));
+            asm.addi(reg, reg, table_len << 1);
+            asm.slli(
+                reg,
+                reg,
+                asm.rt_config.xlen_bytes().trailing_zeros() as usize - 1,
+            );
+            asm.comment("Borrow TP for the table address, stashing it in scratch");
+            asm.csrw(Csr::Scratch, tp);
+            asm.la(
+                tp,
+                &asm.get_label_from_map(LabelType::InterruptEntrypointTable),
+            );
+            asm.add(reg, tp, reg);
+            asm.load(reg, reg, 0);
+            asm.csrr(tp, Csr::Scratch);
+            asm.comment("Empty slots go to the Trap entrypoint");
+            asm.beqz(reg, &forward_label(&trap_label));
+            asm.store(reg, tp, asm.rt_config.rust_entrypoint_offset());
+            asm.j(&forward_label(&done_label));
+        }
+    }
     asm.release_reg(reg);
+
+    asm.label(&trap_label, None, None, None);
+    write_entrypoint_in_tp(asm, asm.rt_config.trap_rust_entrypoint());
+    asm.label(&done_label, None, None, None);
+}
+
+// Entrypoints of the interrupts indexed by their code, 0 for the ones without one
+fn define_interrupt_entrypoint_table(asm: &AsmBuilder) {
+    if asm.rt_config.cause_dispatch != CauseDispatch::JumpTable {
+        return;
+    }
+    let entrypoints = asm.rt_config.interrupt_rust_entrypoints();
+    let Some(&(max_code, _)) = entrypoints.last() else {
+        return;
+    };
+    asm.section(&rodata_default_section(), None);
+    asm.balign(asm.rt_config.xlen_bytes() as usize);
+    asm.label(
+        &asm.get_label_from_map(LabelType::InterruptEntrypointTable),
+        None,
+        None,
+        None,
+    );
+    asm.comment("Rust entrypoints of the interrupts dispatched by handle_trap");
+    for code in 0..=max_code {
+        match entrypoints.iter().find(|(c, _)| *c == code) {
+            Some((_, entrypoint)) => asm.xword_symbol(entrypoint),
+            None => asm.xword(0),
+        }
+    }
+    asm.end_section();
 }
 
 fn write_tvec(asm: &AsmBuilder) {
     let reg = asm.get_free_reg();
     asm.comment("Initialize trap vector base address");
-    asm.la(reg, &asm.get_label_from_map(LabelType::HandleTrap));
+    match asm.rt_config.trap_vector_mode {
+        TrapVectorMode::Direct => {
+            asm.la(reg, &asm.get_label_from_map(LabelType::HandleTrap));
+        }
+        TrapVectorMode::Vectored => {
+            asm.la(reg, &asm.get_label_from_map(LabelType::TrapVectorTable));
+            asm.comment("Select vectored mode");
+            asm.ori(reg, reg, TrapVectorMode::Vectored.mode_bits());
+        }
+    }
     asm.csrw(Csr::Tvec, reg);
     asm.release_reg(reg);
 }
 
+// In vectored mode, exceptions land on the first entry and interrupts on the entry of their cause.
+// Every entry currently jumps to the common trap handler.
+fn trap_vector_table(asm: &AsmBuilder) {
+    if asm.rt_config.trap_vector_mode != TrapVectorMode::Vectored {
+        return;
+    }
+
+    asm.comment("Vectored trap table");
+    asm.section(&trap_code_section(), Some(asm.text_section_flags()));
+    asm.align(RV_INSTRUCTION_ALIGNMENT_BYTES);
+    asm.balign(asm.rt_config.target_config.trap_vector_alignment());
+    // Global, so that the linker script can assert the alignment of the base
+    asm.add_sentence(AsmSentence::GlobalEntrypoint(
+        asm.get_label_from_map(LabelType::TrapVectorTable),
+    ));
+    // Each entry has to be exactly one 4-byte instruction
+    asm.option_push();
+    asm.add_sentence(AsmSentence::LinkerOption(LinkerOption::NoRvc));
+    for _ in 0..TRAP_VECTOR_TABLE_ENTRIES {
+        asm.j(&asm.get_label_from_map(LabelType::HandleTrap));
+    }
+    asm.option_pop();
+}
+
 fn init_fp(asm: &AsmBuilder) {
     let status_reg = asm.get_free_reg();
     let mask_reg = asm.get_free_reg();
@@ -2563,25 +5981,192 @@ fn init_fp(asm: &AsmBuilder) {
     asm.release_reg(mask_reg);
 }
 
+fn capture_secondary_arg(asm: &AsmBuilder) {
+    let Some(arg_reg) = asm.rt_config.secondary_arg else {
+        return;
+    };
+
+    let boot_hart_label = asm.next_label();
+    asm.comment(&format!(
+        "Stash the argument handed off to non-boot harts in {arg_reg:#}"
+    ));
+    asm.beqz(asm.get_boot_id_reg(), &forward_label(&boot_hart_label));
+    asm.store(
+        arg_reg,
+        GeneralRegister::Tp,
+        asm.rt_config.tp_block_secondary_arg_offset(),
+    );
+    asm.label(&boot_hart_label, None, None, None);
+}
+
+fn capture_boot_arg(asm: &AsmBuilder) {
+    if !asm.rt_config.entrypoint_args {
+        return;
+    }
+
+    asm.comment("Stash a1 as handed off at reset for the Rust entrypoint");
+    asm.store(
+        GeneralRegister::A1,
+        GeneralRegister::Tp,
+        asm.rt_config.tp_block_boot_arg_offset(),
+    );
+}
+
+fn validate_boot_magic(asm: &AsmBuilder) {
+    let Some((magic_reg, magic_value)) = asm.rt_config.boot_magic else {
+        return;
+    };
+
+    asm.comment(&format!(
+        "Validate boot protocol magic value handed off in {magic_reg:#}"
+    ));
+    let expected = asm.get_free_reg();
+    let valid_label = asm.next_label();
+    asm.li_unconstrained(expected, magic_value);
+    asm.beq(magic_reg, expected, &forward_label(&valid_label));
+
+    asm.comment("Bad handoff");
+    call_abort_entrypoint(asm, expected, magic_reg);
+
+    asm.label(&valid_label, None, None, None);
+    asm.release_reg(expected);
+}
+
+fn call_abort_entrypoint(asm: &AsmBuilder, expected: GeneralRegister, actual: GeneralRegister) {
+    asm.comment("There is nothing to return to. So, return to park hart");
+    asm.la(
+        GeneralRegister::Ra,
+        &asm.get_label_from_map(LabelType::ParkHart),
+    );
+    asm.comment("Actual value in a1");
+    asm.mov(GeneralRegister::A1, actual);
+    asm.comment("Expected value in a0");
+    asm.mov(GeneralRegister::A0, expected);
+    switch_to_abort_stack(asm);
+    let rs = asm.get_free_reg();
+    asm.la(rs, asm.rt_config.abort_entrypoint());
+    asm.jr(rs);
+    asm.release_reg(rs);
+}
+
+fn define_boot_checksum(asm: &AsmBuilder) {
+    if asm.rt_config.boot_checksum.is_none() {
+        return;
+    }
+
+    asm.section(&data_default_section(), None);
+    asm.comment("Expected checksum, to be patched in the image after linking");
+    asm.add_sentence(AsmSentence::GlobalEntrypoint(
+        asm.get_label_from_map(LabelType::BootChecksum),
+    ));
+    asm.xword(0);
+    asm.end_section();
+}
+
+fn verify_boot_checksum(asm: &AsmBuilder) {
+    let Some((start_symbol, end_symbol)) = &asm.rt_config.boot_checksum else {
+        return;
+    };
+
+    let addr = asm.get_free_reg();
+    let end = asm.get_free_reg();
+    let sum = asm.get_free_reg();
+    let val = asm.get_free_reg();
+    let loop_label = asm.next_label();
+    let done_label = asm.next_label();
+    let valid_label = asm.next_label();
+
+    asm.comment(&format!(
+        "Verify the checksum of {start_symbol:#}..{end_symbol:#}"
+    ));
+    asm.la(addr, start_symbol);
+    asm.la(end, end_symbol);
+    asm.mov(sum, GeneralRegister::Zero);
+    asm.label(&loop_label, None, None, None);
+    asm.bgeu(addr, end, &forward_label(&done_label));
+    asm.load(val, addr, 0);
+    asm.add(sum, sum, val);
+    asm.addi(addr, addr, asm.rt_config.xlen_bytes());
+    asm.j(&backward_label(&loop_label));
+    asm.label(&done_label, None, None, None);
+    asm.release_reg(addr);
+    asm.release_reg(end);
+
+    asm.la(val, &asm.get_label_from_map(LabelType::BootChecksum));
+    asm.load(val, val, 0);
+    asm.beq(sum, val, &forward_label(&valid_label));
+    asm.comment("Checksum mismatch");
+    call_abort_entrypoint(asm, val, sum);
+    asm.label(&valid_label, None, None, None);
+
+    asm.release_reg(sum);
+    asm.release_reg(val);
+}
+
 fn common_hart_init(asm: &AsmBuilder) {
+    clear_mprv(asm);
+
     if asm.rt_config.target_config.needs_custom_reset() {
         call_custom_reset_entrypoint(asm);
     }
 
+    park_harts_outside_bss_init_groups(asm);
     determine_boot_id(asm);
     read_hart_id(asm);
     init_stack_pointer_using_boot_id(asm);
     zero_trap_csrs(asm);
+    write_ie(asm);
+    write_mcountinhibit(asm);
+    write_menvcfg(asm);
+    write_pmp(asm);
+    write_mseccfg(asm);
+    write_satp(asm);
     write_epc(asm);
     write_status(asm);
     write_tvec(asm);
     write_scratch(asm);
     write_sptp(asm);
+    write_interrupt_sp(asm);
     write_init_rtflags(asm);
+    capture_secondary_arg(asm);
+    capture_boot_arg(asm);
+    // Stack and thread pointer are valid at this point, so the abort entrypoint can be called
+    validate_boot_magic(asm);
+    verify_boot_checksum(asm);
 
     if asm.rt_config.floating_point_support {
         init_fp(asm);
     }
+
+    if asm.rt_config.vector_support() {
+        init_vector(asm);
+    }
+}
+
+fn init_vector(asm: &AsmBuilder) {
+    let status_reg = asm.get_free_reg();
+    let mask_reg = asm.get_free_reg();
+    asm.comment("Set VS to Clean");
+    asm.csrr(status_reg, Csr::Status);
+    asm.li_unconstrained(mask_reg, !STATUS_VS_MASK_DIRTY);
+    asm.and(status_reg, status_reg, mask_reg);
+    asm.li_unconstrained(mask_reg, STATUS_VS_CLEAN);
+    asm.or(status_reg, status_reg, mask_reg);
+    asm.csrw(Csr::Status, status_reg);
+
+    asm.option_enable_vector();
+    asm.comment("Clear VCSR");
+    asm.csrw(Csr::Vcsr, GeneralRegister::Zero);
+
+    asm.comment("Zero the vector registers");
+    asm.vsetvli_max(status_reg);
+    for vr in asm.rt_config.trap_frame.vector_registers.iter() {
+        asm.vmv_zero(*vr);
+    }
+    asm.option_pop();
+
+    asm.release_reg(status_reg);
+    asm.release_reg(mask_reg);
 }
 
 fn build_multi_hart_start(asm: &AsmBuilder) {
@@ -2593,6 +6178,7 @@ fn build_multi_hart_start(asm: &AsmBuilder) {
     handle_nonboot_harts(asm);
 
     // Only boot hart performs this initialization
+    call_early_console_entrypoint(asm);
     zero_bss(asm);
     boothart_call_rust_entrypoint(asm);
 
@@ -2603,20 +6189,22 @@ fn build_multi_hart_start(asm: &AsmBuilder) {
 fn build_boot_hart_start(asm: &AsmBuilder) {
     text_reset_section(asm);
     common_hart_init(asm);
+    call_early_console_entrypoint(asm);
     zero_bss(asm);
     boothart_call_rust_entrypoint(asm);
 }
 
 fn build_secondary_hart_start(asm: &AsmBuilder) {
-    asm.align(RV_INSTRUCTION_ALIGNMENT_BYTES);
+    asm.align_code();
     asm.global_function(&asm.get_label_from_map(LabelType::SecondaryStart));
     common_hart_init(asm);
     wait_for_bss_init_done(asm);
+    wait_for_secondary_release(asm);
     jump_to_rust_entrypoint(asm, asm.rt_config.nonboot_hart_rust_entrypoint());
 }
 
 fn asm_tp_block_base(asm: &AsmBuilder) {
-    asm.align(RV_INSTRUCTION_ALIGNMENT_BYTES);
+    asm.align_code();
     asm.comment("Function to be called from non-assembly code");
     asm.global_function(&GEN_FUNC_MAP.asm_fn(GeneratedFunc::TpBlockBase));
     asm.comment("Load address of tp block in a0 as return value");
@@ -2629,7 +6217,7 @@ fn asm_tp_block_base(asm: &AsmBuilder) {
 }
 
 fn asm_get_rest_tf_label(asm: &AsmBuilder) {
-    asm.align(RV_INSTRUCTION_ALIGNMENT_BYTES);
+    asm.align_code();
     asm.comment("Function to be called from non-assembly code");
     asm.global_function(&GEN_FUNC_MAP.asm_fn(GeneratedFunc::RestoreTrapFrame));
     asm.comment("Load address of rest tf in a0 as return value");
@@ -2641,8 +6229,186 @@ fn asm_get_rest_tf_label(asm: &AsmBuilder) {
     asm.jr(GeneralRegister::Ra);
 }
 
+fn asm_resume_frame(asm: &AsmBuilder) {
+    asm.align_code();
+    asm.comment("Function to be called from non-assembly code");
+    asm.global_function(&GEN_FUNC_MAP.asm_fn(GeneratedFunc::ResumeFrame));
+    asm.comment("input: a0 contains address of the trap frame to resume");
+    asm.comment("Keep interrupts off until the return, they would clobber the restored epc/status");
+    asm.csrci(Csr::Status, asm.rt_config.rv_mode().as_ie());
+    asm.store(
+        GeneralRegister::A0,
+        GeneralRegister::Tp,
+        asm.rt_config.tp_block_trap_frame_offset(),
+    );
+    asm.mov(GeneralRegister::Sp, GeneralRegister::A0);
+    asm.j(&asm.get_label_from_map(LabelType::RestoreTrapFrame));
+}
+
+// Busy-wait for at least a0 cycles. Doesn't depend on tp or the stack, so it can be used early.
+fn asm_delay_cycles(asm: &AsmBuilder) {
+    let Some(method) = asm.rt_config.delay_method else {
+        return;
+    };
+
+    let a0 = GeneralRegister::A0;
+    let loop_label = asm.next_label();
+
+    asm.init_default_free_reg_pool();
+    asm.align_code();
+    asm.comment("Function to be called from non-assembly code");
+    asm.global_function(&GEN_FUNC_MAP.asm_fn(GeneratedFunc::DelayCycles));
+    match method {
+        DelayMethod::CountedLoop(cycles_per_loop) => {
+            let done_label = asm.next_label();
+            if cycles_per_loop != 1 {
+                let reg = asm.get_free_reg();
+                asm.comment("Convert the cycle count to loop iterations");
+                asm.li_unconstrained(reg, cycles_per_loop);
+                asm.divu(a0, a0, reg);
+                asm.release_reg(reg);
+            }
+            asm.label(&loop_label, None, None, None);
+            asm.beqz(a0, &forward_label(&done_label));
+            asm.addi(a0, a0, -1);
+            asm.j(&backward_label(&loop_label));
+            asm.label(&done_label, None, None, None);
+        }
+        DelayMethod::CycleCounter => {
+            let start = asm.get_free_reg();
+            let elapsed = asm.get_free_reg();
+            asm.csrr(start, Csr::Cycle);
+            asm.label(&loop_label, None, None, None);
+            asm.csrr(elapsed, Csr::Cycle);
+            asm.sub(elapsed, elapsed, start);
+            asm.bltu(elapsed, a0, &backward_label(&loop_label));
+            asm.release_reg(start);
+            asm.release_reg(elapsed);
+        }
+    }
+    asm.comment("Return back to address in ra");
+    asm.jr(GeneralRegister::Ra);
+}
+
+fn asm_release_secondaries(asm: &AsmBuilder) {
+    if !asm.rt_config.secondary_release_gate {
+        return;
+    }
+
+    asm.init_default_free_reg_pool();
+    asm.align_code();
+    asm.comment("Function to be called from non-assembly code");
+    asm.global_function(&GEN_FUNC_MAP.asm_fn(GeneratedFunc::ReleaseSecondaries));
+    let addr_reg = asm.get_free_reg();
+    let val_reg = asm.get_free_reg();
+    asm.comment("Release the non-boot harts");
+    asm.la(
+        addr_reg,
+        &asm.get_label_from_map(LabelType::SecondaryRelease),
+    );
+    asm.li_constrained(val_reg, 1);
+    asm.store(val_reg, addr_reg, 0);
+    asm.release_reg(addr_reg);
+    asm.release_reg(val_reg);
+    asm.comment("Return back to address in ra");
+    asm.jr(GeneralRegister::Ra);
+}
+
+fn asm_boot_lock(asm: &AsmBuilder) {
+    if !asm.rt_config.boot_lock {
+        return;
+    }
+
+    for func in [
+        GeneratedFunc::BootLockAcquire,
+        GeneratedFunc::BootLockRelease,
+    ] {
+        asm.init_default_free_reg_pool();
+        asm.align_code();
+        asm.comment("Function to be called from non-assembly code");
+        asm.global_function(&GEN_FUNC_MAP.asm_fn(func));
+        let lock_reg = asm.get_free_reg();
+        asm.la(lock_reg, &asm.get_label_from_map(LabelType::BootLock));
+        if func == GeneratedFunc::BootLockAcquire {
+            asm.spinlock_acquire(lock_reg, asm.rt_config.boot_lock_acquire);
+        } else {
+            asm.spinlock_release(lock_reg);
+        }
+        asm.release_reg(lock_reg);
+        asm.comment("Return back to address in ra");
+        asm.jr(GeneralRegister::Ra);
+    }
+}
+
+fn rust_boot_lock(rust: &RustBuilder, rt_config: &RtConfig) {
+    if !rt_config.boot_lock {
+        return;
+    }
+
+    for func in [
+        GeneratedFunc::BootLockAcquire,
+        GeneratedFunc::BootLockRelease,
+    ] {
+        let asm_fn = GEN_FUNC_MAP.asm_fn(func);
+
+        rust.new_c_extern();
+        rust.func_prototype(asm_fn.clone(), Vec::new(), None);
+        rust.end_extern();
+
+        rust.new_func(GEN_FUNC_MAP.rust_fn(func));
+        rust.new_unsafe_block();
+        rust.call_without_ret(asm_fn, Vec::new());
+        rust.end_unsafe_block();
+        rust.end_func();
+    }
+}
+
+fn rust_release_secondaries(rust: &RustBuilder, rt_config: &RtConfig) {
+    if !rt_config.secondary_release_gate {
+        return;
+    }
+
+    let asm_fn = GEN_FUNC_MAP.asm_fn(GeneratedFunc::ReleaseSecondaries);
+
+    rust.new_c_extern();
+    rust.func_prototype(asm_fn.clone(), Vec::new(), None);
+    rust.end_extern();
+
+    // Order the boot hart's setup before the non-boot harts see the gate open
+    rust.new_func(GEN_FUNC_MAP.rust_fn(GeneratedFunc::ReleaseSecondaries));
+    rust.call_without_ret(
+        "core::sync::atomic::fence".to_string(),
+        vec!["core::sync::atomic::Ordering::Release".to_string()],
+    );
+    rust.new_unsafe_block();
+    rust.call_without_ret(asm_fn, Vec::new());
+    rust.end_unsafe_block();
+    rust.end_func();
+}
+
+fn rust_delay_cycles(rust: &RustBuilder, rt_config: &RtConfig) {
+    if rt_config.delay_method.is_none() {
+        return;
+    }
+
+    let asm_fn = GEN_FUNC_MAP.asm_fn(GeneratedFunc::DelayCycles);
+
+    rust.new_c_extern();
+    rust.func_prototype(asm_fn.clone(), vec!["cycles: usize".to_string()], None);
+    rust.end_extern();
+
+    rust.new_func_with_arg(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::DelayCycles),
+        "cycles: usize".to_string(),
+    );
+    rust.new_unsafe_block();
+    rust.call_without_ret(asm_fn, vec!["cycles".to_string()]);
+    rust.end_unsafe_block();
+    rust.end_func();
+}
+
 fn generate_asm_id(asm: &AsmBuilder, asm_fn_name: &str, tp_block_offset: isize) {
-    asm.align(RV_INSTRUCTION_ALIGNMENT_BYTES);
+    asm.align_code();
     asm.comment("Function to be called from non-assembly code");
     asm.global_function(asm_fn_name);
     asm.comment("Take id from tp block and place it in a0 as return value");
@@ -2665,7 +6431,7 @@ fn asm_my_ids(asm: &AsmBuilder) {
 }
 
 fn asm_my_trap_frame_addr(asm: &AsmBuilder) {
-    asm.align(RV_INSTRUCTION_ALIGNMENT_BYTES);
+    asm.align_code();
     asm.comment("Function to be called from non-assembly code");
     asm.global_function(&asm.get_label_from_map(LabelType::GetTrapAddr));
     asm.comment("Take trap frame addr from tp block and place it in a0 as return value");
@@ -2675,7 +6441,7 @@ fn asm_my_trap_frame_addr(asm: &AsmBuilder) {
 }
 
 fn asm_my_tp_block_addr(asm: &AsmBuilder) {
-    asm.align(RV_INSTRUCTION_ALIGNMENT_BYTES);
+    asm.align_code();
     asm.comment("Function to be called from non-assembly code");
     asm.global_function(&GEN_FUNC_MAP.asm_fn(GeneratedFunc::TpBlockAddr));
     asm.comment("Take tp block address from tp and place it in a0 as return value");
@@ -2750,18 +6516,319 @@ fn rust_my_tp_block_addr(rust: &RustBuilder) {
     rust.end_func();
 }
 
-fn rust_tp_block_mut(rust: &RustBuilder, rt_config: &RtConfig) {
+// Same as my_tpblock_addr() but reads tp directly instead of calling into assembly, for hot paths
+fn rust_read_tp(rust: &RustBuilder) {
+    let tp = "tp";
+
+    rust.attribute("inline(always)");
+    rust.new_func_with_ret(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::ReadTp),
+        "usize".to_string(),
+    );
+    rust.let_uninit(tp, "usize");
+    rust.new_unsafe_block();
+    rust.inline_asm("mv {}, tp", vec![format!("out(reg) {tp:#}")]);
+    rust.end_unsafe_block();
+    rust.implicit_ret(tp.to_string());
+    rust.end_func();
+}
+
+// Mask a single interrupt source in the current mode's ie CSR. Returns whether the source was
+// enabled before, so that it can be restored with unmask_interrupt.
+fn rust_mask_interrupt(rust: &RustBuilder, rt_config: &RtConfig) {
+    let prev = "prev";
+
+    rust.attribute("inline(always)");
+    rust.new_func_with_arg_and_ret(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::MaskInterrupt),
+        "bit: usize".to_string(),
+        "usize".to_string(),
+    );
+    rust.let_uninit(prev, "usize");
+    rust.new_unsafe_block();
+    rust.inline_asm(
+        &format!("csrrc {{}}, {:#}, {{}}", rt_config.csr(Csr::Ie)),
+        vec![
+            format!("out(reg) {prev:#}"),
+            "in(reg) 1usize << bit".to_string(),
+        ],
+    );
+    rust.end_unsafe_block();
+    rust.implicit_ret(format!("({prev:#} >> bit) & 1"));
+    rust.end_func();
+}
+
+fn rust_unmask_interrupt(rust: &RustBuilder, rt_config: &RtConfig) {
+    rust.attribute("inline(always)");
+    rust.new_func_with_arg(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::UnmaskInterrupt),
+        "bit: usize".to_string(),
+    );
+    rust.new_unsafe_block();
+    rust.inline_asm(
+        &format!("csrs {:#}, {{}}", rt_config.csr(Csr::Ie)),
+        vec!["in(reg) 1usize << bit".to_string()],
+    );
+    rust.end_unsafe_block();
+    rust.end_func();
+}
+
+fn rust_tp_block_mut(rust: &RustBuilder, rt_config: &RtConfig) {
+    rust.new_func_with_ret(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::TpBlock),
+        format!("&'static mut {:#}", rt_config.tp_block.rust_struct_name()),
+    );
+    rust.new_unsafe_block();
+    rust.implicit_ret(format!(
+        "&mut *({:#}() as *mut {:#})",
+        GEN_FUNC_MAP.asm_fn(GeneratedFunc::TpBlockAddr),
+        rt_config.tp_block.rust_struct_name()
+    ));
+    rust.end_unsafe_block();
+    rust.end_func();
+}
+
+fn rust_current_context(rust: &RustBuilder, rt_config: &RtConfig) {
+    let tp_block_fn = GEN_FUNC_MAP.rust_fn(GeneratedFunc::TpBlock);
+    let member = TpBlockMember::CurrContext;
+    let conversion = if rt_config.is_address_member(&member.to_string()) {
+        ".into()"
+    } else {
+        ""
+    };
+
+    rust.new_func_with_ret(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::CurrentContext),
+        "usize".to_string(),
+    );
+    rust.implicit_ret(format!(
+        "{tp_block_fn:#}().{:#}(){conversion:#}",
+        getter_func_name(&member.to_string())
+    ));
+    rust.end_func();
+
+    rust.new_func_with_arg(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::SetCurrentContext),
+        "ctx: usize".to_string(),
+    );
+    rust.call_without_ret(
+        format!(
+            "{tp_block_fn:#}().{:#}",
+            setter_func_name(&member.to_string())
+        ),
+        vec![format!("ctx{conversion:#}")],
+    );
+    rust.end_func();
+}
+
+// Spins (with a pause hint) until the xword at `addr` reads back as `expected`. Same pattern as
+// the bss init done wait in boot.S, for mailbox style handoffs between harts.
+fn rust_wait_for_value(rust: &RustBuilder) {
+    rust.doc_comment(
+        "# Safety\n\
+         \n\
+         `addr` must be the address of an aligned xword that stays valid while waiting, and is\n\
+         only written with volatile or atomic accesses.",
+    );
+    rust.new_unsafe_func_with_arg(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::WaitForValue),
+        "addr: usize, expected: usize".to_string(),
+    );
+    rust.while_ne(
+        "unsafe { core::ptr::read_volatile(addr as *const usize) }",
+        "expected",
+    );
+    rust.call_without_ret("core::hint::spin_loop".to_string(), Vec::new());
+    rust.end_while();
+    rust.end_func();
+}
+
+fn rust_hart_started(rust: &RustBuilder) {
+    // The flag is written by another hart, so it has to be read with a volatile access
+    rust.new_func_with_arg_and_ret(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::HartStarted),
+        "boot_id: usize".to_string(),
+        "bool".to_string(),
+    );
+    rust.implicit_ret(format!(
+        "unsafe {{ core::ptr::read_volatile(&{:#}()[boot_id].{:#} as *const usize) != 0 }}",
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::TpBlockSlice),
+        TpBlockMember::Started
+    ));
+    rust.end_func();
+}
+
+fn rust_clear_software_interrupt(rust: &RustBuilder, rt_config: &RtConfig) {
+    let Some(ack) = rt_config.software_interrupt_ack else {
+        return;
+    };
+    let prev = "prev";
+
+    rust.new_func_with_ret(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::ClearSoftwareInterrupt),
+        "usize".to_string(),
+    );
+    match ack {
+        SoftwareInterruptAck::PendingCsr => {
+            let ssip = 1;
+            rust.let_uninit(prev, "usize");
+            rust.new_unsafe_block();
+            rust.inline_asm(
+                &format!("csrrc {{}}, {:#}, {{}}", rt_config.csr(Csr::Ip)),
+                vec![
+                    format!("out(reg) {prev:#}"),
+                    format!("in(reg) 1usize << {ssip:#}"),
+                ],
+            );
+            rust.end_unsafe_block();
+            rust.implicit_ret(format!("({prev:#} >> {ssip:#}) & 1"));
+        }
+        SoftwareInterruptAck::Msip(base) => {
+            let msip = "msip";
+            rust.let_assign(
+                msip,
+                &format!(
+                    "({base:#x} + 4 * {:#}()) as *mut u32",
+                    GEN_FUNC_MAP.rust_fn(GeneratedFunc::HartId)
+                ),
+            );
+            rust.let_assign(
+                prev,
+                &format!("unsafe {{ core::ptr::read_volatile({msip:#}) }}"),
+            );
+            rust.new_unsafe_block();
+            rust.call_without_ret(
+                "core::ptr::write_volatile".to_string(),
+                vec![msip.to_string(), "0".to_string()],
+            );
+            rust.end_unsafe_block();
+            rust.implicit_ret(format!("({prev:#} & 1) as usize"));
+        }
+    }
+    rust.end_func();
+}
+
+fn rust_set_timer(rust: &RustBuilder, rt_config: &RtConfig) {
+    let Some(timer) = rt_config.timer_compare else {
+        return;
+    };
+    let deadline = "deadline";
+    let rv32 = rt_config.rv_xlen() == RvXlen::Rv32;
+
+    rust.new_func_with_arg(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::SetTimer),
+        format!("{deadline:#}: u64"),
+    );
+    match timer {
+        TimerCompare::Mtimecmp { base, stride } => {
+            let mtimecmp = "mtimecmp";
+            let addr = format!(
+                "{base:#x} + {stride:#x} * {:#}()",
+                GEN_FUNC_MAP.rust_fn(GeneratedFunc::HartId)
+            );
+            if rv32 {
+                // Writing the low word to all ones first ensures that no interrupt is raised for
+                // a deadline made of the new high word and the old low word
+                rust.let_assign(mtimecmp, &format!("({addr:#}) as *mut u32"));
+                rust.new_unsafe_block();
+                rust.call_without_ret(
+                    "core::ptr::write_volatile".to_string(),
+                    vec![mtimecmp.to_string(), "u32::MAX".to_string()],
+                );
+                rust.call_without_ret(
+                    "core::ptr::write_volatile".to_string(),
+                    vec![
+                        format!("{mtimecmp:#}.add(1)"),
+                        format!("({deadline:#} >> 32) as u32"),
+                    ],
+                );
+                rust.call_without_ret(
+                    "core::ptr::write_volatile".to_string(),
+                    vec![mtimecmp.to_string(), format!("{deadline:#} as u32")],
+                );
+                rust.end_unsafe_block();
+            } else {
+                rust.let_assign(mtimecmp, &format!("({addr:#}) as *mut u64"));
+                rust.new_unsafe_block();
+                rust.call_without_ret(
+                    "core::ptr::write_volatile".to_string(),
+                    vec![mtimecmp.to_string(), deadline.to_string()],
+                );
+                rust.end_unsafe_block();
+            }
+        }
+        TimerCompare::Sbi => {
+            const SBI_EXT_TIME: usize = 0x5449_4d45;
+            const SBI_EXT_TIME_SET_TIMER: usize = 0;
+
+            let mut operands = vec![
+                format!("in(\"a7\") {SBI_EXT_TIME:#x}usize"),
+                format!("in(\"a6\") {SBI_EXT_TIME_SET_TIMER:#}usize"),
+                format!("inlateout(\"a0\") {deadline:#} as usize => _"),
+            ];
+            operands.push(if rv32 {
+                format!("inlateout(\"a1\") ({deadline:#} >> 32) as usize => _")
+            } else {
+                "lateout(\"a1\") _".to_string()
+            });
+            rust.new_unsafe_block();
+            rust.inline_asm("ecall", operands);
+            rust.end_unsafe_block();
+        }
+    }
+    rust.end_func();
+}
+
+fn rust_fence_tso(rust: &RustBuilder, rt_config: &RtConfig) {
+    if !rt_config.fence_tso {
+        return;
+    }
+
+    rust.attribute("inline(always)");
+    rust.new_func(GEN_FUNC_MAP.rust_fn(GeneratedFunc::FenceTso));
+    rust.new_unsafe_block();
+    rust.inline_asm("fence.tso", vec![]);
+    rust.end_unsafe_block();
+    rust.end_func();
+}
+
+fn rust_secondary_arg(rust: &RustBuilder, rt_config: &RtConfig) {
+    if rt_config.secondary_arg.is_none() {
+        return;
+    }
+
+    rust.new_func_with_ret(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::SecondaryArg),
+        "usize".to_string(),
+    );
+    let member = TpBlockMember::SecondaryArg.to_string();
+    rust.implicit_ret(format!(
+        "{:#}().{:#}(){:#}",
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::TpBlock),
+        getter_func_name(&member),
+        if rt_config.is_address_member(&member) {
+            ".into()"
+        } else {
+            ""
+        }
+    ));
+    rust.end_func();
+}
+
+fn rust_trap_count(rust: &RustBuilder, rt_config: &RtConfig) {
+    if rt_config.trap_entry_trace != Some(TraceAction::CounterBump) {
+        return;
+    }
+
     rust.new_func_with_ret(
-        GEN_FUNC_MAP.rust_fn(GeneratedFunc::TpBlock),
-        format!("&'static mut {:#}", rt_config.tp_block.rust_struct_name()),
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::TrapCount),
+        "usize".to_string(),
     );
-    rust.new_unsafe_block();
     rust.implicit_ret(format!(
-        "&mut *({:#}() as *mut {:#})",
-        GEN_FUNC_MAP.asm_fn(GeneratedFunc::TpBlockAddr),
-        rt_config.tp_block.rust_struct_name()
+        "{:#}().{:#}()",
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::TpBlock),
+        getter_func_name(&TpBlockMember::TrapCount.to_string()),
     ));
-    rust.end_unsafe_block();
     rust.end_func();
 }
 
@@ -2815,29 +6882,57 @@ fn write_asm_helpers(asm: &AsmBuilder) {
     asm_my_tp_block_addr(asm);
     asm_tp_block_base(asm);
     asm_get_rest_tf_label(asm);
+    asm_delay_cycles(asm);
+    asm_release_secondaries(asm);
+    asm_boot_lock(asm);
+    asm_resume_frame(asm);
     switch_to(asm);
 }
 
 fn write_boot_s_file(dirpath: &Path, rt_config: &RtConfig, filename: &str) -> std::io::Result<()> {
     let filepath = dirpath.join(filename);
     let fw = FileWriter::new(filepath, BlockDelimiter::None);
+
+    generate_boot_asm(rt_config, &fw);
+    fw.write()
+}
+
+fn generate_boot_asm(rt_config: &RtConfig, fw: &FileWriter) {
     let asm = AsmBuilder::new(rt_config);
 
     asm.preamble();
 
     asm.add_labels(&[
-        (LabelType::ResetStart, START_SYMBOL),
-        (LabelType::ParkHart, "_park_hart"),
+        (
+            LabelType::ResetStart,
+            asm.rt_config.target_config.start_symbol(),
+        ),
+        (
+            LabelType::ParkHart,
+            asm.rt_config.target_config.park_hart_symbol(),
+        ),
         (LabelType::SecondaryStart, "_secondary_start"),
         (LabelType::RestoreTrapFrame, "restore_trap_frame"),
         (LabelType::CreateTrapFrame, "create_trap_frame"),
         (LabelType::HandleTrap, "handle_trap"),
         (LabelType::JumpToRustEntrypoint, "jump_to_rust"),
         (LabelType::BootIdxVariable, "boot_idx"),
+        (LabelType::BootIdChoosing, "boot_id_choosing"),
+        (LabelType::BootIdTicket, "boot_id_ticket"),
         (LabelType::ThreadPointerBlock, "tp_block"),
         (LabelType::BssInitDone, "bss_init_done"),
+        (LabelType::BssInitClaim, "bss_init_claim"),
+        (LabelType::SecondaryRelease, "secondaries_released"),
+        (LabelType::BootLock, "boot_lock"),
+        (LabelType::ResetEntryCopy, "reset_entry_copy"),
         (LabelType::ProtectStack, "protect_stack"),
         (LabelType::GetTrapAddr, "__my_trap_frame_addr"),
+        (LabelType::TrapVectorTable, TRAP_VECTOR_TABLE_SYMBOL),
+        (LabelType::BootChecksum, "_boot_checksum"),
+        (
+            LabelType::InterruptEntrypointTable,
+            "interrupt_entrypoint_table",
+        ),
     ]);
 
     asm.init_default_free_reg_pool();
@@ -2847,8 +6942,13 @@ fn write_boot_s_file(dirpath: &Path, rt_config: &RtConfig, filename: &str) -> st
     if asm.rt_config.is_multi_hart() {
         define_hart_idx_variable(&asm);
         define_bss_init_done(&asm);
+        define_secondary_release_gate(&asm);
+        define_boot_lock(&asm);
     }
+    define_reset_entry_copy(&asm);
     define_thread_pointer_block(&asm);
+    define_boot_checksum(&asm);
+    define_interrupt_entrypoint_table(&asm);
     if asm.rt_config.multihart_reset_handling_required() {
         build_multi_hart_start(&asm);
     } else {
@@ -2868,27 +6968,43 @@ fn write_boot_s_file(dirpath: &Path, rt_config: &RtConfig, filename: &str) -> st
     park_hart(&asm);
 
     restore_trap_frame(&asm);
+    trap_vector_table(&asm);
     handle_trap(&asm);
     goto_rust_entrypoint(&asm);
 
     write_asm_helpers(&asm);
     create_trap_frame(&asm);
-    asm.generate(&fw);
-    fw.write()
+
+    asm.postamble();
+    asm.generate(fw);
 }
 
 fn write_asm_rs_file(
     dirpath: &Path,
+    rt_config: &RtConfig,
     boot_s_filename: &str,
     root_fw: &FileWriter,
 ) -> std::io::Result<()> {
     let asm_rs_filename = "asm.rs";
     let filepath = dirpath.join(asm_rs_filename);
     let fw = FileWriter::new(filepath.clone(), BlockDelimiter::Parens);
-    fw.add_line(&format!("// {}", auto_generate_banner()));
-    fw.add_line(&format!(
-        "core::arch::global_asm!(include_str!({boot_s_filename:?}));"
-    ));
+    add_banner(&fw, "//", &rt_config.banner);
+    match rt_config.asm_output {
+        AsmOutput::SeparateFile => fw.add_line(&format!(
+            "core::arch::global_asm!(include_str!({boot_s_filename:?}));"
+        )),
+        AsmOutput::Inline => {
+            // Same sentences as boot.S, collected in memory instead of being written out
+            let asm_fw = FileWriter::new(dirpath.join(boot_s_filename), BlockDelimiter::None);
+            generate_boot_asm(rt_config, &asm_fw);
+            let asm_text = asm_fw.contents();
+            assert!(
+                !asm_text.contains("\"#"),
+                "Generated assembly can't be embedded in a raw string literal"
+            );
+            fw.add_line(&format!("core::arch::global_asm!(r#\"\n{asm_text:#}\"#);"));
+        }
+    }
     add_module(root_fw, &filepath);
     fw.write()
 }
@@ -2901,29 +7017,56 @@ fn setter_func_name(member_name: &str) -> String {
     format!("set_{member_name:#}")
 }
 
-fn define_getter(rust: &RustBuilder, member_name: &str) {
-    rust.new_method_with_ret(getter_func_name(member_name), "usize".to_string());
-    rust.get_self_member(member_name.to_string());
+const VADDR_TYPE: &str = "super::VAddr";
+
+fn define_getter(rust: &RustBuilder, member_name: &str, is_address: bool) {
+    if is_address {
+        rust.new_method_with_ret(getter_func_name(member_name), VADDR_TYPE.to_string());
+        rust.implicit_ret(format!("{VADDR_TYPE:#}(self.{member_name:#})"));
+    } else {
+        rust.new_method_with_ret(getter_func_name(member_name), "usize".to_string());
+        rust.get_self_member(member_name.to_string());
+    }
     rust.end_method();
 }
 
-fn define_setter(rust: &RustBuilder, member_name: &str) {
-    rust.new_method_self_mut_with_arg(setter_func_name(member_name), "val: usize".to_string());
-    rust.set_self_member(member_name.to_string(), "val".to_string());
+fn define_setter(rust: &RustBuilder, member_name: &str, is_address: bool) {
+    if is_address {
+        rust.new_method_self_mut_with_arg(
+            setter_func_name(member_name),
+            format!("val: {VADDR_TYPE:#}"),
+        );
+        rust.set_self_member(member_name.to_string(), "val.0".to_string());
+    } else {
+        rust.new_method_self_mut_with_arg(setter_func_name(member_name), "val: usize".to_string());
+        rust.set_self_member(member_name.to_string(), "val".to_string());
+    }
     rust.end_method();
 }
 
-fn define_struct(rust: &RustBuilder, name: String, members: Vec<String>, define_reset_func: bool) {
+// `byte_array_members` are (name, size in bytes) pairs for members wider than XLEN. These are placed
+// after all the other members and are accessed directly rather than through getters/setters.
+fn define_struct(
+    rust: &RustBuilder,
+    name: String,
+    members: Vec<String>,
+    byte_array_members: Vec<(String, usize)>,
+    define_reset_func: bool,
+    rt_config: &RtConfig,
+) {
     rust.new_struct(name.to_string());
     for member in &members {
         rust.new_struct_field(member.to_string(), "usize".to_string());
     }
+    for (member, size) in &byte_array_members {
+        rust.new_struct_field(member.to_string(), format!("[u8; {size:#}]"));
+    }
     rust.end_struct();
 
     rust.new_impl(name);
     for member in &members {
-        define_getter(rust, member);
-        define_setter(rust, member);
+        define_getter(rust, member, rt_config.is_address_member(member));
+        define_setter(rust, member, rt_config.is_address_member(member));
     }
 
     if define_reset_func {
@@ -2931,10 +7074,15 @@ fn define_struct(rust: &RustBuilder, name: String, members: Vec<String>, define_
         rust.new_method_self_mut("reset".to_string());
 
         for member in &members {
-            rust.call_without_ret(
-                format!("self.{}", setter_func_name(member)),
-                vec!["0".to_string()],
-            );
+            let zero = if rt_config.is_address_member(member) {
+                format!("{VADDR_TYPE:#}(0)")
+            } else {
+                "0".to_string()
+            };
+            rust.call_without_ret(format!("self.{}", setter_func_name(member)), vec![zero]);
+        }
+        for (member, size) in &byte_array_members {
+            rust.set_self_member(member.to_string(), format!("[0; {size:#}]"));
         }
 
         rust.end_method();
@@ -2956,6 +7104,375 @@ fn define_trapframe_helper(rust: &RustBuilder, rt_config: &RtConfig) {
     ));
     rust.end_unsafe_block();
     rust.end_func();
+
+    // The trap frame of a context suspended by switch_to() is saved in its priv_ctx member, which
+    // is cleared once the context runs again
+    rust.doc_comment(
+        "Trap frame of the context `ctx` suspended by switch_to(), None if it is running.\n\
+         \n\
+         # Safety\n\
+         \n\
+         `ctx` must point to a thread context, and no other reference to the trap frame of the\n\
+         suspended context may be live while the returned one is used.",
+    );
+    rust.new_unsafe_func_with_arg_and_ret(
+        "context_trap_frame".to_string(),
+        "ctx: usize".to_string(),
+        format!(
+            "Option<&'static mut {:#}>",
+            rt_config.trap_frame_rust_struct_name()
+        ),
+    );
+    let priv_ctx_addr = match rt_config.priv_ctx_offset() {
+        0 => "ctx".to_string(),
+        offset => format!("(ctx + {offset:#})"),
+    };
+    rust.new_unsafe_block();
+    rust.implicit_ret(format!(
+        "(*({priv_ctx_addr:#} as *const usize) as *mut {:#}).as_mut()",
+        rt_config.trap_frame_rust_struct_name()
+    ));
+    rust.end_unsafe_block();
+    rust.end_func();
+
+    // Make `tf` the current trap frame and return through it, like a return from the trap handler.
+    // `tf` can be any frame laid out by the runtime, not necessarily the one of the current trap.
+    let asm_fn = GEN_FUNC_MAP.asm_fn(GeneratedFunc::ResumeFrame);
+    rust.new_c_extern();
+    rust.func_prototype(
+        asm_fn.clone(),
+        vec!["tf: usize".to_string()],
+        Some("!".to_string()),
+    );
+    rust.end_extern();
+
+    rust.doc_comment(
+        "# Safety\n\
+         \n\
+         `tf` must hold a complete register state to return to, with an epc, status and\n\
+         stack that are valid to resume. Any interrupted trap frame it links to must still\n\
+         be live.",
+    );
+    rust.new_unsafe_func_with_arg_and_ret(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::ResumeFrame),
+        format!("tf: &mut {:#}", rt_config.trap_frame_rust_struct_name()),
+        "!".to_string(),
+    );
+    rust.new_unsafe_block();
+    rust.call_with_ret(
+        asm_fn,
+        vec![format!(
+            "tf as *mut {:#} as usize",
+            rt_config.trap_frame_rust_struct_name()
+        )],
+    );
+    rust.end_unsafe_block();
+    rust.end_func();
+}
+
+// Provide access to the saved general registers by architectural register number. Registers that
+// are not part of the trap frame (including x0) read as None and ignore writes.
+fn define_trapframe_reg_accessors(rust: &RustBuilder, rt_config: &RtConfig) {
+    let reg_arg = "n";
+
+    rust.new_impl(rt_config.trap_frame_rust_struct_name());
+
+    rust.new_method_with_arg_and_ret(
+        "reg".to_string(),
+        format!("{reg_arg:#}: usize"),
+        "Option<usize>".to_string(),
+    );
+    rust.new_match(reg_arg);
+    for gr in &rt_config.trap_frame.general_regs {
+        rust.match_arm(&gr.number().to_string(), &format!("Some(self.{gr:#})"));
+    }
+    rust.match_arm("_", "None");
+    rust.end_match();
+    rust.end_method();
+
+    rust.new_method_self_mut_with_arg(
+        "set_reg".to_string(),
+        format!("{reg_arg:#}: usize, val: usize"),
+    );
+    rust.new_match(reg_arg);
+    for gr in &rt_config.trap_frame.general_regs {
+        rust.match_arm(&gr.number().to_string(), &format!("self.{gr:#} = val"));
+    }
+    rust.match_arm("_", "{}");
+    rust.end_match();
+    rust.end_method();
+
+    rust.end_impl();
+}
+
+// Provide access to the pc that the trap returns to, without having to know the mode specific name
+// of the epc CSR.
+fn define_trapframe_return_pc_accessors(rust: &RustBuilder, rt_config: &RtConfig) {
+    let epc = rt_config.csr(Csr::Epc);
+
+    rust.new_impl(rt_config.trap_frame_rust_struct_name());
+
+    rust.new_method_with_ret("return_pc".to_string(), "usize".to_string());
+    rust.get_self_member(epc.clone());
+    rust.end_method();
+
+    rust.new_method_self_mut_with_arg("set_return_pc".to_string(), "pc: usize".to_string());
+    rust.set_self_member(epc, "pc".to_string());
+    rust.end_method();
+
+    rust.end_impl();
+}
+
+// Only generated if tval is saved in the trap frame
+fn define_trapframe_fault_address_accessor(rust: &RustBuilder, rt_config: &RtConfig) {
+    if !rt_config.trap_frame.csrs.contains(&Csr::Tval) {
+        return;
+    }
+
+    rust.new_impl(rt_config.trap_frame_rust_struct_name());
+
+    rust.comment(
+        "Faulting address of an access or page fault. For instruction faults, this is the",
+    );
+    rust.comment("faulting instruction address, which is past the return PC if the instruction");
+    rust.comment("straddles a page boundary.");
+    rust.new_method_with_ret("fault_address".to_string(), "usize".to_string());
+    rust.get_self_member(rt_config.csr(Csr::Tval));
+    rust.end_method();
+
+    rust.end_impl();
+}
+
+// Same check as the one performed by restore_trap_frame to decide whether the current mode stack
+// needs to be unwound: the trap came from a lower privilege mode unless the saved PP bits match the
+// current mode.
+fn define_trapframe_lower_mode_accessor(rust: &RustBuilder, rt_config: &RtConfig) {
+    let pp = rt_config.rv_mode().as_pp();
+
+    rust.new_impl(rt_config.trap_frame_rust_struct_name());
+
+    rust.new_method_with_ret("trap_from_lower_mode".to_string(), "bool".to_string());
+    rust.implicit_ret(format!(
+        "self.{:#} & {pp:#x} != {pp:#x}",
+        rt_config.csr(Csr::Status)
+    ));
+    rust.end_method();
+
+    rust.end_impl();
+}
+
+// `rt_flags` -> `RtFlags`
+fn member_to_enum_case(member: &str) -> String {
+    member
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(c) => c.to_ascii_uppercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+// Enumerate the XLEN sized trap frame members, so that a saved context can be walked without
+// hardcoding the member names. The enum value is the index of the member in the trap frame.
+fn define_trapframe_field_accessors(rust: &RustBuilder, rt_config: &RtConfig) {
+    let enum_name = format!("{:#}Field", rt_config.trap_frame_rust_struct_name());
+    let members = rt_config.trap_frame_members();
+    let field_arg = "f";
+
+    rust.new_enum(&enum_name, Some("usize"));
+    for (idx, member) in members.iter().enumerate() {
+        rust.enum_case_value(member_to_enum_case(member), idx);
+    }
+    rust.end_enum();
+
+    rust.new_impl(enum_name.clone());
+    rust.new_func_with_ret(
+        "all".to_string(),
+        format!("[{enum_name:#}; {:#}]", members.len()),
+    );
+    rust.implicit_ret(format!(
+        "[{:#}]",
+        members
+            .iter()
+            .map(|member| format!("{enum_name:#}::{:#}", member_to_enum_case(member)))
+            .collect::<Vec<String>>()
+            .join(", ")
+    ));
+    rust.end_func();
+    rust.end_impl();
+
+    rust.new_impl(rt_config.trap_frame_rust_struct_name());
+
+    rust.new_method_with_arg_and_ret(
+        "get_field".to_string(),
+        format!("{field_arg:#}: {enum_name:#}"),
+        "usize".to_string(),
+    );
+    rust.new_match(field_arg);
+    for member in &members {
+        rust.match_arm(
+            &format!("{enum_name:#}::{:#}", member_to_enum_case(member)),
+            &format!("self.{member:#}"),
+        );
+    }
+    rust.end_match();
+    rust.end_method();
+
+    rust.new_method_self_mut_with_arg(
+        "set_field".to_string(),
+        format!("{field_arg:#}: {enum_name:#}, val: usize"),
+    );
+    rust.new_match(field_arg);
+    for member in &members {
+        rust.match_arm(
+            &format!("{enum_name:#}::{:#}", member_to_enum_case(member)),
+            &format!("self.{member:#} = val"),
+        );
+    }
+    rust.end_match();
+    rust.end_method();
+
+    rust.end_impl();
+}
+
+// CSRs every trap writes. They are always part of SavedCsr, so that handlers can ask for them even
+// when the trap frame doesn't save them.
+const TRAP_CSRS: [Csr; 4] = [Csr::Status, Csr::Epc, Csr::Cause, Csr::Tval];
+
+// Access the CSRs saved in the trap frame by their mode independent name, e.g.
+// tf.csr(SavedCsr::Cause). CSRs that are not saved read as None.
+fn define_trapframe_csr_accessor(rust: &RustBuilder, rt_config: &RtConfig) {
+    let enum_name = "SavedCsr";
+    let csr_arg = "c";
+
+    let mut csrs = rt_config.trap_frame.csrs.clone();
+    for csr in TRAP_CSRS {
+        if !csrs.contains(&csr) {
+            csrs.push(csr);
+        }
+    }
+
+    rust.new_enum(enum_name, None::<&str>);
+    for csr in &csrs {
+        rust.enum_case(member_to_enum_case(&csr.to_string()));
+    }
+    rust.end_enum();
+
+    rust.new_impl(rt_config.trap_frame_rust_struct_name());
+    rust.new_method_with_arg_and_ret(
+        "csr".to_string(),
+        format!("{csr_arg:#}: {enum_name:#}"),
+        "Option<usize>".to_string(),
+    );
+    rust.new_match(csr_arg);
+    for csr in &csrs {
+        let value = if rt_config.trap_frame.csrs.contains(csr) {
+            format!("Some(self.{:#})", rt_config.csr(*csr))
+        } else {
+            "None".to_string()
+        };
+        rust.match_arm(
+            &format!("{enum_name:#}::{:#}", member_to_enum_case(&csr.to_string())),
+            &value,
+        );
+    }
+    rust.end_match();
+    rust.end_method();
+    rust.end_impl();
+}
+
+fn spare_slot_member_name(slot: usize) -> String {
+    format!("spare_slot_{slot:#}")
+}
+
+// Provide access to the spare trap frame slots by index. Out of range indices read as None and
+// ignore writes.
+fn define_trapframe_spare_slot_accessors(rust: &RustBuilder, rt_config: &RtConfig) {
+    if rt_config.trap_frame.spare_csr_slots == 0 {
+        return;
+    }
+
+    let slot_arg = "n";
+
+    rust.new_impl(rt_config.trap_frame_rust_struct_name());
+
+    rust.new_method_with_arg_and_ret(
+        "spare_slot".to_string(),
+        format!("{slot_arg:#}: usize"),
+        "Option<usize>".to_string(),
+    );
+    rust.new_match(slot_arg);
+    for slot in 0..rt_config.trap_frame.spare_csr_slots {
+        rust.match_arm(
+            &slot.to_string(),
+            &format!("Some(self.{:#})", spare_slot_member_name(slot)),
+        );
+    }
+    rust.match_arm("_", "None");
+    rust.end_match();
+    rust.end_method();
+
+    rust.new_method_self_mut_with_arg(
+        "set_spare_slot".to_string(),
+        format!("{slot_arg:#}: usize, val: usize"),
+    );
+    rust.new_match(slot_arg);
+    for slot in 0..rt_config.trap_frame.spare_csr_slots {
+        rust.match_arm(
+            &slot.to_string(),
+            &format!("self.{:#} = val", spare_slot_member_name(slot)),
+        );
+    }
+    rust.match_arm("_", "{}");
+    rust.end_match();
+    rust.end_method();
+
+    rust.end_impl();
+}
+
+// Addresses of the CSRs saved in the trap frame, e.g. csr_addr::MEPC
+fn define_csr_addr_consts(rust: &RustBuilder, rt_config: &RtConfig) {
+    rust.attribute("allow(dead_code)");
+    rust.new_mod("csr_addr".to_string());
+    for csr in &rt_config.trap_frame.csrs {
+        rust.const_def(
+            rt_config.csr(*csr).to_uppercase(),
+            "usize".to_string(),
+            format!("{:#x}", csr.address(rt_config.rv_mode())),
+        );
+    }
+    rust.end_mod();
+}
+
+// Byte offsets of the trap frame members, matching the ones used by the assembly, for code
+// accessing a saved frame through raw pointers
+fn define_trapframe_layout_consts(rust: &RustBuilder, rt_config: &RtConfig) {
+    rust.attribute("allow(dead_code)");
+    rust.new_mod("trapframe_layout".to_string());
+    let members = rt_config.trap_frame_members();
+    for (idx, member) in members.iter().enumerate() {
+        rust.const_def(
+            format!("{:#}_OFFSET", member.to_uppercase()),
+            "usize".to_string(),
+            format!("{:#}", idx as isize * rt_config.xlen_bytes()),
+        );
+    }
+    for (idx, (member, _)) in rt_config.trap_frame_vector_members().iter().enumerate() {
+        rust.const_def(
+            format!("{:#}_OFFSET", member.to_uppercase()),
+            "usize".to_string(),
+            format!("{:#}", rt_config.vector_register_offset(idx)),
+        );
+    }
+    rust.const_def(
+        "TRAP_FRAME_SIZE".to_string(),
+        "usize".to_string(),
+        format!("{:#}", rt_config.trap_frame_size()),
+    );
+    rust.end_mod();
 }
 
 fn write_trapframe_rs_file(
@@ -2967,16 +7484,27 @@ fn write_trapframe_rs_file(
     let filepath = dirpath.join(trapframe_rs_filename);
     let fw = FileWriter::new(filepath.clone(), BlockDelimiter::Parens);
 
-    let rust = RustBuilder::new();
+    let rust = RustBuilder::new(&rt_config.banner);
 
     define_struct(
         &rust,
         rt_config.trap_frame_rust_struct_name(),
         rt_config.trap_frame_members(),
+        rt_config.trap_frame_vector_members(),
         true,
+        rt_config,
     );
 
+    define_trapframe_reg_accessors(&rust, rt_config);
+    define_trapframe_return_pc_accessors(&rust, rt_config);
+    define_trapframe_fault_address_accessor(&rust, rt_config);
+    define_trapframe_lower_mode_accessor(&rust, rt_config);
+    define_trapframe_spare_slot_accessors(&rust, rt_config);
+    define_trapframe_field_accessors(&rust, rt_config);
+    define_trapframe_csr_accessor(&rust, rt_config);
     define_trapframe_helper(&rust, rt_config);
+    define_csr_addr_consts(&rust, rt_config);
+    define_trapframe_layout_consts(&rust, rt_config);
     RtFlagBit::generate(&rust);
 
     rust.generate(&fw);
@@ -3050,15 +7578,45 @@ fn rust_hart_to_boot_id(rust: &RustBuilder) {
     );
 }
 
+fn rust_per_hart_addr(rust: &RustBuilder) {
+    // Generic form of the `base + boot_id * stride` computation used for the per-hart stack and
+    // tp block, for consumers that lay out their own per-hart arrays.
+    rust.new_func_with_arg_and_ret(
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::PerHartAddr),
+        "base: usize, stride: usize".to_string(),
+        "usize".to_string(),
+    );
+    rust.implicit_ret(format!(
+        "base + stride * {:#}()",
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::BootId)
+    ));
+    rust.end_func();
+}
+
 fn write_tpblock_rust_helpers(rust: &RustBuilder, rt_config: &RtConfig) {
     rust_my_ids(rust);
     rust_my_trap_frame_addr(rust);
     rust_my_tp_block_addr(rust);
+    rust_read_tp(rust);
+    rust_mask_interrupt(rust, rt_config);
+    rust_unmask_interrupt(rust, rt_config);
+    rust_clear_software_interrupt(rust, rt_config);
+    rust_set_timer(rust, rt_config);
+    rust_fence_tso(rust, rt_config);
     rust_get_rest_tf_label(rust);
     rust_tp_block_mut(rust, rt_config);
+    rust_current_context(rust, rt_config);
     rust_tp_block_slice(rust, rt_config);
     rust_boot_to_hart_id(rust);
     rust_hart_to_boot_id(rust);
+    rust_hart_started(rust);
+    rust_secondary_arg(rust, rt_config);
+    rust_trap_count(rust, rt_config);
+    rust_per_hart_addr(rust);
+    rust_wait_for_value(rust);
+    rust_delay_cycles(rust, rt_config);
+    rust_release_secondaries(rust, rt_config);
+    rust_boot_lock(rust, rt_config);
     rust_switch_to(rust, "ctx".to_string());
 }
 
@@ -3071,13 +7629,15 @@ fn write_tpblock_rs_file(
     let filepath = dirpath.join(tpblock_rs_filename);
     let fw = FileWriter::new(filepath.clone(), BlockDelimiter::Parens);
 
-    let rust = RustBuilder::new();
+    let rust = RustBuilder::new(&rt_config.banner);
 
     define_struct(
         &rust,
         rt_config.tp_block.rust_struct_name(),
         rt_config.tp_block.members(),
+        Vec::new(),
         false,
+        rt_config,
     );
 
     write_tpblock_rust_helpers(&rust, rt_config);
@@ -3087,6 +7647,64 @@ fn write_tpblock_rs_file(
     fw.write()
 }
 
+// Address newtype used by the accessors of the members passed to RtConfig::with_address_members()
+fn export_vaddr(rt_config: &RtConfig, root_fw: &FileWriter) {
+    if rt_config.address_members.is_empty() {
+        return;
+    }
+
+    root_fw.add_line("#[allow(dead_code)]");
+    root_fw.add_line("#[repr(transparent)]");
+    root_fw.add_line("#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]");
+    root_fw.add_line("pub struct VAddr(pub usize);");
+    root_fw.new_block("impl From<usize> for VAddr");
+    root_fw.new_block("fn from(addr: usize) -> Self");
+    root_fw.add_line("Self(addr)");
+    root_fw.end_block();
+    root_fw.end_block();
+    root_fw.new_block("impl From<VAddr> for usize");
+    root_fw.new_block("fn from(addr: VAddr) -> Self");
+    root_fw.add_line("addr.0");
+    root_fw.end_block();
+    root_fw.end_block();
+}
+
+// Formats the banner on the stack, so that it can be printed with any `fn(&str)` writer as soon as
+// Rust is reached
+fn export_boot_banner(rt_config: &RtConfig, root_fw: &FileWriter) {
+    let Some(version) = &rt_config.boot_banner_version else {
+        return;
+    };
+
+    root_fw.add_line("#[allow(dead_code)]");
+    root_fw.new_block("pub fn print_boot_banner(f: fn(&str))");
+    root_fw.add_line("struct BannerWriter(fn(&str));");
+    root_fw.new_block("impl core::fmt::Write for BannerWriter");
+    root_fw.new_block("fn write_str(&mut self, s: &str) -> core::fmt::Result");
+    root_fw.add_line("(self.0)(s);");
+    root_fw.add_line("Ok(())");
+    root_fw.end_block();
+    root_fw.end_block();
+    root_fw.add_line(&format!(
+        "let _ = core::fmt::Write::write_fmt(&mut BannerWriter(f), format_args!(\"{{}} on hart {{}} (boot id {{}})\\n\", {version:?}, {:#}(), {:#}()));",
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::HartId),
+        GEN_FUNC_MAP.rust_fn(GeneratedFunc::BootId),
+    ));
+    root_fw.end_block();
+}
+
+// Signature of the Rust entrypoints when they receive arguments, e.g. `const _: EntrypointFn = main;`
+fn export_entrypoint_fn(rt_config: &RtConfig, root_fw: &FileWriter) {
+    if !rt_config.entrypoint_args {
+        return;
+    }
+
+    root_fw.add_line("#[allow(dead_code)]");
+    root_fw.add_line(
+        "pub type EntrypointFn = extern \"C\" fn(boot_id: usize, hart_id: usize, dtb: usize);",
+    );
+}
+
 fn export_max_boot_ids(rt_config: &RtConfig, root_fw: &FileWriter) {
     root_fw.add_line("#[allow(dead_code)]");
     root_fw.add_line(&format!(
@@ -3095,6 +7713,19 @@ fn export_max_boot_ids(rt_config: &RtConfig, root_fw: &FileWriter) {
     ));
 }
 
+// XLEN of the runtime, for generic code which can't rely on cfg(target_pointer_width)
+fn export_xlen(rt_config: &RtConfig, root_fw: &FileWriter) {
+    root_fw.add_line("#[allow(dead_code)]");
+    root_fw.add_line(&format!(
+        "pub const RUNTIME_XLEN_BITS: usize = {};",
+        rt_config.xlen_bytes() * 8
+    ));
+    root_fw.add_line("#[allow(dead_code)]");
+    root_fw.new_block("pub const fn xlen_bytes() -> usize");
+    root_fw.add_line("RUNTIME_XLEN_BITS / 8");
+    root_fw.end_block();
+}
+
 pub fn write_rt_files(
     dirpath_name: &str,
     rt_config: &RtConfig,
@@ -3102,12 +7733,152 @@ pub fn write_rt_files(
 ) -> std::io::Result<()> {
     let dirpath = PathBuf::from(dirpath_name);
     let boot_s_filename = "boot.S";
-    let root_fw = create_root_rs_filewriter(&dirpath, crate_type);
+    let root_fw = create_root_rs_filewriter(&dirpath, crate_type, &rt_config.banner);
 
-    write_boot_s_file(&dirpath, rt_config, boot_s_filename)?;
-    write_asm_rs_file(&dirpath, boot_s_filename, &root_fw)?;
+    if rt_config.asm_output == AsmOutput::SeparateFile {
+        write_boot_s_file(&dirpath, rt_config, boot_s_filename)?;
+    }
+    write_asm_rs_file(&dirpath, rt_config, boot_s_filename, &root_fw)?;
     write_tpblock_rs_file(&dirpath, rt_config, &root_fw)?;
     write_trapframe_rs_file(&dirpath, rt_config, &root_fw)?;
     export_max_boot_ids(rt_config, &root_fw);
+    export_xlen(rt_config, &root_fw);
+    export_vaddr(rt_config, &root_fw);
+    export_boot_banner(rt_config, &root_fw);
+    export_entrypoint_fn(rt_config, &root_fw);
     root_fw.write()
 }
+
+// Asm functions callable from C, as (function, parameter list, argument names, return type)
+fn c_callable_asm_funcs(
+    rt_config: &RtConfig,
+) -> Vec<(GeneratedFunc, &'static str, &'static str, &'static str)> {
+    let mut funcs = vec![
+        (GeneratedFunc::BootId, "void", "", "uintptr_t"),
+        (GeneratedFunc::HartId, "void", "", "uintptr_t"),
+        (GeneratedFunc::TpBlockAddr, "void", "", "uintptr_t"),
+        (GeneratedFunc::TrapFrameAddr, "void", "", "uintptr_t"),
+        (GeneratedFunc::TpBlockBase, "void", "", "uintptr_t"),
+        (GeneratedFunc::RestoreTrapFrame, "void", "", "uintptr_t"),
+        (GeneratedFunc::SwitchTo, "uintptr_t ctx", "ctx", "void"),
+    ];
+    if rt_config.delay_method.is_some() {
+        funcs.push((
+            GeneratedFunc::DelayCycles,
+            "uintptr_t cycles",
+            "cycles",
+            "void",
+        ));
+    }
+    if rt_config.secondary_release_gate {
+        funcs.push((GeneratedFunc::ReleaseSecondaries, "void", "", "void"));
+    }
+    if rt_config.boot_lock {
+        funcs.push((GeneratedFunc::BootLockAcquire, "void", "", "void"));
+        funcs.push((GeneratedFunc::BootLockRelease, "void", "", "void"));
+    }
+    funcs
+}
+
+fn define_c_struct(
+    fw: &FileWriter,
+    name: &str,
+    members: &[String],
+    byte_array_members: &[(String, usize)],
+    rt_config: &RtConfig,
+) {
+    let prefix = name_to_c_define_prefix(name);
+
+    fw.new_block(&format!("typedef struct {name:#}"));
+    for member in members {
+        fw.add_line(&format!("uintptr_t {member:#};"));
+    }
+    for (member, size) in byte_array_members {
+        fw.add_line(&format!("uint8_t {member:#}[{size:#}];"));
+    }
+    fw.end_block_with_suffix(&format!("{name:#};"));
+
+    for (idx, member) in members.iter().enumerate() {
+        fw.add_line(&format!(
+            "#define {prefix:#}_{:#}_OFFSET {:#}",
+            member.to_uppercase(),
+            idx as isize * rt_config.xlen_bytes()
+        ));
+    }
+    let mut offset = members.len() * rt_config.xlen_bytes() as usize;
+    for (member, size) in byte_array_members {
+        fw.add_line(&format!(
+            "#define {prefix:#}_{:#}_OFFSET {offset:#}",
+            member.to_uppercase()
+        ));
+        offset += size;
+    }
+    fw.add_line(&format!("#define {prefix:#}_SIZE {offset:#}"));
+    fw.add_line(&format!(
+        "_Static_assert(sizeof({name:#}) == {prefix:#}_SIZE, \"{name:#} layout mismatch\");"
+    ));
+    fw.add_line("");
+}
+
+// TrapFrame -> TRAP_FRAME
+fn name_to_c_define_prefix(name: &str) -> String {
+    let mut prefix = String::new();
+    for (idx, c) in name.chars().enumerate() {
+        if c.is_uppercase() && idx != 0 {
+            prefix.push('_');
+        }
+        prefix.push(c.to_ascii_uppercase());
+    }
+    prefix
+}
+
+// Writes a C header with the trap frame and tp block layouts and prototypes for the generated asm
+// functions, for components written in C. The header is meant to be used alongside the files
+// generated by `write_rt_files` with the same `rt_config`.
+pub fn write_c_header_file(dirpath_name: &str, rt_config: &RtConfig) -> std::io::Result<()> {
+    let header_filename = "rv_runtime.h";
+    let filepath = PathBuf::from(dirpath_name).join(header_filename);
+    let fw = FileWriter::new(filepath, BlockDelimiter::Parens);
+    let include_guard = "RV_RUNTIME_GENERATED_H";
+
+    add_banner(&fw, "//", &rt_config.banner);
+    fw.add_line(&format!("#ifndef {include_guard:#}"));
+    fw.add_line(&format!("#define {include_guard:#}"));
+    fw.add_line("");
+    fw.add_line("#include <stdint.h>");
+    fw.add_line("");
+    fw.add_line(&format!(
+        "#define MAX_BOOT_IDS {}",
+        rt_config.target_config.max_hart_count()
+    ));
+    fw.add_line("");
+
+    define_c_struct(
+        &fw,
+        &rt_config.trap_frame_rust_struct_name(),
+        &rt_config.trap_frame_members(),
+        &rt_config.trap_frame_vector_members(),
+        rt_config,
+    );
+    define_c_struct(
+        &fw,
+        &rt_config.tp_block.rust_struct_name(),
+        &rt_config.tp_block.members(),
+        &[],
+        rt_config,
+    );
+
+    for (func, params, args, ret) in c_callable_asm_funcs(rt_config) {
+        let asm_fn = GEN_FUNC_MAP.asm_fn(func);
+        let ret_keyword = if ret == "void" { "" } else { "return " };
+        fw.add_line(&format!("extern {ret:#} {asm_fn:#}({params:#});"));
+        fw.add_line(&format!(
+            "static inline {ret:#} {:#}({params:#}) {{ {ret_keyword:#}{asm_fn:#}({args:#}); }}",
+            GEN_FUNC_MAP.rust_fn(func)
+        ));
+    }
+    fw.add_line("");
+    fw.add_line(&format!("#endif // {include_guard:#}"));
+
+    fw.write()
+}
@@ -19,8 +19,10 @@ pub enum RustSentence {
     SetSelfMember(String, String), // (self member name, param name)
     ExternStart(String),           // (ffi name)
     ExternEnd,
-    StaticDef(String, String),                         // (name, type)
+    StaticDef(String, String),                               // (name, type)
     FuncStart(String, Option<String>, Option<String>), // (function name, optional arg, optional ret)
+    ConstFuncStart(String, Option<String>, Option<String>), // (function name, optional arg, optional ret)
+    UnsafeFuncStart(String, Option<String>, Option<String>), // (function name, optional arg, optional ret)
     FuncEnd,
     AddrOf(String),                                     // (var)
     Use(String),                                        // (use name)
@@ -36,10 +38,24 @@ pub enum RustSentence {
     ForEnd,
     IfEq(String, String), // (left, right)
     IfEnd,
+    WhileNe(String, String), // (left, right)
+    WhileEnd,
     Comment(String),                                // // comment_string
+    DocComment(String),                             // /// comment_string
     EnumStart(String, Vec<String>, Option<String>), // (enum name, custom derive, repr)
     EnumEnd,
     EnumCaseValue(String, usize), // (case name, value)
+    EnumCase(String),             // (case name)
+    MatchStart(String),           // (expression)
+    MatchArm(String, String),     // (pattern, expression)
+    MatchEnd,
+    Attribute(String),              // (attribute)
+    LetUninit(String, String),      // (variable name, type)
+    Let(String, String),            // (pattern, expression)
+    InlineAsm(String, Vec<String>), // (template, operands)
+    ModStart(String),               // (module name)
+    ModEnd,
+    ConstDef(String, String, String), // (name, type, value)
 }
 
 impl RustSentence {
@@ -58,7 +74,10 @@ impl RustSentence {
             | Self::UnsafeEnd
             | Self::ForEnd
             | Self::IfEnd
-            | Self::EnumEnd => fw.end_block(),
+            | Self::WhileEnd
+            | Self::EnumEnd
+            | Self::MatchEnd
+            | Self::ModEnd => fw.end_block(),
             Self::StructField(name, ty) => fw.add_line(&format!("pub {name:#}: {ty:#},")),
             Self::MethodStart(name, mut_self, arg, ret) => {
                 fw.add_line("#[allow(dead_code, non_snake_case)]");
@@ -78,10 +97,17 @@ impl RustSentence {
                     }
                 ));
             }
-            Self::FuncStart(name, arg, ret) => {
+            Self::FuncStart(name, arg, ret)
+            | Self::ConstFuncStart(name, arg, ret)
+            | Self::UnsafeFuncStart(name, arg, ret) => {
                 fw.add_line("#[allow(dead_code, non_snake_case)]");
                 fw.new_block(&format!(
-                    "pub fn {:#}({:#}){:#}",
+                    "pub {:#}fn {:#}({:#}){:#}",
+                    match self {
+                        Self::ConstFuncStart(..) => "const ",
+                        Self::UnsafeFuncStart(..) => "unsafe ",
+                        _ => "",
+                    },
                     name,
                     if let Some(arg) = arg {
                         format!("{arg:#}")
@@ -134,7 +160,12 @@ impl RustSentence {
             Self::IfEq(left, right) => {
                 fw.new_block(&format!("if {left:#} == {right:#}"));
             }
+            Self::WhileNe(left, right) => {
+                fw.new_block(&format!("while {left:#} != {right:#}"));
+            }
             Self::Comment(comment) => fw.add_line(&format!("// {comment:#}")),
+            Self::DocComment(comment) if comment.is_empty() => fw.add_line("///"),
+            Self::DocComment(comment) => fw.add_line(&format!("/// {comment:#}")),
             Self::EnumStart(name, custom_derive, repr) => {
                 if let Some(s) = repr {
                     fw.add_line(&format!("#[repr({s})]"));
@@ -153,6 +184,24 @@ impl RustSentence {
             Self::EnumCaseValue(name, value) => {
                 fw.add_line(&format!("{name} = {value:#x?},"));
             }
+            Self::EnumCase(name) => fw.add_line(&format!("{name},")),
+            Self::MatchStart(expr) => fw.new_block(&format!("match {expr:#}")),
+            Self::MatchArm(pattern, expr) => fw.add_line(&format!("{pattern:#} => {expr:#},")),
+            Self::Attribute(attr) => fw.add_line(&format!("#[{attr:#}]")),
+            Self::LetUninit(name, ty) => fw.add_line(&format!("let {name:#}: {ty:#};")),
+            Self::Let(pattern, expr) => fw.add_line(&format!("let {pattern:#} = {expr:#};")),
+            Self::InlineAsm(template, operands) => fw.add_line(&format!(
+                "core::arch::asm!({:?}{:#});",
+                template,
+                operands
+                    .iter()
+                    .map(|op| format!(", {op:#}"))
+                    .collect::<String>()
+            )),
+            Self::ModStart(name) => fw.new_block(&format!("pub mod {name:#}")),
+            Self::ConstDef(name, ty, value) => {
+                fw.add_line(&format!("pub const {name:#}: {ty:#} = {value:#};"))
+            }
         }
     }
 }
@@ -163,12 +212,14 @@ pub struct RustBuilder {
 }
 
 impl RustBuilder {
-    pub fn new() -> Self {
+    pub fn new(banner: &str) -> Self {
         let rb = Self {
             sentences: RefCell::new(Vec::new()),
         };
 
-        rb.comment(&auto_generate_banner());
+        for line in banner.lines() {
+            rb.comment(line);
+        }
         rb
     }
 
@@ -198,6 +249,10 @@ impl RustBuilder {
         self.add_sentence(RustSentence::MethodStart(name, false, None, Some(ret)));
     }
 
+    pub fn new_method_with_arg_and_ret(&self, name: String, arg: String, ret: String) {
+        self.add_sentence(RustSentence::MethodStart(name, false, Some(arg), Some(ret)));
+    }
+
     pub fn new_method_self_mut_with_arg(&self, name: String, arg: String) {
         self.add_sentence(RustSentence::MethodStart(name, true, Some(arg), None));
     }
@@ -210,6 +265,10 @@ impl RustBuilder {
         self.add_sentence(RustSentence::MethodEnd);
     }
 
+    pub fn new_func(&self, name: String) {
+        self.add_sentence(RustSentence::FuncStart(name, None, None));
+    }
+
     pub fn new_func_with_ret(&self, name: String, ret: String) {
         self.add_sentence(RustSentence::FuncStart(name, None, Some(ret)));
     }
@@ -222,6 +281,22 @@ impl RustBuilder {
         self.add_sentence(RustSentence::FuncStart(name, Some(arg), None));
     }
 
+    pub fn new_const_func_with_arg_and_ret(&self, name: String, arg: String, ret: String) {
+        self.add_sentence(RustSentence::ConstFuncStart(name, Some(arg), Some(ret)));
+    }
+
+    pub fn new_unsafe_func(&self, name: String) {
+        self.add_sentence(RustSentence::UnsafeFuncStart(name, None, None));
+    }
+
+    pub fn new_unsafe_func_with_arg(&self, name: String, arg: String) {
+        self.add_sentence(RustSentence::UnsafeFuncStart(name, Some(arg), None));
+    }
+
+    pub fn new_unsafe_func_with_arg_and_ret(&self, name: String, arg: String, ret: String) {
+        self.add_sentence(RustSentence::UnsafeFuncStart(name, Some(arg), Some(ret)));
+    }
+
     pub fn end_func(&self) {
         self.add_sentence(RustSentence::FuncEnd);
     }
@@ -310,6 +385,21 @@ impl RustBuilder {
         self.add_sentence(RustSentence::IfEnd);
     }
 
+    pub fn while_ne(&self, left: &str, right: &str) {
+        self.add_sentence(RustSentence::WhileNe(left.to_string(), right.to_string()));
+    }
+
+    pub fn end_while(&self) {
+        self.add_sentence(RustSentence::WhileEnd);
+    }
+
+    // Emit a `///` line per line of `comment`, e.g. for the `# Safety` section of an unsafe fn
+    pub fn doc_comment(&self, comment: &str) {
+        for line in comment.lines() {
+            self.add_sentence(RustSentence::DocComment(line.to_string()));
+        }
+    }
+
     pub fn comment(&self, comment: &str) {
         self.add_sentence(RustSentence::Comment(comment.to_string()));
     }
@@ -329,4 +419,51 @@ impl RustBuilder {
     pub fn enum_case_value<T: ToString>(&self, name: T, value: usize) {
         self.add_sentence(RustSentence::EnumCaseValue(name.to_string(), value));
     }
+
+    pub fn enum_case<T: ToString>(&self, name: T) {
+        self.add_sentence(RustSentence::EnumCase(name.to_string()));
+    }
+
+    pub fn new_match(&self, expr: &str) {
+        self.add_sentence(RustSentence::MatchStart(expr.to_string()));
+    }
+
+    pub fn match_arm(&self, pattern: &str, expr: &str) {
+        self.add_sentence(RustSentence::MatchArm(
+            pattern.to_string(),
+            expr.to_string(),
+        ));
+    }
+
+    pub fn end_match(&self) {
+        self.add_sentence(RustSentence::MatchEnd);
+    }
+
+    pub fn attribute(&self, attr: &str) {
+        self.add_sentence(RustSentence::Attribute(attr.to_string()));
+    }
+
+    pub fn let_uninit(&self, name: &str, ty: &str) {
+        self.add_sentence(RustSentence::LetUninit(name.to_string(), ty.to_string()));
+    }
+
+    pub fn let_assign(&self, pattern: &str, expr: &str) {
+        self.add_sentence(RustSentence::Let(pattern.to_string(), expr.to_string()));
+    }
+
+    pub fn inline_asm(&self, template: &str, operands: Vec<String>) {
+        self.add_sentence(RustSentence::InlineAsm(template.to_string(), operands));
+    }
+
+    pub fn new_mod(&self, name: String) {
+        self.add_sentence(RustSentence::ModStart(name));
+    }
+
+    pub fn end_mod(&self) {
+        self.add_sentence(RustSentence::ModEnd);
+    }
+
+    pub fn const_def(&self, name: String, ty: String, value: String) {
+        self.add_sentence(RustSentence::ConstDef(name, ty, value));
+    }
 }
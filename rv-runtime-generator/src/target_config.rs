@@ -2,6 +2,9 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::func::{PARK_HART_SYMBOL, START_SYMBOL};
+use crate::linker::reset_section;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum RvMode {
     MMode,
@@ -32,12 +35,24 @@ impl RvMode {
         // Values are the same
         self.as_pp()
     }
+
+    // Global interrupt enable bit of the mode in status
+    pub fn as_ie(&self) -> usize {
+        match self {
+            // MIE
+            Self::MMode => 1 << 3,
+            // SIE
+            Self::SMode => 1 << 1,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum RvXlen {
     Rv32,
     Rv64,
+    // Experimental, 128-bit words are emitted as two .dword
+    Rv128,
 }
 
 impl RvXlen {
@@ -45,6 +60,7 @@ impl RvXlen {
         match self {
             Self::Rv32 => 4,
             Self::Rv64 => 8,
+            Self::Rv128 => 16,
         }
     }
 
@@ -52,6 +68,7 @@ impl RvXlen {
         match self {
             Self::Rv32 => "w",
             Self::Rv64 => "d",
+            Self::Rv128 => "q",
         }
     }
 }
@@ -62,8 +79,22 @@ pub struct HartConfig {
     pub rv_xlen: RvXlen,
     pub max_hart_count: usize,
     pub all_harts_start_at_reset_vector: bool,
+    // Alignment of the vectored trap table base required by the hardware, in bytes
+    pub trap_vector_alignment: usize,
+    // Input section holding the reset entry, and the symbols of the reset entry and park loop
+    pub reset_section: String,
+    pub start_symbol: String,
+    pub park_hart_symbol: String,
+    // Memory the reset entry is loaded from when it doesn't run in place
+    pub reset_entry_load_region: Option<String>,
+    // Hart ids are below this limit (max_hart_count unless set), which sizes the per-hart slots of
+    // the boot id lock used without the A extension
+    pub hart_id_limit: Option<usize>,
 }
 
+// The MODE field takes the 2 low bits of the trap vector CSR, so the base is at least 4-byte aligned
+const MIN_TRAP_VECTOR_ALIGNMENT: usize = 4;
+
 impl HartConfig {
     pub fn new(
         rv_mode: RvMode,
@@ -76,18 +107,109 @@ impl HartConfig {
             rv_xlen,
             max_hart_count,
             all_harts_start_at_reset_vector,
+            trap_vector_alignment: MIN_TRAP_VECTOR_ALIGNMENT,
+            reset_section: reset_section(),
+            start_symbol: START_SYMBOL.to_string(),
+            park_hart_symbol: PARK_HART_SYMBOL.to_string(),
+            reset_entry_load_region: None,
+            hart_id_limit: None,
         }
     }
 
+    // Use the builder pattern to place the reset entry in a different input section than
+    // .text.entry, e.g. to avoid collisions with an existing bootloader linked in the same image
+    pub fn with_reset_section(mut self, section: &str) -> Self {
+        assert!(!section.is_empty(), "Reset section name is empty");
+        self.reset_section = section.to_string();
+        self
+    }
+
+    // Use the builder pattern to rename the reset entry symbol (_start), which is also the ENTRY()
+    // of the linker script
+    pub fn with_start_symbol(mut self, symbol: &str) -> Self {
+        assert!(!symbol.is_empty(), "Start symbol name is empty");
+        assert!(
+            symbol != self.park_hart_symbol,
+            "Start symbol {symbol:?} is also the park hart symbol"
+        );
+        self.start_symbol = symbol.to_string();
+        self
+    }
+
+    // Use the builder pattern to rename the symbol of the loop parking harts (_park_hart)
+    pub fn with_park_hart_symbol(mut self, symbol: &str) -> Self {
+        assert!(!symbol.is_empty(), "Park hart symbol name is empty");
+        assert!(
+            symbol != self.start_symbol,
+            "Park hart symbol {symbol:?} is also the start symbol"
+        );
+        self.park_hart_symbol = symbol.to_string();
+        self
+    }
+
+    // Use the builder pattern to load the reset entry from the memory `load_region` (e.g. a ROM
+    // holding the reset vector) while running it from the start of the text section's memory. The
+    // start symbol then becomes a PC-relative stub at the start of `load_region`, which copies the
+    // reset entry to its run address and jumps to it. When all harts start at the reset vector,
+    // the first one does the copy while the others wait for it, which requires the A extension.
+    pub fn with_reset_entry_load_region(mut self, load_region: &str) -> Self {
+        assert!(
+            !load_region.is_empty(),
+            "Reset entry load region name is empty"
+        );
+        self.reset_entry_load_region = Some(load_region.to_string());
+        self
+    }
+
+    // Use the builder pattern to allow hart ids up to `limit` (exclusive) on targets numbering
+    // their harts sparsely. Without the A extension, boot ids are assigned under a lock with a slot
+    // per hart id, and harts with an id beyond the limit are parked.
+    pub fn with_hart_id_limit(mut self, limit: usize) -> Self {
+        assert!(
+            limit >= self.max_hart_count,
+            "Hart id limit {limit} is below the max hart count {}",
+            self.max_hart_count
+        );
+        self.hart_id_limit = Some(limit);
+        self
+    }
+
+    // Use the builder pattern to require a larger alignment of the vectored trap table base, for
+    // hardware which ignores more of the low bits of the trap vector CSR
+    pub fn with_trap_vector_alignment(mut self, alignment_in_bytes: usize) -> Self {
+        assert!(
+            alignment_in_bytes >= MIN_TRAP_VECTOR_ALIGNMENT
+                && (alignment_in_bytes & (alignment_in_bytes - 1)) == 0,
+            "Trap vector alignment {alignment_in_bytes:#x} is not a power-of-2 of at least {MIN_TRAP_VECTOR_ALIGNMENT}"
+        );
+        self.trap_vector_alignment = alignment_in_bytes;
+        self
+    }
+
     pub fn multihart_reset_handling_required(&self) -> bool {
         self.all_harts_start_at_reset_vector && self.max_hart_count > 1
     }
 }
 
+// How overflows of the per-hart stacks are caught
+#[derive(Default, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum StackGuard {
+    // A sentry value at the bottom of each hart's stack is checked on trap return, when stack
+    // overflow detection is enabled
+    #[default]
+    Sentry,
+    // An unused region of the given size is reserved below each hart's stack, to be left unmapped
+    // (or protected) so that overflowing accesses fault
+    Page(usize),
+}
+
 #[derive(Clone, Debug)]
 pub struct MemConfig {
     pub per_hart_stack_size: usize,
     pub heap_size: usize,
+    pub per_hart_abort_stack_size: usize,
+    pub per_hart_interrupt_stack_size: usize,
+    pub stack_guard: StackGuard,
 }
 
 impl MemConfig {
@@ -95,7 +217,51 @@ impl MemConfig {
         Self {
             per_hart_stack_size,
             heap_size,
+            per_hart_abort_stack_size: 0,
+            per_hart_interrupt_stack_size: 0,
+            stack_guard: StackGuard::default(),
+        }
+    }
+
+    // Use the builder pattern to reserve a per-hart stack which the abort and stack overflow
+    // handlers run on, so that they don't depend on the (possibly overflowed) hart stack.
+    pub fn with_abort_stack_size(mut self, per_hart_abort_stack_size: usize) -> Self {
+        assert!(
+            per_hart_abort_stack_size % 16 == 0,
+            "Abort stack size {per_hart_abort_stack_size:#x} is not 16-byte aligned"
+        );
+        self.per_hart_abort_stack_size = per_hart_abort_stack_size;
+        self
+    }
+
+    // Use the builder pattern to reserve a per-hart stack which traps taken outside of the trap
+    // handler run on, so that nested traps don't consume the hart stack. Trap frames of contexts
+    // suspended with switch_to() from such a trap live on it, so they have to be resumed before the
+    // next one. Overflows are caught like for the hart stacks, see `with_stack_guard()`.
+    pub fn with_interrupt_stack_size(mut self, per_hart_interrupt_stack_size: usize) -> Self {
+        assert!(
+            per_hart_interrupt_stack_size % 16 == 0,
+            "Interrupt stack size {per_hart_interrupt_stack_size:#x} is not 16-byte aligned"
+        );
+        self.per_hart_interrupt_stack_size = per_hart_interrupt_stack_size;
+        self
+    }
+
+    // Use the builder pattern to select how stack overflows are caught
+    pub fn with_stack_guard(mut self, stack_guard: StackGuard) -> Self {
+        if let StackGuard::Page(size) = stack_guard {
+            assert!(
+                size != 0 && (size & (size - 1)) == 0,
+                "Stack guard size {size:#x} is not a power-of-2"
+            );
+            assert!(
+                self.per_hart_stack_size % size == 0,
+                "Per-hart stack size {:#x} is not a multiple of the stack guard size {size:#x}",
+                self.per_hart_stack_size
+            );
         }
+        self.stack_guard = stack_guard;
+        self
     }
 }
 
@@ -119,6 +285,45 @@ impl TargetConfig {
         self.mem_config.heap_size
     }
 
+    pub fn per_hart_abort_stack_size(&self) -> usize {
+        self.mem_config.per_hart_abort_stack_size
+    }
+
+    pub fn per_hart_interrupt_stack_size(&self) -> usize {
+        self.mem_config.per_hart_interrupt_stack_size
+    }
+
+    pub fn stack_guard(&self) -> StackGuard {
+        self.mem_config.stack_guard
+    }
+
+    // Size of the guard region below each hart's stack, 0 if page guards are not used
+    pub fn stack_guard_size(&self) -> usize {
+        match self.mem_config.stack_guard {
+            StackGuard::Sentry => 0,
+            StackGuard::Page(size) => size,
+        }
+    }
+
+    // Distance between the tops of the stacks of consecutive harts
+    pub fn per_hart_stack_stride(&self) -> usize {
+        self.per_hart_stack_size() + self.stack_guard_size()
+    }
+
+    // Distance between the tops of the interrupt stacks of consecutive harts, which get the same
+    // guard regions as the hart stacks
+    pub fn per_hart_interrupt_stack_stride(&self) -> usize {
+        self.per_hart_interrupt_stack_size() + self.stack_guard_size()
+    }
+
+    pub fn needs_abort_stack(&self) -> bool {
+        self.mem_config.per_hart_abort_stack_size != 0
+    }
+
+    pub fn needs_interrupt_stack(&self) -> bool {
+        self.mem_config.per_hart_interrupt_stack_size != 0
+    }
+
     pub fn rv_mode(&self) -> RvMode {
         self.hart_config.rv_mode
     }
@@ -143,7 +348,33 @@ impl TargetConfig {
         self.hart_config.max_hart_count > 1
     }
 
+    pub fn trap_vector_alignment(&self) -> usize {
+        self.hart_config.trap_vector_alignment
+    }
+
     pub fn needs_custom_reset(&self) -> bool {
         self.custom_reset_config
     }
+
+    pub fn reset_section(&self) -> &str {
+        &self.hart_config.reset_section
+    }
+
+    pub fn start_symbol(&self) -> &str {
+        &self.hart_config.start_symbol
+    }
+
+    pub fn park_hart_symbol(&self) -> &str {
+        &self.hart_config.park_hart_symbol
+    }
+
+    pub fn reset_entry_load_region(&self) -> Option<&str> {
+        self.hart_config.reset_entry_load_region.as_deref()
+    }
+
+    pub fn hart_id_limit(&self) -> usize {
+        self.hart_config
+            .hart_id_limit
+            .unwrap_or(self.hart_config.max_hart_count)
+    }
 }
@@ -0,0 +1,551 @@
+// SPDX-FileCopyrightText: 2025 Rivos Inc.
+//
+// SPDX-License-Identifier: Apache-2.0
+
+use rv_runtime_generator::*;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const KIB: usize = 1024;
+
+fn target_config(hart_config: HartConfig) -> TargetConfig {
+    TargetConfig {
+        hart_config,
+        mem_config: MemConfig::new(8 * KIB, 4 * KIB),
+        custom_reset_config: false,
+    }
+}
+
+fn linker_config<'a>(target_config: TargetConfig) -> LinkerConfig<'a> {
+    LinkerConfig::new(
+        vec![
+            MemoryRegion::new(
+                "rom",
+                0x1000_0000,
+                64 * KIB,
+                false,
+                MemoryAttribs::rx(),
+                Vec::new(),
+            ),
+            MemoryRegion::new(
+                "ram",
+                0x8000_0000,
+                target_config.max_hart_count() * 16 * KIB + 512 * KIB,
+                false,
+                MemoryAttribs::rwx(),
+                Vec::new(),
+            ),
+        ],
+        vec![
+            Section::new(SectionType::Text, 4096, "ram"),
+            Section::new(SectionType::Rodata, 4096, "ram"),
+            Section::new(SectionType::Data, 4096, "ram"),
+            Section::new(SectionType::Bss, 4096, "ram"),
+            Section::new(SectionType::Heap, 4096, "ram"),
+        ],
+        StackLocation::InBss(StackAlignment::Natural),
+        target_config,
+    )
+}
+
+fn rt_config(
+    target_config: TargetConfig,
+    supports_atomic_extension: bool,
+    extra_entrypoints: Vec<(EntrypointType, &str)>,
+) -> RtConfig {
+    let mut entrypoints = HashMap::from([
+        (EntrypointType::BootHart, "main".to_string()),
+        (EntrypointType::Trap, "trap_enter".to_string()),
+    ]);
+    for (ty, name) in extra_entrypoints {
+        entrypoints.insert(ty, name.to_string());
+    }
+    if target_config.is_multi_hart() {
+        entrypoints.insert(EntrypointType::NonBootHart, "secondary_main".to_string());
+    }
+    RtConfig::new(
+        entrypoints,
+        TrapFrame::get_default(),
+        TpBlock::get_default(),
+        ThreadContext::get_default(),
+        target_config,
+        false,
+        false,
+        supports_atomic_extension,
+        false,
+        FloatWidth::Double,
+        false,
+    )
+}
+
+// Generate the rt and linker files in a directory of its own and return (boot.S, program.ld)
+fn generate<'a>(
+    name: &str,
+    linker_config: &'a LinkerConfig<'a>,
+    rt_config: &RtConfig,
+) -> (String, String) {
+    let dirpath = std::env::temp_dir().join(format!(
+        "rv-runtime-generator-{}-{name}",
+        std::process::id()
+    ));
+    let rt_dirpath = dirpath.join("rt");
+    let linker_dirpath = dirpath.join("linker");
+    std::fs::create_dir_all(&rt_dirpath).unwrap();
+    std::fs::create_dir_all(&linker_dirpath).unwrap();
+
+    write_linker_files(
+        linker_dirpath.to_str().unwrap(),
+        linker_config,
+        CrateType::Module,
+    )
+    .unwrap();
+    write_rt_files(rt_dirpath.to_str().unwrap(), rt_config, CrateType::Module).unwrap();
+
+    let read = |path: PathBuf| std::fs::read_to_string(path).unwrap();
+    let files = (
+        read(rt_dirpath.join("boot.S")),
+        read(linker_dirpath.join("program.ld")),
+    );
+    std::fs::remove_dir_all(&dirpath).unwrap();
+    files
+}
+
+// Generate the linker files only and return the Rust helpers (consts.rs)
+fn generate_linker_consts<'a>(name: &str, linker_config: &'a LinkerConfig<'a>) -> String {
+    let dirpath = std::env::temp_dir().join(format!(
+        "rv-runtime-generator-{}-{name}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dirpath).unwrap();
+    write_linker_files(dirpath.to_str().unwrap(), linker_config, CrateType::Module).unwrap();
+    let consts = std::fs::read_to_string(dirpath.join("consts.rs")).unwrap();
+    std::fs::remove_dir_all(&dirpath).unwrap();
+    consts
+}
+
+#[test]
+fn reset_entry_copied_by_first_hart() {
+    let target_config = target_config(
+        HartConfig::new(RvMode::MMode, RvXlen::Rv64, 4, true).with_reset_entry_load_region("rom"),
+    );
+    let (boot_s, program_ld) = generate(
+        "reset_entry_copy",
+        &linker_config(target_config.clone()),
+        &rt_config(target_config, true, Vec::new()),
+    );
+
+    // The stub is placed in the load region and runs from there
+    assert!(program_ld.contains("} >rom\n"));
+    assert!(program_ld.contains("} >ram AT>rom\n"));
+    assert!(program_ld.contains("_sitext_entry = LOADADDR(.text.entry);"));
+
+    // Only the hart winning the claim copies, the others wait for the done flag
+    assert!(boot_s.contains("reset_entry_copy:\n"));
+    let stub = &boot_s[boot_s.find("_start:\n").unwrap()..];
+    let claim = stub.find("amoswap.d.aq").unwrap();
+    let copy = stub.find("_sitext_entry").unwrap();
+    let done = stub.find("fence.i").unwrap();
+    assert!(claim < copy && copy < done);
+}
+
+#[test]
+#[should_panic(expected = "requires the atomic extension")]
+fn reset_entry_copy_requires_atomics() {
+    let target_config = target_config(
+        HartConfig::new(RvMode::MMode, RvXlen::Rv64, 4, true).with_reset_entry_load_region("rom"),
+    );
+    rt_config(target_config, false, Vec::new());
+}
+
+#[test]
+fn interrupt_entrypoint_dispatch() {
+    let target_config = target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 1, false));
+    let linker_config = linker_config(target_config.clone());
+    let interrupt_entrypoints = || {
+        vec![
+            (EntrypointType::SoftwareInterrupt, "swi_enter"),
+            (EntrypointType::ExternalInterrupt, "external_enter"),
+        ]
+    };
+
+    let (compare_chain, _) = generate(
+        "compare_chain",
+        &linker_config,
+        &rt_config(target_config.clone(), true, interrupt_entrypoints()),
+    );
+    assert!(!compare_chain.contains("interrupt_entrypoint_table"));
+    assert!(compare_chain.contains("la sp, swi_enter"));
+    assert!(compare_chain.contains("la sp, external_enter"));
+
+    let (jump_table, _) = generate(
+        "jump_table",
+        &linker_config,
+        &rt_config(target_config, true, interrupt_entrypoints())
+            .with_cause_dispatch(CauseDispatch::JumpTable),
+    );
+    let table = &jump_table[jump_table.find("interrupt_entrypoint_table:\n").unwrap()..];
+    let entries: Vec<&str> = table
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(".dword "))
+        .take(12)
+        .collect();
+    assert_eq!(entries[3], "swi_enter");
+    assert_eq!(entries[11], "external_enter");
+    assert!(entries
+        .iter()
+        .enumerate()
+        .all(|(code, entry)| code == 3 || code == 11 || *entry == "0"));
+    assert!(jump_table.contains("la tp, interrupt_entrypoint_table"));
+}
+
+#[test]
+fn nested_trap_switches_to_interrupt_stack() {
+    let mut target_config = target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 1, false));
+    target_config.mem_config = target_config.mem_config.with_interrupt_stack_size(4 * KIB);
+    let (boot_s, _) = generate(
+        "nested_interrupt_stack",
+        &linker_config(target_config.clone()),
+        &rt_config(target_config, true, Vec::new()),
+    );
+
+    // Nested traps taken off the interrupt stack move to its top, the others stay where they are
+    let handle_trap = &boot_s[boot_s.find("handle_trap:\n").unwrap()..];
+    let nested = &handle_trap[..handle_trap.find("Not in recursive trap").unwrap()];
+    let check = nested.find("li tp, 4096").unwrap();
+    let switch = nested[check..].find("ld sp, 88(tp)").unwrap();
+    let stay = nested[check..].find("ld sp, 8(tp)").unwrap();
+    assert!(nested[..check].contains("bnez tp,"));
+    assert!(nested[check..].contains("bltu sp, tp,"));
+    assert!(switch < stay);
+}
+
+#[test]
+fn boot_lock_acquire_sequences() {
+    let target_config = target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 4, true));
+    let linker_config = linker_config(target_config.clone());
+
+    let (amoswap, _) = generate(
+        "boot_lock_amoswap",
+        &linker_config,
+        &rt_config(target_config.clone(), true, Vec::new()).with_boot_lock(),
+    );
+    assert!(amoswap.contains("amoswap.d.aq"));
+    assert!(!amoswap.contains("lr.d"));
+
+    let (lr_sc, _) = generate(
+        "boot_lock_lr_sc",
+        &linker_config,
+        &rt_config(target_config, true, Vec::new())
+            .with_boot_lock()
+            .with_boot_lock_acquire(SpinlockAcquire::LrSc),
+    );
+    let acquire = &lr_sc[lr_sc.find("__boot_lock_acquire:\n").unwrap()
+        ..lr_sc.find("__boot_lock_release:\n").unwrap()];
+    let lr = acquire.find("lr.d.aq").unwrap();
+    let sc = acquire.find("sc.d").unwrap();
+    assert!(lr < sc);
+    assert!(!acquire.contains("amoswap"));
+}
+
+#[test]
+fn trap_frame_base_reg() {
+    let target_config = target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 1, false));
+    let (boot_s, _) = generate(
+        "trap_frame_base_reg",
+        &linker_config(target_config.clone()),
+        &rt_config(target_config, true, Vec::new()).with_trap_frame_base_reg(),
+    );
+
+    // Both the save and the restore path copy sp and access mepc in the frame through the copy
+    let path = |label: &str| -> Vec<String> {
+        let path = &boot_s[boot_s.find(label).unwrap()..];
+        path[..path.find("ret\n").unwrap()]
+            .lines()
+            .map(|line| line.trim().to_string())
+            .collect()
+    };
+    let base = |lines: &[String]| -> String {
+        lines
+            .iter()
+            .find_map(|line| line.strip_prefix("add ")?.strip_suffix(", sp, zero"))
+            .unwrap()
+            .to_string()
+    };
+
+    let save = path("create_trap_frame:\n");
+    let base_reg = base(&save);
+    assert!(save.windows(2).any(|pair| {
+        let reg = pair[0]
+            .strip_prefix("csrr ")
+            .and_then(|s| s.strip_suffix(", mepc"));
+        reg.is_some_and(|reg| {
+            pair[1].starts_with(&format!("sd {reg}, "))
+                && pair[1].ends_with(&format!("({base_reg})"))
+        })
+    }));
+
+    let restore = path("restore_trap_frame:\n");
+    let base_reg = base(&restore);
+    assert!(restore.windows(2).any(|pair| {
+        let reg = pair[1].strip_prefix("csrw mepc, ");
+        reg.is_some_and(|reg| {
+            pair[0].starts_with(&format!("ld {reg}, "))
+                && pair[0].ends_with(&format!("({base_reg})"))
+        })
+    }));
+}
+
+#[test]
+fn max_hart_count_beyond_12_bits() {
+    let target_config = target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 4096, false));
+    let (boot_s, _) = generate(
+        "max_hart_count_beyond_12_bits",
+        &linker_config(target_config.clone()),
+        &rt_config(target_config, true, Vec::new()),
+    );
+
+    // The hart count no longer fits a 12-bit immediate, but is still loaded for the boot id check
+    let check = &boot_s[boot_s.find("greater than max hart count").unwrap()..];
+    let li = check.lines().nth(1).unwrap().trim();
+    assert!(li.starts_with("li ") && li.ends_with(", 4096"));
+}
+
+#[test]
+#[should_panic(expected = "NAPOT memory empty has a zero length")]
+fn zero_length_napot_region_rejected() {
+    LinkerConfig::new(
+        vec![MemoryRegion::new(
+            "empty",
+            0x8000_0000,
+            0,
+            true,
+            MemoryAttribs::rwx(),
+            Vec::new(),
+        )],
+        Vec::new(),
+        StackLocation::InBss(StackAlignment::Natural),
+        target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 1, false)),
+    );
+}
+
+#[test]
+fn ro_rw_guard_gap_placed_once_per_memory() {
+    let target_config = target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 1, false));
+    let mut linker_config = linker_config(target_config.clone());
+    linker_config.sections = vec![
+        Section::new(SectionType::Text, 4096, "ram"),
+        Section::new(SectionType::Rodata, 4096, "rom"),
+        Section::new(SectionType::Data, 4096, "ram"),
+        Section::new(SectionType::Bss, 4096, "ram"),
+        Section::new(SectionType::Heap, 4096, "ram"),
+    ];
+    let linker_config = linker_config.with_ro_rw_guard_gap(4096, 4096);
+    let (_, program_ld) = generate(
+        "ro_rw_guard_gap",
+        &linker_config,
+        &rt_config(target_config, true, Vec::new()),
+    );
+
+    // The gap of ram goes between .text and .data, even with .rodata placed in rom in between
+    assert_eq!(program_ld.matches(".guard_ram").count(), 1);
+    assert_eq!(program_ld.matches("_sguard_ram = .;").count(), 1);
+    assert!(!program_ld.contains(".guard_rom"));
+    let gap = program_ld.find(".guard_ram").unwrap();
+    assert!(program_ld.find(".rodata").unwrap() < gap);
+    assert!(gap < program_ld.find(".data").unwrap());
+}
+
+#[test]
+#[should_panic(
+    expected = "Read-only section .rodata is placed after writable section .data in memory ram"
+)]
+fn ro_rw_guard_gap_requires_read_only_sections_first() {
+    let target_config = target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 1, false));
+    let mut linker_config = linker_config(target_config);
+    linker_config.sections = vec![
+        Section::new(SectionType::Text, 4096, "ram"),
+        Section::new(SectionType::Data, 4096, "ram"),
+        Section::new(SectionType::Rodata, 4096, "ram"),
+        Section::new(SectionType::Bss, 4096, "ram"),
+    ];
+    linker_config.with_ro_rw_guard_gap(4096, 4096);
+}
+
+#[test]
+fn satp_saved_for_sfence_on_restore() {
+    let target_config = target_config(HartConfig::new(RvMode::SMode, RvXlen::Rv64, 1, false));
+    let rt_config = RtConfig::new(
+        HashMap::from([
+            (EntrypointType::BootHart, "main".to_string()),
+            (EntrypointType::Trap, "trap_enter".to_string()),
+        ]),
+        TrapFrame::get_default(),
+        TpBlock::get_default(),
+        ThreadContext::get_default(),
+        target_config.clone(),
+        false,
+        false,
+        true,
+        false,
+        FloatWidth::Double,
+        true,
+    );
+    let (boot_s, _) = generate(
+        "satp_saved_for_sfence_on_restore",
+        &linker_config(target_config),
+        &rt_config,
+    );
+
+    // satp is saved on entry, even though the default trap frame doesn't list it
+    let save = &boot_s[boot_s.find("create_trap_frame:\n").unwrap()..];
+    assert!(save[..save.find("ret\n").unwrap()].contains(", satp\n"));
+
+    // and compared against the current one on restore, to sfence.vma only if it changed
+    let restore = &boot_s[boot_s.find("restore_trap_frame:\n").unwrap()..];
+    let restore = &restore[..restore.find("sret\n").unwrap()];
+    let compare = restore.find(", satp\n").unwrap();
+    assert!(restore[compare..].contains("csrw satp, "));
+    assert!(restore[compare..].contains("sfence.vma"));
+}
+
+#[test]
+fn compressed_code_alignment() {
+    let target_config = target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 1, false));
+    let linker_config = linker_config(target_config.clone());
+    let aligned_before = |boot_s: &str, label: &str| {
+        let lines: Vec<&str> = boot_s.lines().map(str::trim).collect();
+        let idx = lines.iter().position(|line| *line == label).unwrap();
+        lines[..idx]
+            .iter()
+            .rev()
+            .take_while(|line| !line.is_empty() && !line.ends_with(':'))
+            .any(|line| line.starts_with(".align"))
+    };
+
+    let (full, _) = generate(
+        "full_code",
+        &linker_config,
+        &rt_config(target_config.clone(), true, Vec::new()),
+    );
+    assert!(aligned_before(&full, "create_trap_frame:"));
+    assert!(aligned_before(&full, "handle_trap:"));
+
+    // With compression allowed, only the trap vector keeps its 4-byte alignment
+    let (compressed, _) = generate(
+        "compressed_code",
+        &linker_config,
+        &rt_config(target_config, true, Vec::new()).with_compressed_code(CompressedCode::Allow),
+    );
+    assert!(compressed.contains(".option rvc"));
+    assert!(!aligned_before(&compressed, "create_trap_frame:"));
+    assert!(aligned_before(&compressed, "handle_trap:"));
+}
+
+#[test]
+fn arch_string_from_config() {
+    let arch = |boot_s: &str| -> String {
+        let line = boot_s
+            .lines()
+            .find(|line| line.starts_with(".attribute arch, "))
+            .unwrap();
+        line.trim_start_matches(".attribute arch, ")
+            .trim_matches('"')
+            .to_string()
+    };
+
+    // The assembler default doesn't claim the C extension, and fence.i isn't used
+    let single_hart = target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 1, false));
+    let (boot_s, _) = generate(
+        "arch_default",
+        &linker_config(single_hart.clone()),
+        &rt_config(single_hart, false, Vec::new()),
+    );
+    assert_eq!(arch(&boot_s), "rv64im_zicsr");
+
+    // Copying the reset entry synchronizes the instruction fetches with fence.i
+    let reset_entry_copy = target_config(
+        HartConfig::new(RvMode::MMode, RvXlen::Rv64, 4, true).with_reset_entry_load_region("rom"),
+    );
+    let (boot_s, _) = generate(
+        "arch_compressed",
+        &linker_config(reset_entry_copy.clone()),
+        &rt_config(reset_entry_copy, true, Vec::new()).with_compressed_code(CompressedCode::Allow),
+    );
+    assert_eq!(arch(&boot_s), "rv64imac_zicsr_zifencei");
+}
+
+#[test]
+fn rv32_cfg_csrs_write_both_halves() {
+    let target_config = target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv32, 1, false));
+    let (boot_s, _) = generate(
+        "rv32_cfg_csrs",
+        &linker_config(target_config.clone()),
+        &rt_config(target_config, true, Vec::new())
+            .with_menvcfg(1 << 62)
+            .with_mseccfg(1 << 8),
+    );
+
+    // The upper halves are written by number (menvcfgh is 0x31a, mseccfgh is 0x757), also when
+    // zero to clear whatever they held at reset
+    assert!(boot_s.contains("csrw 0x31a, "));
+    assert!(boot_s.contains("csrw menvcfg, "));
+    assert!(boot_s.contains("csrw 0x757, zero"));
+    assert!(boot_s.contains("csrw mseccfg, "));
+}
+
+#[test]
+#[should_panic(expected = "Hart id limit 2 is below the max hart count 4")]
+fn hart_id_limit_below_max_hart_count() {
+    HartConfig::new(RvMode::MMode, RvXlen::Rv64, 4, false).with_hart_id_limit(2);
+}
+
+#[test]
+fn boot_ids_without_atomics() {
+    let target_config =
+        target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 4, false).with_hart_id_limit(8));
+    let (boot_s, _) = generate(
+        "bakery_boot_ids",
+        &linker_config(target_config.clone()),
+        &rt_config(target_config, false, Vec::new()),
+    );
+
+    assert!(!boot_s.contains("amo"));
+    assert!(!boot_s.contains("lr."));
+    assert!(!boot_s.contains("sc."));
+    // A slot per hart id below the limit in each of the lock arrays
+    for array in ["boot_id_choosing:\n", "boot_id_ticket:\n"] {
+        let slots = &boot_s[boot_s.find(array).unwrap()..];
+        assert!(slots.contains(".rept 8\n"));
+    }
+    // The boot id variable is only read and incremented once the lock is held
+    let start = &boot_s[boot_s.find("_start:\n").unwrap()..];
+    let choosing = start.find("boot_id_choosing").unwrap();
+    let boot_idx = start.find("boot_idx").unwrap();
+    let release = start.find("Release the lock").unwrap();
+    assert!(choosing < boot_idx && boot_idx < release);
+}
+
+#[test]
+fn stack_remaining_excludes_sentry_only_if_placed() {
+    let target_config = target_config(HartConfig::new(RvMode::MMode, RvXlen::Rv64, 1, false));
+    let sentry_size = "bottom + core::mem::size_of::<usize>()";
+
+    let consts = generate_linker_consts("no_sentry", &linker_config(target_config.clone()));
+    assert!(consts.contains("read_sp().saturating_sub(bottom)"));
+    assert!(!consts.contains(sentry_size));
+
+    let consts =
+        generate_linker_consts("sentry", &linker_config(target_config).with_stack_sentry());
+    assert!(consts.contains(sentry_size));
+}
+
+#[test]
+#[should_panic(expected = "requires the sentry stack guard")]
+fn stack_sentry_requires_sentry_guard() {
+    let target_config = TargetConfig {
+        hart_config: HartConfig::new(RvMode::MMode, RvXlen::Rv64, 1, false),
+        mem_config: MemConfig::new(8 * KIB, 4 * KIB).with_stack_guard(StackGuard::Page(4 * KIB)),
+        custom_reset_config: false,
+    };
+    linker_config(target_config).with_stack_sentry();
+}
@@ -15,8 +15,11 @@ fn main() {
             RvXlen::Rv64,
             max_hart_count,
             all_harts_start_at_reset_vector,
-        ),
-        mem_config: MemConfig::new(per_hart_stack_size, heap_size),
+        )
+        /* The reset entry is loaded in the ROM and copied to region_1 by the first hart */
+        .with_reset_entry_load_region("rom"),
+        /* Room for a trap frame plus the logging done by trap_enter */
+        mem_config: MemConfig::new(per_hart_stack_size, heap_size).with_interrupt_stack_size(4096),
         custom_reset_config: true,
     };
 
@@ -31,6 +34,8 @@ fn main() {
      * This ensures that the runtime saves/restores floating point registers as well.
      */
     let floating_point_support = true;
+    /* Target implements the D extension, so the floating point registers are 64 bits wide */
+    let float_width = FloatWidth::Double;
     /*
      * We are not messing with satp or other paging structures in this component, so we don't need
      * a sfence to be executed on trapframe restore.
@@ -53,26 +58,83 @@ fn main() {
                 MemoryRegion::new(
                     "region_2",
                     0x8002_0000,
-                    64 * KiB,
+                    128 * KiB,
                     true,
                     MemoryAttribs::rw(),
                     vec![
-                        SubRegion::new("subregion_1", 56 * KiB, false),
+                        SubRegion::new("subregion_1", 120 * KiB, false),
                         SubRegion::new("subregion_2", 8 * KiB, true),
                     ],
                 ),
+                MemoryRegion::new(
+                    "rom",
+                    0x8004_0000,
+                    64 * KiB,
+                    false,
+                    MemoryAttribs::rx(),
+                    Vec::new(),
+                ),
+                /* Initialized data stored in the ROM, copied by copy_data_to_ram() */
+                MemoryRegion::new(
+                    "region_3",
+                    0x8005_0000,
+                    16 * KiB,
+                    false,
+                    MemoryAttribs::rw(),
+                    Vec::new(),
+                )
+                .with_load_region("rom"),
             ],
             vec![
                 Section::new(SectionType::Text, alignment, "region_1"),
                 Section::new(SectionType::Rodata, alignment, "region_1"),
+                Section::new(
+                    SectionType::Note("provenance".to_string(), b"rv-runtime-test".to_vec()),
+                    8,
+                    "region_1",
+                ),
                 Section::new(SectionType::Data, alignment, "subregion_1"),
                 Section::new(SectionType::Bss, alignment, "subregion_1"),
                 Section::new(SectionType::Heap, alignment, "subregion_1"),
+                Section::new(
+                    SectionType::NamedHeap("dma_heap".to_string(), 4096),
+                    alignment,
+                    "subregion_2",
+                )
+                .with_fill(0xdeadbeef),
                 Section::new(
                     SectionType::Custom("custom_section".to_string(), 4096),
                     alignment,
                     "subregion_1",
                 ),
+                Section::new(
+                    SectionType::Custom("build_info".to_string(), 64),
+                    alignment,
+                    "subregion_1",
+                )
+                .with_output_type(OutputSectionType::Info),
+                {
+                    /*
+                     * BSS of the hart group 1, cleared by its first hart. Its contents are kept in
+                     * the image so that the test can tell it was cleared.
+                     */
+                    let mut bss_group1 = Section::new(
+                        SectionType::Custom("bss_group1".to_string(), 4096),
+                        8,
+                        "subregion_1",
+                    );
+                    bss_group1.add_subsection(SubSection::new(".group1_zeroed", 8, None).keep());
+                    bss_group1
+                },
+                {
+                    let mut reloc_data = Section::new(
+                        SectionType::Custom("reloc_data".to_string(), 4096),
+                        8,
+                        "region_3",
+                    );
+                    reloc_data.add_subsection(SubSection::new(".reloc_blob", 8, None).keep());
+                    reloc_data
+                },
             ],
             StackLocation::InBss(StackAlignment::Natural),
             target_config.clone(),
@@ -83,12 +145,26 @@ fn main() {
                 (EntrypointType::NonBootHart, "secondary_main".to_string()),
                 (EntrypointType::Trap, "trap_enter".to_string()),
                 (EntrypointType::CustomReset, "my_custom_reset".to_string()),
+                (EntrypointType::EarlyConsole, "early_console".to_string()),
+                (EntrypointType::TimerInterrupt, "timer_enter".to_string()),
                 (
                     EntrypointType::StackOverflow,
                     "handle_stack_overflow".to_string(),
                 ),
             ]),
-            TrapFrame::get_default(),
+            {
+                /*
+                 * Keep the fields touched on every trap (mepc, ra, sp) together at the front of
+                 * the trap frame, so that they share a cache line.
+                 */
+                let mut trap_frame = TrapFrame::get_default();
+                trap_frame.hot_members = vec![
+                    TrapFrameMember::Csr(Csr::Epc),
+                    TrapFrameMember::GeneralRegister(GeneralRegister::Ra),
+                    TrapFrameMember::GeneralRegister(GeneralRegister::Sp),
+                ];
+                trap_frame
+            },
             TpBlock::get_default(),
             ThreadContext::get_default(),
             target_config,
@@ -96,8 +172,39 @@ fn main() {
             stack_overflow_detection,
             atomic_extension_supported,
             floating_point_support,
+            float_width,
             sfence_on_trapframe_restore_feature,
-        ),
+        )
+        /* Secondaries receive the DTB pointer in a1 */
+        .with_secondary_arg(GeneralRegister::A1)
+        /* Save the vector state of the 128-bit VLEN harts, so that the V asm is assembled */
+        .with_vector_support(128)
+        /* riscv64gc has the C extension, so let the runtime code be compressed */
+        .with_compressed_code(CompressedCode::Allow)
+        .with_fence_tso()
+        .with_clear_mprv()
+        .with_pmp_grant_all()
+        .with_address_members(&["sp", "mepc", "curr_context", "trap_ctx_frame"])
+        /* CLINT of the QEMU virt machine */
+        .with_software_interrupt_ack(SoftwareInterruptAck::Msip(0x200_0000))
+        .with_set_timer(TimerCompare::Mtimecmp {
+            base: 0x200_4000,
+            stride: 8,
+        })
+        /* Route the timer interrupt through the entrypoint table rather than compares */
+        .with_cause_dispatch(CauseDispatch::JumpTable)
+        .with_trap_entry_trace(TraceAction::CounterBump)
+        /* Save and restore the trap frame CSRs through a register other than sp */
+        .with_trap_frame_base_reg()
+        .with_unwind_info()
+        .with_trap_entry_patch_slots(2)
+        .with_secondary_release_gate()
+        /* Harts 0-1 and 2-3 are groups which clear their BSS independently */
+        .with_bss_init_groups(1, 2)
+        .with_entrypoint_args()
+        .with_boot_lock()
+        .with_boot_lock_acquire(SpinlockAcquire::LrSc)
+        .with_boot_banner(concat!("rv-runtime-test ", env!("CARGO_PKG_VERSION"))),
     };
 
     std::fs::create_dir_all(runtime_config.rt_dirpath_name)
@@ -116,6 +223,8 @@ fn main() {
         CrateType::Module,
     )
     .expect("Failed to write rt files");
+    write_c_header_file(runtime_config.rt_dirpath_name, &runtime_config.rt_config)
+        .expect("Failed to write C header file");
 
     println!("cargo:rerun-if-changed={}", runtime_config.rt_dirpath_name);
     println!(
@@ -5,14 +5,24 @@ extern "C" {
     static _etext: usize;
     static _srodata: usize;
     static _erodata: usize;
+    static _sprovenance: usize;
+    static _eprovenance: usize;
     static _sdata: usize;
     static _edata: usize;
     static _sbss: usize;
     static _ebss: usize;
     static _sheap: usize;
     static _eheap: usize;
+    static _sdma_heap: usize;
+    static _edma_heap: usize;
     static _scustom_section: usize;
     static _ecustom_section: usize;
+    static _sbuild_info: usize;
+    static _ebuild_info: usize;
+    static _sbss_group1: usize;
+    static _ebss_group1: usize;
+    static _sreloc_data: usize;
+    static _ereloc_data: usize;
     static _sstack: usize;
     static _estack: usize;
     static _sregion_1: usize;
@@ -23,8 +33,17 @@ extern "C" {
     static _esubregion_1: usize;
     static _ssubregion_2: usize;
     static _esubregion_2: usize;
+    static _srom: usize;
+    static _erom: usize;
+    static _sregion_3: usize;
+    static _eregion_3: usize;
     static _sprogram: usize;
     static _eprogram: usize;
+    static _strap_code: usize;
+    static _etrap_code: usize;
+    static _sinterrupt_stack: usize;
+    static _einterrupt_stack: usize;
+    static _sireloc_data: usize;
 }
 #[allow(dead_code, non_snake_case)]
 pub fn text_region_start() -> usize {
@@ -51,6 +70,18 @@ pub fn rodata_region_size() -> usize {
     rodata_region_end() - rodata_region_start()
 }
 #[allow(dead_code, non_snake_case)]
+pub fn provenance_region_start() -> usize {
+    (addr_of!(_sprovenance)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn provenance_region_end() -> usize {
+    (addr_of!(_eprovenance)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn provenance_region_size() -> usize {
+    provenance_region_end() - provenance_region_start()
+}
+#[allow(dead_code, non_snake_case)]
 pub fn data_region_start() -> usize {
     (addr_of!(_sdata)) as usize
 }
@@ -87,6 +118,18 @@ pub fn heap_region_size() -> usize {
     heap_region_end() - heap_region_start()
 }
 #[allow(dead_code, non_snake_case)]
+pub fn dma_heap_region_start() -> usize {
+    (addr_of!(_sdma_heap)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn dma_heap_region_end() -> usize {
+    (addr_of!(_edma_heap)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn dma_heap_region_size() -> usize {
+    dma_heap_region_end() - dma_heap_region_start()
+}
+#[allow(dead_code, non_snake_case)]
 pub fn custom_section_region_start() -> usize {
     (addr_of!(_scustom_section)) as usize
 }
@@ -99,6 +142,42 @@ pub fn custom_section_region_size() -> usize {
     custom_section_region_end() - custom_section_region_start()
 }
 #[allow(dead_code, non_snake_case)]
+pub fn build_info_region_start() -> usize {
+    (addr_of!(_sbuild_info)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn build_info_region_end() -> usize {
+    (addr_of!(_ebuild_info)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn build_info_region_size() -> usize {
+    build_info_region_end() - build_info_region_start()
+}
+#[allow(dead_code, non_snake_case)]
+pub fn bss_group1_region_start() -> usize {
+    (addr_of!(_sbss_group1)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn bss_group1_region_end() -> usize {
+    (addr_of!(_ebss_group1)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn bss_group1_region_size() -> usize {
+    bss_group1_region_end() - bss_group1_region_start()
+}
+#[allow(dead_code, non_snake_case)]
+pub fn reloc_data_region_start() -> usize {
+    (addr_of!(_sreloc_data)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn reloc_data_region_end() -> usize {
+    (addr_of!(_ereloc_data)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn reloc_data_region_size() -> usize {
+    reloc_data_region_end() - reloc_data_region_start()
+}
+#[allow(dead_code, non_snake_case)]
 pub fn stack_region_start() -> usize {
     (addr_of!(_sstack)) as usize
 }
@@ -159,6 +238,30 @@ pub fn subregion_2_region_size() -> usize {
     subregion_2_region_end() - subregion_2_region_start()
 }
 #[allow(dead_code, non_snake_case)]
+pub fn rom_region_start() -> usize {
+    (addr_of!(_srom)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn rom_region_end() -> usize {
+    (addr_of!(_erom)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn rom_region_size() -> usize {
+    rom_region_end() - rom_region_start()
+}
+#[allow(dead_code, non_snake_case)]
+pub fn region_3_region_start() -> usize {
+    (addr_of!(_sregion_3)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn region_3_region_end() -> usize {
+    (addr_of!(_eregion_3)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn region_3_region_size() -> usize {
+    region_3_region_end() - region_3_region_start()
+}
+#[allow(dead_code, non_snake_case)]
 pub fn program_region_start() -> usize {
     (addr_of!(_sprogram)) as usize
 }
@@ -170,6 +273,48 @@ pub fn program_region_end() -> usize {
 pub fn program_region_size() -> usize {
     program_region_end() - program_region_start()
 }
+#[allow(dead_code, non_snake_case)]
+pub fn trap_code_region_start() -> usize {
+    (addr_of!(_strap_code)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn trap_code_region_end() -> usize {
+    (addr_of!(_etrap_code)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn trap_code_region_size() -> usize {
+    trap_code_region_end() - trap_code_region_start()
+}
+#[allow(dead_code, non_snake_case)]
+pub fn lock_text_rodata() -> [(usize, usize); 2] {
+    [(text_region_start(), text_region_size()), (rodata_region_start(), rodata_region_size())]
+}
+#[allow(dead_code, non_snake_case)]
+pub fn interrupt_stack_region_start() -> usize {
+    (addr_of!(_sinterrupt_stack)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn interrupt_stack_region_end() -> usize {
+    (addr_of!(_einterrupt_stack)) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn interrupt_stack_region_size() -> usize {
+    interrupt_stack_region_end() - interrupt_stack_region_start()
+}
+#[allow(dead_code, non_snake_case)]
+pub fn reloc_data_load_start() -> usize {
+    (addr_of!(_sireloc_data)) as usize
+}
+/// # Safety
+///
+/// Overwrites the relocated sections with their initial contents, so it must be called
+/// once, by a single hart, before anything in them is used.
+#[allow(dead_code, non_snake_case)]
+pub unsafe fn copy_data_to_ram() {
+    unsafe {
+        core::ptr::copy_nonoverlapping(reloc_data_load_start() as *const u8,reloc_data_region_start() as *mut u8,reloc_data_region_size());
+    }
+}
 extern "C" {
     fn __my_boot_id() -> usize;
 }
@@ -179,3 +324,28 @@ pub fn my_stack() -> (usize, usize) {
         (stack_region_end() - 0x2000 * (__my_boot_id() + 1), 0x2000)
     }
 }
+#[allow(dead_code, non_snake_case)]
+pub fn my_interrupt_stack() -> (usize, usize) {
+    unsafe {
+        (interrupt_stack_region_end() - 0x1000 * (__my_boot_id() + 1), 0x1000)
+    }
+}
+#[inline(always)]
+#[allow(dead_code, non_snake_case)]
+pub fn read_sp() -> usize {
+    let sp: usize;
+    unsafe {
+        core::arch::asm!("mv {}, sp", out(reg) sp);
+    }
+    sp
+}
+#[allow(dead_code, non_snake_case)]
+pub fn on_trap_stack() -> bool {
+    let (bottom, size) = my_interrupt_stack();
+    (bottom..=bottom + size).contains(&read_sp())
+}
+#[allow(dead_code, non_snake_case)]
+pub fn stack_remaining() -> usize {
+    let (bottom, _) = if on_trap_stack() { my_interrupt_stack() } else { my_stack() };
+    read_sp().saturating_sub(bottom)
+}
@@ -1,3 +1,5 @@
 // DO NOT EDIT. AUTOGENERATED BY 'rv-runtime-generator'
 mod consts;
 pub use consts::*;
+#[allow(dead_code)]
+pub const STACK_SIZE: usize = 0x2000;
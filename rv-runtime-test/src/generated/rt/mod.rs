@@ -7,3 +7,36 @@ mod trapframe;
 pub use trapframe::*;
 #[allow(dead_code)]
 pub const MAX_BOOT_IDS: usize = 4;
+#[allow(dead_code)]
+pub const RUNTIME_XLEN_BITS: usize = 64;
+#[allow(dead_code)]
+pub const fn xlen_bytes() -> usize {
+    RUNTIME_XLEN_BITS / 8
+}
+#[allow(dead_code)]
+#[repr(transparent)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct VAddr(pub usize);
+impl From<usize> for VAddr {
+    fn from(addr: usize) -> Self {
+        Self(addr)
+    }
+}
+impl From<VAddr> for usize {
+    fn from(addr: VAddr) -> Self {
+        addr.0
+    }
+}
+#[allow(dead_code)]
+pub fn print_boot_banner(f: fn(&str)) {
+    struct BannerWriter(fn(&str));
+    impl core::fmt::Write for BannerWriter {
+        fn write_str(&mut self, s: &str) -> core::fmt::Result {
+            (self.0)(s);
+            Ok(())
+        }
+    }
+    let _ = core::fmt::Write::write_fmt(&mut BannerWriter(f), format_args!("{} on hart {} (boot id {})\n", "rv-runtime-test 0.1.0", my_hart_id(), my_boot_id()));
+}
+#[allow(dead_code)]
+pub type EntrypointFn = extern "C" fn(boot_id: usize, hart_id: usize, dtb: usize);
@@ -0,0 +1,282 @@
+// DO NOT EDIT. AUTOGENERATED BY 'rv-runtime-generator'
+#ifndef RV_RUNTIME_GENERATED_H
+#define RV_RUNTIME_GENERATED_H
+
+#include <stdint.h>
+
+#define MAX_BOOT_IDS 4
+
+typedef struct TrapFrame {
+    uintptr_t mepc;
+    uintptr_t ra;
+    uintptr_t sp;
+    uintptr_t gp;
+    uintptr_t tp;
+    uintptr_t t0;
+    uintptr_t t1;
+    uintptr_t t2;
+    uintptr_t s0;
+    uintptr_t s1;
+    uintptr_t a0;
+    uintptr_t a1;
+    uintptr_t a2;
+    uintptr_t a3;
+    uintptr_t a4;
+    uintptr_t a5;
+    uintptr_t a6;
+    uintptr_t a7;
+    uintptr_t s2;
+    uintptr_t s3;
+    uintptr_t s4;
+    uintptr_t s5;
+    uintptr_t s6;
+    uintptr_t s7;
+    uintptr_t s8;
+    uintptr_t s9;
+    uintptr_t s10;
+    uintptr_t s11;
+    uintptr_t t3;
+    uintptr_t t4;
+    uintptr_t t5;
+    uintptr_t t6;
+    uintptr_t f0;
+    uintptr_t f1;
+    uintptr_t f2;
+    uintptr_t f3;
+    uintptr_t f4;
+    uintptr_t f5;
+    uintptr_t f6;
+    uintptr_t f7;
+    uintptr_t f8;
+    uintptr_t f9;
+    uintptr_t f10;
+    uintptr_t f11;
+    uintptr_t f12;
+    uintptr_t f13;
+    uintptr_t f14;
+    uintptr_t f15;
+    uintptr_t f16;
+    uintptr_t f17;
+    uintptr_t f18;
+    uintptr_t f19;
+    uintptr_t f20;
+    uintptr_t f21;
+    uintptr_t f22;
+    uintptr_t f23;
+    uintptr_t f24;
+    uintptr_t f25;
+    uintptr_t f26;
+    uintptr_t f27;
+    uintptr_t f28;
+    uintptr_t f29;
+    uintptr_t f30;
+    uintptr_t f31;
+    uintptr_t mstatus;
+    uintptr_t mtval;
+    uintptr_t mcause;
+    uintptr_t fcsr;
+    uintptr_t rt_flags;
+    uintptr_t int_frame;
+    uintptr_t vl;
+    uintptr_t vtype;
+    uintptr_t vcsr;
+    uintptr_t vstart;
+    uint8_t v0[16];
+    uint8_t v1[16];
+    uint8_t v2[16];
+    uint8_t v3[16];
+    uint8_t v4[16];
+    uint8_t v5[16];
+    uint8_t v6[16];
+    uint8_t v7[16];
+    uint8_t v8[16];
+    uint8_t v9[16];
+    uint8_t v10[16];
+    uint8_t v11[16];
+    uint8_t v12[16];
+    uint8_t v13[16];
+    uint8_t v14[16];
+    uint8_t v15[16];
+    uint8_t v16[16];
+    uint8_t v17[16];
+    uint8_t v18[16];
+    uint8_t v19[16];
+    uint8_t v20[16];
+    uint8_t v21[16];
+    uint8_t v22[16];
+    uint8_t v23[16];
+    uint8_t v24[16];
+    uint8_t v25[16];
+    uint8_t v26[16];
+    uint8_t v27[16];
+    uint8_t v28[16];
+    uint8_t v29[16];
+    uint8_t v30[16];
+    uint8_t v31[16];
+} TrapFrame;
+#define TRAP_FRAME_MEPC_OFFSET 0
+#define TRAP_FRAME_RA_OFFSET 8
+#define TRAP_FRAME_SP_OFFSET 16
+#define TRAP_FRAME_GP_OFFSET 24
+#define TRAP_FRAME_TP_OFFSET 32
+#define TRAP_FRAME_T0_OFFSET 40
+#define TRAP_FRAME_T1_OFFSET 48
+#define TRAP_FRAME_T2_OFFSET 56
+#define TRAP_FRAME_S0_OFFSET 64
+#define TRAP_FRAME_S1_OFFSET 72
+#define TRAP_FRAME_A0_OFFSET 80
+#define TRAP_FRAME_A1_OFFSET 88
+#define TRAP_FRAME_A2_OFFSET 96
+#define TRAP_FRAME_A3_OFFSET 104
+#define TRAP_FRAME_A4_OFFSET 112
+#define TRAP_FRAME_A5_OFFSET 120
+#define TRAP_FRAME_A6_OFFSET 128
+#define TRAP_FRAME_A7_OFFSET 136
+#define TRAP_FRAME_S2_OFFSET 144
+#define TRAP_FRAME_S3_OFFSET 152
+#define TRAP_FRAME_S4_OFFSET 160
+#define TRAP_FRAME_S5_OFFSET 168
+#define TRAP_FRAME_S6_OFFSET 176
+#define TRAP_FRAME_S7_OFFSET 184
+#define TRAP_FRAME_S8_OFFSET 192
+#define TRAP_FRAME_S9_OFFSET 200
+#define TRAP_FRAME_S10_OFFSET 208
+#define TRAP_FRAME_S11_OFFSET 216
+#define TRAP_FRAME_T3_OFFSET 224
+#define TRAP_FRAME_T4_OFFSET 232
+#define TRAP_FRAME_T5_OFFSET 240
+#define TRAP_FRAME_T6_OFFSET 248
+#define TRAP_FRAME_F0_OFFSET 256
+#define TRAP_FRAME_F1_OFFSET 264
+#define TRAP_FRAME_F2_OFFSET 272
+#define TRAP_FRAME_F3_OFFSET 280
+#define TRAP_FRAME_F4_OFFSET 288
+#define TRAP_FRAME_F5_OFFSET 296
+#define TRAP_FRAME_F6_OFFSET 304
+#define TRAP_FRAME_F7_OFFSET 312
+#define TRAP_FRAME_F8_OFFSET 320
+#define TRAP_FRAME_F9_OFFSET 328
+#define TRAP_FRAME_F10_OFFSET 336
+#define TRAP_FRAME_F11_OFFSET 344
+#define TRAP_FRAME_F12_OFFSET 352
+#define TRAP_FRAME_F13_OFFSET 360
+#define TRAP_FRAME_F14_OFFSET 368
+#define TRAP_FRAME_F15_OFFSET 376
+#define TRAP_FRAME_F16_OFFSET 384
+#define TRAP_FRAME_F17_OFFSET 392
+#define TRAP_FRAME_F18_OFFSET 400
+#define TRAP_FRAME_F19_OFFSET 408
+#define TRAP_FRAME_F20_OFFSET 416
+#define TRAP_FRAME_F21_OFFSET 424
+#define TRAP_FRAME_F22_OFFSET 432
+#define TRAP_FRAME_F23_OFFSET 440
+#define TRAP_FRAME_F24_OFFSET 448
+#define TRAP_FRAME_F25_OFFSET 456
+#define TRAP_FRAME_F26_OFFSET 464
+#define TRAP_FRAME_F27_OFFSET 472
+#define TRAP_FRAME_F28_OFFSET 480
+#define TRAP_FRAME_F29_OFFSET 488
+#define TRAP_FRAME_F30_OFFSET 496
+#define TRAP_FRAME_F31_OFFSET 504
+#define TRAP_FRAME_MSTATUS_OFFSET 512
+#define TRAP_FRAME_MTVAL_OFFSET 520
+#define TRAP_FRAME_MCAUSE_OFFSET 528
+#define TRAP_FRAME_FCSR_OFFSET 536
+#define TRAP_FRAME_RT_FLAGS_OFFSET 544
+#define TRAP_FRAME_INT_FRAME_OFFSET 552
+#define TRAP_FRAME_VL_OFFSET 560
+#define TRAP_FRAME_VTYPE_OFFSET 568
+#define TRAP_FRAME_VCSR_OFFSET 576
+#define TRAP_FRAME_VSTART_OFFSET 584
+#define TRAP_FRAME_V0_OFFSET 592
+#define TRAP_FRAME_V1_OFFSET 608
+#define TRAP_FRAME_V2_OFFSET 624
+#define TRAP_FRAME_V3_OFFSET 640
+#define TRAP_FRAME_V4_OFFSET 656
+#define TRAP_FRAME_V5_OFFSET 672
+#define TRAP_FRAME_V6_OFFSET 688
+#define TRAP_FRAME_V7_OFFSET 704
+#define TRAP_FRAME_V8_OFFSET 720
+#define TRAP_FRAME_V9_OFFSET 736
+#define TRAP_FRAME_V10_OFFSET 752
+#define TRAP_FRAME_V11_OFFSET 768
+#define TRAP_FRAME_V12_OFFSET 784
+#define TRAP_FRAME_V13_OFFSET 800
+#define TRAP_FRAME_V14_OFFSET 816
+#define TRAP_FRAME_V15_OFFSET 832
+#define TRAP_FRAME_V16_OFFSET 848
+#define TRAP_FRAME_V17_OFFSET 864
+#define TRAP_FRAME_V18_OFFSET 880
+#define TRAP_FRAME_V19_OFFSET 896
+#define TRAP_FRAME_V20_OFFSET 912
+#define TRAP_FRAME_V21_OFFSET 928
+#define TRAP_FRAME_V22_OFFSET 944
+#define TRAP_FRAME_V23_OFFSET 960
+#define TRAP_FRAME_V24_OFFSET 976
+#define TRAP_FRAME_V25_OFFSET 992
+#define TRAP_FRAME_V26_OFFSET 1008
+#define TRAP_FRAME_V27_OFFSET 1024
+#define TRAP_FRAME_V28_OFFSET 1040
+#define TRAP_FRAME_V29_OFFSET 1056
+#define TRAP_FRAME_V30_OFFSET 1072
+#define TRAP_FRAME_V31_OFFSET 1088
+#define TRAP_FRAME_SIZE 1104
+_Static_assert(sizeof(TrapFrame) == TRAP_FRAME_SIZE, "TrapFrame layout mismatch");
+
+typedef struct TpBlock {
+    uintptr_t current_mode_sp;
+    uintptr_t interrupted_mode_sp;
+    uintptr_t interrupted_mode_tp;
+    uintptr_t rust_entrypoint;
+    uintptr_t boot_id;
+    uintptr_t hart_id;
+    uintptr_t curr_context;
+    uintptr_t return_addr;
+    uintptr_t rt_flags;
+    uintptr_t trap_ctx_frame;
+    uintptr_t started;
+    uintptr_t interrupt_mode_sp;
+    uintptr_t secondary_arg;
+    uintptr_t trap_count;
+    uintptr_t boot_arg;
+} TpBlock;
+#define TP_BLOCK_CURRENT_MODE_SP_OFFSET 0
+#define TP_BLOCK_INTERRUPTED_MODE_SP_OFFSET 8
+#define TP_BLOCK_INTERRUPTED_MODE_TP_OFFSET 16
+#define TP_BLOCK_RUST_ENTRYPOINT_OFFSET 24
+#define TP_BLOCK_BOOT_ID_OFFSET 32
+#define TP_BLOCK_HART_ID_OFFSET 40
+#define TP_BLOCK_CURR_CONTEXT_OFFSET 48
+#define TP_BLOCK_RETURN_ADDR_OFFSET 56
+#define TP_BLOCK_RT_FLAGS_OFFSET 64
+#define TP_BLOCK_TRAP_CTX_FRAME_OFFSET 72
+#define TP_BLOCK_STARTED_OFFSET 80
+#define TP_BLOCK_INTERRUPT_MODE_SP_OFFSET 88
+#define TP_BLOCK_SECONDARY_ARG_OFFSET 96
+#define TP_BLOCK_TRAP_COUNT_OFFSET 104
+#define TP_BLOCK_BOOT_ARG_OFFSET 112
+#define TP_BLOCK_SIZE 120
+_Static_assert(sizeof(TpBlock) == TP_BLOCK_SIZE, "TpBlock layout mismatch");
+
+extern uintptr_t __my_boot_id(void);
+static inline uintptr_t my_boot_id(void) { return __my_boot_id(); }
+extern uintptr_t __my_hart_id(void);
+static inline uintptr_t my_hart_id(void) { return __my_hart_id(); }
+extern uintptr_t __my_tpblock_addr(void);
+static inline uintptr_t my_tpblock_addr(void) { return __my_tpblock_addr(); }
+extern uintptr_t __my_trap_frame_addr(void);
+static inline uintptr_t my_trap_frame_addr(void) { return __my_trap_frame_addr(); }
+extern uintptr_t __tpblock_base(void);
+static inline uintptr_t tpblock_base(void) { return __tpblock_base(); }
+extern uintptr_t __get_restore_tf_label(void);
+static inline uintptr_t get_restore_tf_label(void) { return __get_restore_tf_label(); }
+extern void __switch_to(uintptr_t ctx);
+static inline void switch_to(uintptr_t ctx) { __switch_to(ctx); }
+extern void __release_secondaries(void);
+static inline void release_secondaries(void) { __release_secondaries(); }
+extern void __boot_lock_acquire(void);
+static inline void boot_lock_acquire(void) { __boot_lock_acquire(); }
+extern void __boot_lock_release(void);
+static inline void boot_lock_release(void) { __boot_lock_release(); }
+
+#endif // RV_RUNTIME_GENERATED_H
@@ -12,6 +12,11 @@ pub struct TpBlock {
     pub return_addr: usize,
     pub rt_flags: usize,
     pub trap_ctx_frame: usize,
+    pub started: usize,
+    pub interrupt_mode_sp: usize,
+    pub secondary_arg: usize,
+    pub trap_count: usize,
+    pub boot_arg: usize,
 }
 impl TpBlock {
     #[allow(dead_code, non_snake_case)]
@@ -63,12 +68,12 @@ impl TpBlock {
         self.hart_id = val;
     }
     #[allow(dead_code, non_snake_case)]
-    pub fn get_curr_context(&self) -> usize {
-        self.curr_context
+    pub fn get_curr_context(&self) -> super::VAddr {
+        super::VAddr(self.curr_context)
     }
     #[allow(dead_code, non_snake_case)]
-    pub fn set_curr_context(&mut self, val: usize) {
-        self.curr_context = val;
+    pub fn set_curr_context(&mut self, val: super::VAddr) {
+        self.curr_context = val.0;
     }
     #[allow(dead_code, non_snake_case)]
     pub fn get_return_addr(&self) -> usize {
@@ -87,12 +92,52 @@ impl TpBlock {
         self.rt_flags = val;
     }
     #[allow(dead_code, non_snake_case)]
-    pub fn get_trap_ctx_frame(&self) -> usize {
-        self.trap_ctx_frame
+    pub fn get_trap_ctx_frame(&self) -> super::VAddr {
+        super::VAddr(self.trap_ctx_frame)
     }
     #[allow(dead_code, non_snake_case)]
-    pub fn set_trap_ctx_frame(&mut self, val: usize) {
-        self.trap_ctx_frame = val;
+    pub fn set_trap_ctx_frame(&mut self, val: super::VAddr) {
+        self.trap_ctx_frame = val.0;
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn get_started(&self) -> usize {
+        self.started
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_started(&mut self, val: usize) {
+        self.started = val;
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn get_interrupt_mode_sp(&self) -> usize {
+        self.interrupt_mode_sp
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_interrupt_mode_sp(&mut self, val: usize) {
+        self.interrupt_mode_sp = val;
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn get_secondary_arg(&self) -> usize {
+        self.secondary_arg
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_secondary_arg(&mut self, val: usize) {
+        self.secondary_arg = val;
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn get_trap_count(&self) -> usize {
+        self.trap_count
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_trap_count(&mut self, val: usize) {
+        self.trap_count = val;
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn get_boot_arg(&self) -> usize {
+        self.boot_arg
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_boot_arg(&mut self, val: usize) {
+        self.boot_arg = val;
     }
 }
 extern "C" {
@@ -131,6 +176,54 @@ pub fn my_tpblock_addr() -> usize {
         __my_tpblock_addr()
     }
 }
+#[inline(always)]
+#[allow(dead_code, non_snake_case)]
+pub fn read_tp() -> usize {
+    let tp: usize;
+    unsafe {
+        core::arch::asm!("mv {}, tp", out(reg) tp);
+    }
+    tp
+}
+#[inline(always)]
+#[allow(dead_code, non_snake_case)]
+pub fn mask_interrupt(bit: usize) -> usize {
+    let prev: usize;
+    unsafe {
+        core::arch::asm!("csrrc {}, mie, {}", out(reg) prev, in(reg) 1usize << bit);
+    }
+    (prev >> bit) & 1
+}
+#[inline(always)]
+#[allow(dead_code, non_snake_case)]
+pub fn unmask_interrupt(bit: usize) {
+    unsafe {
+        core::arch::asm!("csrs mie, {}", in(reg) 1usize << bit);
+    }
+}
+#[allow(dead_code, non_snake_case)]
+pub fn clear_software_interrupt() -> usize {
+    let msip = (0x2000000 + 4 * my_hart_id()) as *mut u32;
+    let prev = unsafe { core::ptr::read_volatile(msip) };
+    unsafe {
+        core::ptr::write_volatile(msip,0);
+    }
+    (prev & 1) as usize
+}
+#[allow(dead_code, non_snake_case)]
+pub fn set_timer(deadline: u64) {
+    let mtimecmp = (0x2004000 + 0x8 * my_hart_id()) as *mut u64;
+    unsafe {
+        core::ptr::write_volatile(mtimecmp,deadline);
+    }
+}
+#[inline(always)]
+#[allow(dead_code, non_snake_case)]
+pub fn fence_tso() {
+    unsafe {
+        core::arch::asm!("fence.tso");
+    }
+}
 extern "C" {
     fn __get_restore_tf_label() -> usize;
 }
@@ -146,6 +239,14 @@ pub fn my_tpblock_mut() -> &'static mut TpBlock {
         &mut *(__my_tpblock_addr() as *mut TpBlock)
     }
 }
+#[allow(dead_code, non_snake_case)]
+pub fn current_context() -> usize {
+    my_tpblock_mut().get_curr_context().into()
+}
+#[allow(dead_code, non_snake_case)]
+pub fn set_current_context(ctx: usize) {
+    my_tpblock_mut().set_curr_context(ctx.into());
+}
 extern "C" {
     fn __tpblock_base() -> usize;
 }
@@ -173,6 +274,60 @@ pub fn hart_to_boot_id(id: usize) -> Option<usize> {
     }
     None
 }
+#[allow(dead_code, non_snake_case)]
+pub fn hart_started(boot_id: usize) -> bool {
+    unsafe { core::ptr::read_volatile(&tp_block_slice()[boot_id].started as *const usize) != 0 }
+}
+#[allow(dead_code, non_snake_case)]
+pub fn secondary_arg() -> usize {
+    my_tpblock_mut().get_secondary_arg()
+}
+#[allow(dead_code, non_snake_case)]
+pub fn trap_count() -> usize {
+    my_tpblock_mut().get_trap_count()
+}
+#[allow(dead_code, non_snake_case)]
+pub fn per_hart_addr(base: usize, stride: usize) -> usize {
+    base + stride * my_boot_id()
+}
+/// # Safety
+///
+/// `addr` must be the address of an aligned xword that stays valid while waiting, and is
+/// only written with volatile or atomic accesses.
+#[allow(dead_code, non_snake_case)]
+pub unsafe fn wait_for_value(addr: usize, expected: usize) {
+    while unsafe { core::ptr::read_volatile(addr as *const usize) } != expected {
+        core::hint::spin_loop();
+    }
+}
+extern "C" {
+    fn __release_secondaries();
+}
+#[allow(dead_code, non_snake_case)]
+pub fn release_secondaries() {
+    core::sync::atomic::fence(core::sync::atomic::Ordering::Release);
+    unsafe {
+        __release_secondaries();
+    }
+}
+extern "C" {
+    fn __boot_lock_acquire();
+}
+#[allow(dead_code, non_snake_case)]
+pub fn boot_lock_acquire() {
+    unsafe {
+        __boot_lock_acquire();
+    }
+}
+extern "C" {
+    fn __boot_lock_release();
+}
+#[allow(dead_code, non_snake_case)]
+pub fn boot_lock_release() {
+    unsafe {
+        __boot_lock_release();
+    }
+}
 extern "C" {
     fn __switch_to(ctx: usize);
 }
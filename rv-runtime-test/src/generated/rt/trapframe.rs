@@ -2,6 +2,7 @@
 #[repr(C)]
 #[derive(Debug, Copy, Clone)]
 pub struct TrapFrame {
+    pub mepc: usize,
     pub ra: usize,
     pub sp: usize,
     pub gp: usize,
@@ -66,14 +67,57 @@ pub struct TrapFrame {
     pub f30: usize,
     pub f31: usize,
     pub mstatus: usize,
-    pub mepc: usize,
     pub mtval: usize,
     pub mcause: usize,
     pub fcsr: usize,
     pub rt_flags: usize,
     pub int_frame: usize,
+    pub vl: usize,
+    pub vtype: usize,
+    pub vcsr: usize,
+    pub vstart: usize,
+    pub v0: [u8; 16],
+    pub v1: [u8; 16],
+    pub v2: [u8; 16],
+    pub v3: [u8; 16],
+    pub v4: [u8; 16],
+    pub v5: [u8; 16],
+    pub v6: [u8; 16],
+    pub v7: [u8; 16],
+    pub v8: [u8; 16],
+    pub v9: [u8; 16],
+    pub v10: [u8; 16],
+    pub v11: [u8; 16],
+    pub v12: [u8; 16],
+    pub v13: [u8; 16],
+    pub v14: [u8; 16],
+    pub v15: [u8; 16],
+    pub v16: [u8; 16],
+    pub v17: [u8; 16],
+    pub v18: [u8; 16],
+    pub v19: [u8; 16],
+    pub v20: [u8; 16],
+    pub v21: [u8; 16],
+    pub v22: [u8; 16],
+    pub v23: [u8; 16],
+    pub v24: [u8; 16],
+    pub v25: [u8; 16],
+    pub v26: [u8; 16],
+    pub v27: [u8; 16],
+    pub v28: [u8; 16],
+    pub v29: [u8; 16],
+    pub v30: [u8; 16],
+    pub v31: [u8; 16],
 }
 impl TrapFrame {
+    #[allow(dead_code, non_snake_case)]
+    pub fn get_mepc(&self) -> super::VAddr {
+        super::VAddr(self.mepc)
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_mepc(&mut self, val: super::VAddr) {
+        self.mepc = val.0;
+    }
     #[allow(dead_code, non_snake_case)]
     pub fn get_ra(&self) -> usize {
         self.ra
@@ -83,12 +127,12 @@ impl TrapFrame {
         self.ra = val;
     }
     #[allow(dead_code, non_snake_case)]
-    pub fn get_sp(&self) -> usize {
-        self.sp
+    pub fn get_sp(&self) -> super::VAddr {
+        super::VAddr(self.sp)
     }
     #[allow(dead_code, non_snake_case)]
-    pub fn set_sp(&mut self, val: usize) {
-        self.sp = val;
+    pub fn set_sp(&mut self, val: super::VAddr) {
+        self.sp = val.0;
     }
     #[allow(dead_code, non_snake_case)]
     pub fn get_gp(&self) -> usize {
@@ -587,14 +631,6 @@ impl TrapFrame {
         self.mstatus = val;
     }
     #[allow(dead_code, non_snake_case)]
-    pub fn get_mepc(&self) -> usize {
-        self.mepc
-    }
-    #[allow(dead_code, non_snake_case)]
-    pub fn set_mepc(&mut self, val: usize) {
-        self.mepc = val;
-    }
-    #[allow(dead_code, non_snake_case)]
     pub fn get_mtval(&self) -> usize {
         self.mtval
     }
@@ -635,9 +671,42 @@ impl TrapFrame {
         self.int_frame = val;
     }
     #[allow(dead_code, non_snake_case)]
+    pub fn get_vl(&self) -> usize {
+        self.vl
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_vl(&mut self, val: usize) {
+        self.vl = val;
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn get_vtype(&self) -> usize {
+        self.vtype
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_vtype(&mut self, val: usize) {
+        self.vtype = val;
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn get_vcsr(&self) -> usize {
+        self.vcsr
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_vcsr(&mut self, val: usize) {
+        self.vcsr = val;
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn get_vstart(&self) -> usize {
+        self.vstart
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_vstart(&mut self, val: usize) {
+        self.vstart = val;
+    }
+    #[allow(dead_code, non_snake_case)]
     pub fn reset(&mut self) {
+        self.set_mepc(super::VAddr(0));
         self.set_ra(0);
-        self.set_sp(0);
+        self.set_sp(super::VAddr(0));
         self.set_gp(0);
         self.set_tp(0);
         self.set_t0(0);
@@ -700,12 +769,414 @@ impl TrapFrame {
         self.set_f30(0);
         self.set_f31(0);
         self.set_mstatus(0);
-        self.set_mepc(0);
         self.set_mtval(0);
         self.set_mcause(0);
         self.set_fcsr(0);
         self.set_rt_flags(0);
         self.set_int_frame(0);
+        self.set_vl(0);
+        self.set_vtype(0);
+        self.set_vcsr(0);
+        self.set_vstart(0);
+        self.v0 = [0; 16];
+        self.v1 = [0; 16];
+        self.v2 = [0; 16];
+        self.v3 = [0; 16];
+        self.v4 = [0; 16];
+        self.v5 = [0; 16];
+        self.v6 = [0; 16];
+        self.v7 = [0; 16];
+        self.v8 = [0; 16];
+        self.v9 = [0; 16];
+        self.v10 = [0; 16];
+        self.v11 = [0; 16];
+        self.v12 = [0; 16];
+        self.v13 = [0; 16];
+        self.v14 = [0; 16];
+        self.v15 = [0; 16];
+        self.v16 = [0; 16];
+        self.v17 = [0; 16];
+        self.v18 = [0; 16];
+        self.v19 = [0; 16];
+        self.v20 = [0; 16];
+        self.v21 = [0; 16];
+        self.v22 = [0; 16];
+        self.v23 = [0; 16];
+        self.v24 = [0; 16];
+        self.v25 = [0; 16];
+        self.v26 = [0; 16];
+        self.v27 = [0; 16];
+        self.v28 = [0; 16];
+        self.v29 = [0; 16];
+        self.v30 = [0; 16];
+        self.v31 = [0; 16];
+    }
+}
+impl TrapFrame {
+    #[allow(dead_code, non_snake_case)]
+    pub fn reg(&self, n: usize) -> Option<usize> {
+        match n {
+            1 => Some(self.ra),
+            2 => Some(self.sp),
+            3 => Some(self.gp),
+            4 => Some(self.tp),
+            5 => Some(self.t0),
+            6 => Some(self.t1),
+            7 => Some(self.t2),
+            8 => Some(self.s0),
+            9 => Some(self.s1),
+            10 => Some(self.a0),
+            11 => Some(self.a1),
+            12 => Some(self.a2),
+            13 => Some(self.a3),
+            14 => Some(self.a4),
+            15 => Some(self.a5),
+            16 => Some(self.a6),
+            17 => Some(self.a7),
+            18 => Some(self.s2),
+            19 => Some(self.s3),
+            20 => Some(self.s4),
+            21 => Some(self.s5),
+            22 => Some(self.s6),
+            23 => Some(self.s7),
+            24 => Some(self.s8),
+            25 => Some(self.s9),
+            26 => Some(self.s10),
+            27 => Some(self.s11),
+            28 => Some(self.t3),
+            29 => Some(self.t4),
+            30 => Some(self.t5),
+            31 => Some(self.t6),
+            _ => None,
+        }
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_reg(&mut self, n: usize, val: usize) {
+        match n {
+            1 => self.ra = val,
+            2 => self.sp = val,
+            3 => self.gp = val,
+            4 => self.tp = val,
+            5 => self.t0 = val,
+            6 => self.t1 = val,
+            7 => self.t2 = val,
+            8 => self.s0 = val,
+            9 => self.s1 = val,
+            10 => self.a0 = val,
+            11 => self.a1 = val,
+            12 => self.a2 = val,
+            13 => self.a3 = val,
+            14 => self.a4 = val,
+            15 => self.a5 = val,
+            16 => self.a6 = val,
+            17 => self.a7 = val,
+            18 => self.s2 = val,
+            19 => self.s3 = val,
+            20 => self.s4 = val,
+            21 => self.s5 = val,
+            22 => self.s6 = val,
+            23 => self.s7 = val,
+            24 => self.s8 = val,
+            25 => self.s9 = val,
+            26 => self.s10 = val,
+            27 => self.s11 = val,
+            28 => self.t3 = val,
+            29 => self.t4 = val,
+            30 => self.t5 = val,
+            31 => self.t6 = val,
+            _ => {},
+        }
+    }
+}
+impl TrapFrame {
+    #[allow(dead_code, non_snake_case)]
+    pub fn return_pc(&self) -> usize {
+        self.mepc
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_return_pc(&mut self, pc: usize) {
+        self.mepc = pc;
+    }
+}
+impl TrapFrame {
+    // Faulting address of an access or page fault. For instruction faults, this is the
+    // faulting instruction address, which is past the return PC if the instruction
+    // straddles a page boundary.
+    #[allow(dead_code, non_snake_case)]
+    pub fn fault_address(&self) -> usize {
+        self.mtval
+    }
+}
+impl TrapFrame {
+    #[allow(dead_code, non_snake_case)]
+    pub fn trap_from_lower_mode(&self) -> bool {
+        self.mstatus & 0x1800 != 0x1800
+    }
+}
+#[repr(usize)]
+#[derive(Debug, Copy, Clone)]
+#[allow(dead_code, non_snake_case)]
+pub enum TrapFrameField {
+    Mepc = 0x0,
+    Ra = 0x1,
+    Sp = 0x2,
+    Gp = 0x3,
+    Tp = 0x4,
+    T0 = 0x5,
+    T1 = 0x6,
+    T2 = 0x7,
+    S0 = 0x8,
+    S1 = 0x9,
+    A0 = 0xa,
+    A1 = 0xb,
+    A2 = 0xc,
+    A3 = 0xd,
+    A4 = 0xe,
+    A5 = 0xf,
+    A6 = 0x10,
+    A7 = 0x11,
+    S2 = 0x12,
+    S3 = 0x13,
+    S4 = 0x14,
+    S5 = 0x15,
+    S6 = 0x16,
+    S7 = 0x17,
+    S8 = 0x18,
+    S9 = 0x19,
+    S10 = 0x1a,
+    S11 = 0x1b,
+    T3 = 0x1c,
+    T4 = 0x1d,
+    T5 = 0x1e,
+    T6 = 0x1f,
+    F0 = 0x20,
+    F1 = 0x21,
+    F2 = 0x22,
+    F3 = 0x23,
+    F4 = 0x24,
+    F5 = 0x25,
+    F6 = 0x26,
+    F7 = 0x27,
+    F8 = 0x28,
+    F9 = 0x29,
+    F10 = 0x2a,
+    F11 = 0x2b,
+    F12 = 0x2c,
+    F13 = 0x2d,
+    F14 = 0x2e,
+    F15 = 0x2f,
+    F16 = 0x30,
+    F17 = 0x31,
+    F18 = 0x32,
+    F19 = 0x33,
+    F20 = 0x34,
+    F21 = 0x35,
+    F22 = 0x36,
+    F23 = 0x37,
+    F24 = 0x38,
+    F25 = 0x39,
+    F26 = 0x3a,
+    F27 = 0x3b,
+    F28 = 0x3c,
+    F29 = 0x3d,
+    F30 = 0x3e,
+    F31 = 0x3f,
+    Mstatus = 0x40,
+    Mtval = 0x41,
+    Mcause = 0x42,
+    Fcsr = 0x43,
+    RtFlags = 0x44,
+    IntFrame = 0x45,
+    Vl = 0x46,
+    Vtype = 0x47,
+    Vcsr = 0x48,
+    Vstart = 0x49,
+}
+impl TrapFrameField {
+    #[allow(dead_code, non_snake_case)]
+    pub fn all() -> [TrapFrameField; 74] {
+        [TrapFrameField::Mepc, TrapFrameField::Ra, TrapFrameField::Sp, TrapFrameField::Gp, TrapFrameField::Tp, TrapFrameField::T0, TrapFrameField::T1, TrapFrameField::T2, TrapFrameField::S0, TrapFrameField::S1, TrapFrameField::A0, TrapFrameField::A1, TrapFrameField::A2, TrapFrameField::A3, TrapFrameField::A4, TrapFrameField::A5, TrapFrameField::A6, TrapFrameField::A7, TrapFrameField::S2, TrapFrameField::S3, TrapFrameField::S4, TrapFrameField::S5, TrapFrameField::S6, TrapFrameField::S7, TrapFrameField::S8, TrapFrameField::S9, TrapFrameField::S10, TrapFrameField::S11, TrapFrameField::T3, TrapFrameField::T4, TrapFrameField::T5, TrapFrameField::T6, TrapFrameField::F0, TrapFrameField::F1, TrapFrameField::F2, TrapFrameField::F3, TrapFrameField::F4, TrapFrameField::F5, TrapFrameField::F6, TrapFrameField::F7, TrapFrameField::F8, TrapFrameField::F9, TrapFrameField::F10, TrapFrameField::F11, TrapFrameField::F12, TrapFrameField::F13, TrapFrameField::F14, TrapFrameField::F15, TrapFrameField::F16, TrapFrameField::F17, TrapFrameField::F18, TrapFrameField::F19, TrapFrameField::F20, TrapFrameField::F21, TrapFrameField::F22, TrapFrameField::F23, TrapFrameField::F24, TrapFrameField::F25, TrapFrameField::F26, TrapFrameField::F27, TrapFrameField::F28, TrapFrameField::F29, TrapFrameField::F30, TrapFrameField::F31, TrapFrameField::Mstatus, TrapFrameField::Mtval, TrapFrameField::Mcause, TrapFrameField::Fcsr, TrapFrameField::RtFlags, TrapFrameField::IntFrame, TrapFrameField::Vl, TrapFrameField::Vtype, TrapFrameField::Vcsr, TrapFrameField::Vstart]
+    }
+}
+impl TrapFrame {
+    #[allow(dead_code, non_snake_case)]
+    pub fn get_field(&self, f: TrapFrameField) -> usize {
+        match f {
+            TrapFrameField::Mepc => self.mepc,
+            TrapFrameField::Ra => self.ra,
+            TrapFrameField::Sp => self.sp,
+            TrapFrameField::Gp => self.gp,
+            TrapFrameField::Tp => self.tp,
+            TrapFrameField::T0 => self.t0,
+            TrapFrameField::T1 => self.t1,
+            TrapFrameField::T2 => self.t2,
+            TrapFrameField::S0 => self.s0,
+            TrapFrameField::S1 => self.s1,
+            TrapFrameField::A0 => self.a0,
+            TrapFrameField::A1 => self.a1,
+            TrapFrameField::A2 => self.a2,
+            TrapFrameField::A3 => self.a3,
+            TrapFrameField::A4 => self.a4,
+            TrapFrameField::A5 => self.a5,
+            TrapFrameField::A6 => self.a6,
+            TrapFrameField::A7 => self.a7,
+            TrapFrameField::S2 => self.s2,
+            TrapFrameField::S3 => self.s3,
+            TrapFrameField::S4 => self.s4,
+            TrapFrameField::S5 => self.s5,
+            TrapFrameField::S6 => self.s6,
+            TrapFrameField::S7 => self.s7,
+            TrapFrameField::S8 => self.s8,
+            TrapFrameField::S9 => self.s9,
+            TrapFrameField::S10 => self.s10,
+            TrapFrameField::S11 => self.s11,
+            TrapFrameField::T3 => self.t3,
+            TrapFrameField::T4 => self.t4,
+            TrapFrameField::T5 => self.t5,
+            TrapFrameField::T6 => self.t6,
+            TrapFrameField::F0 => self.f0,
+            TrapFrameField::F1 => self.f1,
+            TrapFrameField::F2 => self.f2,
+            TrapFrameField::F3 => self.f3,
+            TrapFrameField::F4 => self.f4,
+            TrapFrameField::F5 => self.f5,
+            TrapFrameField::F6 => self.f6,
+            TrapFrameField::F7 => self.f7,
+            TrapFrameField::F8 => self.f8,
+            TrapFrameField::F9 => self.f9,
+            TrapFrameField::F10 => self.f10,
+            TrapFrameField::F11 => self.f11,
+            TrapFrameField::F12 => self.f12,
+            TrapFrameField::F13 => self.f13,
+            TrapFrameField::F14 => self.f14,
+            TrapFrameField::F15 => self.f15,
+            TrapFrameField::F16 => self.f16,
+            TrapFrameField::F17 => self.f17,
+            TrapFrameField::F18 => self.f18,
+            TrapFrameField::F19 => self.f19,
+            TrapFrameField::F20 => self.f20,
+            TrapFrameField::F21 => self.f21,
+            TrapFrameField::F22 => self.f22,
+            TrapFrameField::F23 => self.f23,
+            TrapFrameField::F24 => self.f24,
+            TrapFrameField::F25 => self.f25,
+            TrapFrameField::F26 => self.f26,
+            TrapFrameField::F27 => self.f27,
+            TrapFrameField::F28 => self.f28,
+            TrapFrameField::F29 => self.f29,
+            TrapFrameField::F30 => self.f30,
+            TrapFrameField::F31 => self.f31,
+            TrapFrameField::Mstatus => self.mstatus,
+            TrapFrameField::Mtval => self.mtval,
+            TrapFrameField::Mcause => self.mcause,
+            TrapFrameField::Fcsr => self.fcsr,
+            TrapFrameField::RtFlags => self.rt_flags,
+            TrapFrameField::IntFrame => self.int_frame,
+            TrapFrameField::Vl => self.vl,
+            TrapFrameField::Vtype => self.vtype,
+            TrapFrameField::Vcsr => self.vcsr,
+            TrapFrameField::Vstart => self.vstart,
+        }
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub fn set_field(&mut self, f: TrapFrameField, val: usize) {
+        match f {
+            TrapFrameField::Mepc => self.mepc = val,
+            TrapFrameField::Ra => self.ra = val,
+            TrapFrameField::Sp => self.sp = val,
+            TrapFrameField::Gp => self.gp = val,
+            TrapFrameField::Tp => self.tp = val,
+            TrapFrameField::T0 => self.t0 = val,
+            TrapFrameField::T1 => self.t1 = val,
+            TrapFrameField::T2 => self.t2 = val,
+            TrapFrameField::S0 => self.s0 = val,
+            TrapFrameField::S1 => self.s1 = val,
+            TrapFrameField::A0 => self.a0 = val,
+            TrapFrameField::A1 => self.a1 = val,
+            TrapFrameField::A2 => self.a2 = val,
+            TrapFrameField::A3 => self.a3 = val,
+            TrapFrameField::A4 => self.a4 = val,
+            TrapFrameField::A5 => self.a5 = val,
+            TrapFrameField::A6 => self.a6 = val,
+            TrapFrameField::A7 => self.a7 = val,
+            TrapFrameField::S2 => self.s2 = val,
+            TrapFrameField::S3 => self.s3 = val,
+            TrapFrameField::S4 => self.s4 = val,
+            TrapFrameField::S5 => self.s5 = val,
+            TrapFrameField::S6 => self.s6 = val,
+            TrapFrameField::S7 => self.s7 = val,
+            TrapFrameField::S8 => self.s8 = val,
+            TrapFrameField::S9 => self.s9 = val,
+            TrapFrameField::S10 => self.s10 = val,
+            TrapFrameField::S11 => self.s11 = val,
+            TrapFrameField::T3 => self.t3 = val,
+            TrapFrameField::T4 => self.t4 = val,
+            TrapFrameField::T5 => self.t5 = val,
+            TrapFrameField::T6 => self.t6 = val,
+            TrapFrameField::F0 => self.f0 = val,
+            TrapFrameField::F1 => self.f1 = val,
+            TrapFrameField::F2 => self.f2 = val,
+            TrapFrameField::F3 => self.f3 = val,
+            TrapFrameField::F4 => self.f4 = val,
+            TrapFrameField::F5 => self.f5 = val,
+            TrapFrameField::F6 => self.f6 = val,
+            TrapFrameField::F7 => self.f7 = val,
+            TrapFrameField::F8 => self.f8 = val,
+            TrapFrameField::F9 => self.f9 = val,
+            TrapFrameField::F10 => self.f10 = val,
+            TrapFrameField::F11 => self.f11 = val,
+            TrapFrameField::F12 => self.f12 = val,
+            TrapFrameField::F13 => self.f13 = val,
+            TrapFrameField::F14 => self.f14 = val,
+            TrapFrameField::F15 => self.f15 = val,
+            TrapFrameField::F16 => self.f16 = val,
+            TrapFrameField::F17 => self.f17 = val,
+            TrapFrameField::F18 => self.f18 = val,
+            TrapFrameField::F19 => self.f19 = val,
+            TrapFrameField::F20 => self.f20 = val,
+            TrapFrameField::F21 => self.f21 = val,
+            TrapFrameField::F22 => self.f22 = val,
+            TrapFrameField::F23 => self.f23 = val,
+            TrapFrameField::F24 => self.f24 = val,
+            TrapFrameField::F25 => self.f25 = val,
+            TrapFrameField::F26 => self.f26 = val,
+            TrapFrameField::F27 => self.f27 = val,
+            TrapFrameField::F28 => self.f28 = val,
+            TrapFrameField::F29 => self.f29 = val,
+            TrapFrameField::F30 => self.f30 = val,
+            TrapFrameField::F31 => self.f31 = val,
+            TrapFrameField::Mstatus => self.mstatus = val,
+            TrapFrameField::Mtval => self.mtval = val,
+            TrapFrameField::Mcause => self.mcause = val,
+            TrapFrameField::Fcsr => self.fcsr = val,
+            TrapFrameField::RtFlags => self.rt_flags = val,
+            TrapFrameField::IntFrame => self.int_frame = val,
+            TrapFrameField::Vl => self.vl = val,
+            TrapFrameField::Vtype => self.vtype = val,
+            TrapFrameField::Vcsr => self.vcsr = val,
+            TrapFrameField::Vstart => self.vstart = val,
+        }
+    }
+}
+#[derive(Debug, Copy, Clone)]
+#[allow(dead_code, non_snake_case)]
+pub enum SavedCsr {
+    Status,
+    Epc,
+    Tval,
+    Cause,
+    Fcsr,
+}
+impl TrapFrame {
+    #[allow(dead_code, non_snake_case)]
+    pub fn csr(&self, c: SavedCsr) -> Option<usize> {
+        match c {
+            SavedCsr::Status => Some(self.mstatus),
+            SavedCsr::Epc => Some(self.mepc),
+            SavedCsr::Tval => Some(self.mtval),
+            SavedCsr::Cause => Some(self.mcause),
+            SavedCsr::Fcsr => Some(self.fcsr),
+        }
     }
 }
 #[allow(dead_code, non_snake_case)]
@@ -714,6 +1185,150 @@ pub fn trapframe() -> &'static mut TrapFrame {
         &mut *(super::my_trap_frame_addr() as *mut TrapFrame)
     }
 }
+/// Trap frame of the context `ctx` suspended by switch_to(), None if it is running.
+///
+/// # Safety
+///
+/// `ctx` must point to a thread context, and no other reference to the trap frame of the
+/// suspended context may be live while the returned one is used.
+#[allow(dead_code, non_snake_case)]
+pub unsafe fn context_trap_frame(ctx: usize) -> Option<&'static mut TrapFrame> {
+    unsafe {
+        (*(ctx as *const usize) as *mut TrapFrame).as_mut()
+    }
+}
+extern "C" {
+    fn __resume_frame(tf: usize) -> !;
+}
+/// # Safety
+///
+/// `tf` must hold a complete register state to return to, with an epc, status and
+/// stack that are valid to resume. Any interrupted trap frame it links to must still
+/// be live.
+#[allow(dead_code, non_snake_case)]
+pub unsafe fn resume_frame(tf: &mut TrapFrame) -> ! {
+    unsafe {
+        __resume_frame(tf as *mut TrapFrame as usize)
+    }
+}
+#[allow(dead_code)]
+pub mod csr_addr {
+    pub const MSTATUS: usize = 0x300;
+    pub const MEPC: usize = 0x341;
+    pub const MTVAL: usize = 0x343;
+    pub const MCAUSE: usize = 0x342;
+    pub const FCSR: usize = 0x3;
+}
+#[allow(dead_code)]
+pub mod trapframe_layout {
+    pub const MEPC_OFFSET: usize = 0;
+    pub const RA_OFFSET: usize = 8;
+    pub const SP_OFFSET: usize = 16;
+    pub const GP_OFFSET: usize = 24;
+    pub const TP_OFFSET: usize = 32;
+    pub const T0_OFFSET: usize = 40;
+    pub const T1_OFFSET: usize = 48;
+    pub const T2_OFFSET: usize = 56;
+    pub const S0_OFFSET: usize = 64;
+    pub const S1_OFFSET: usize = 72;
+    pub const A0_OFFSET: usize = 80;
+    pub const A1_OFFSET: usize = 88;
+    pub const A2_OFFSET: usize = 96;
+    pub const A3_OFFSET: usize = 104;
+    pub const A4_OFFSET: usize = 112;
+    pub const A5_OFFSET: usize = 120;
+    pub const A6_OFFSET: usize = 128;
+    pub const A7_OFFSET: usize = 136;
+    pub const S2_OFFSET: usize = 144;
+    pub const S3_OFFSET: usize = 152;
+    pub const S4_OFFSET: usize = 160;
+    pub const S5_OFFSET: usize = 168;
+    pub const S6_OFFSET: usize = 176;
+    pub const S7_OFFSET: usize = 184;
+    pub const S8_OFFSET: usize = 192;
+    pub const S9_OFFSET: usize = 200;
+    pub const S10_OFFSET: usize = 208;
+    pub const S11_OFFSET: usize = 216;
+    pub const T3_OFFSET: usize = 224;
+    pub const T4_OFFSET: usize = 232;
+    pub const T5_OFFSET: usize = 240;
+    pub const T6_OFFSET: usize = 248;
+    pub const F0_OFFSET: usize = 256;
+    pub const F1_OFFSET: usize = 264;
+    pub const F2_OFFSET: usize = 272;
+    pub const F3_OFFSET: usize = 280;
+    pub const F4_OFFSET: usize = 288;
+    pub const F5_OFFSET: usize = 296;
+    pub const F6_OFFSET: usize = 304;
+    pub const F7_OFFSET: usize = 312;
+    pub const F8_OFFSET: usize = 320;
+    pub const F9_OFFSET: usize = 328;
+    pub const F10_OFFSET: usize = 336;
+    pub const F11_OFFSET: usize = 344;
+    pub const F12_OFFSET: usize = 352;
+    pub const F13_OFFSET: usize = 360;
+    pub const F14_OFFSET: usize = 368;
+    pub const F15_OFFSET: usize = 376;
+    pub const F16_OFFSET: usize = 384;
+    pub const F17_OFFSET: usize = 392;
+    pub const F18_OFFSET: usize = 400;
+    pub const F19_OFFSET: usize = 408;
+    pub const F20_OFFSET: usize = 416;
+    pub const F21_OFFSET: usize = 424;
+    pub const F22_OFFSET: usize = 432;
+    pub const F23_OFFSET: usize = 440;
+    pub const F24_OFFSET: usize = 448;
+    pub const F25_OFFSET: usize = 456;
+    pub const F26_OFFSET: usize = 464;
+    pub const F27_OFFSET: usize = 472;
+    pub const F28_OFFSET: usize = 480;
+    pub const F29_OFFSET: usize = 488;
+    pub const F30_OFFSET: usize = 496;
+    pub const F31_OFFSET: usize = 504;
+    pub const MSTATUS_OFFSET: usize = 512;
+    pub const MTVAL_OFFSET: usize = 520;
+    pub const MCAUSE_OFFSET: usize = 528;
+    pub const FCSR_OFFSET: usize = 536;
+    pub const RT_FLAGS_OFFSET: usize = 544;
+    pub const INT_FRAME_OFFSET: usize = 552;
+    pub const VL_OFFSET: usize = 560;
+    pub const VTYPE_OFFSET: usize = 568;
+    pub const VCSR_OFFSET: usize = 576;
+    pub const VSTART_OFFSET: usize = 584;
+    pub const V0_OFFSET: usize = 592;
+    pub const V1_OFFSET: usize = 608;
+    pub const V2_OFFSET: usize = 624;
+    pub const V3_OFFSET: usize = 640;
+    pub const V4_OFFSET: usize = 656;
+    pub const V5_OFFSET: usize = 672;
+    pub const V6_OFFSET: usize = 688;
+    pub const V7_OFFSET: usize = 704;
+    pub const V8_OFFSET: usize = 720;
+    pub const V9_OFFSET: usize = 736;
+    pub const V10_OFFSET: usize = 752;
+    pub const V11_OFFSET: usize = 768;
+    pub const V12_OFFSET: usize = 784;
+    pub const V13_OFFSET: usize = 800;
+    pub const V14_OFFSET: usize = 816;
+    pub const V15_OFFSET: usize = 832;
+    pub const V16_OFFSET: usize = 848;
+    pub const V17_OFFSET: usize = 864;
+    pub const V18_OFFSET: usize = 880;
+    pub const V19_OFFSET: usize = 896;
+    pub const V20_OFFSET: usize = 912;
+    pub const V21_OFFSET: usize = 928;
+    pub const V22_OFFSET: usize = 944;
+    pub const V23_OFFSET: usize = 960;
+    pub const V24_OFFSET: usize = 976;
+    pub const V25_OFFSET: usize = 992;
+    pub const V26_OFFSET: usize = 1008;
+    pub const V27_OFFSET: usize = 1024;
+    pub const V28_OFFSET: usize = 1040;
+    pub const V29_OFFSET: usize = 1056;
+    pub const V30_OFFSET: usize = 1072;
+    pub const V31_OFFSET: usize = 1088;
+    pub const TRAP_FRAME_SIZE: usize = 1104;
+}
 #[repr(u32)]
 #[derive(Debug, Copy, Clone)]
 #[allow(dead_code, non_snake_case)]
@@ -721,4 +1336,19 @@ pub enum RtFlags {
     RestoreTrapFrameInTpBlock = 0x1,
     FsStateWasDirty = 0x2,
     TranslationRegChanged = 0x4,
+    VsStateWasDirty = 0x8,
+}
+impl RtFlags {
+    #[allow(dead_code, non_snake_case)]
+    pub const fn contains(bits: u32, flag: RtFlags) -> bool {
+        bits & flag as u32 != 0
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub const fn set(bits: u32, flag: RtFlags) -> u32 {
+        bits | flag as u32
+    }
+    #[allow(dead_code, non_snake_case)]
+    pub const fn clear(bits: u32, flag: RtFlags) -> u32 {
+        bits & !(flag as u32)
+    }
 }
@@ -45,6 +45,11 @@ impl core::fmt::Write for QemuUart {
     }
 }
 
+// Write directly to the UART, without relying on any static. Usable before BSS is cleared.
+pub fn early_puts(s: &str) {
+    let _ = QemuUart::new().write_str(s);
+}
+
 pub fn _print(args: core::fmt::Arguments) {
     // Explicitly ignore errors here.
     let _ = UART.lock().write_fmt(args);
@@ -65,15 +65,87 @@ fn sbicall(eid: usize, fid: usize) -> (usize, usize) {
     (error, value)
 }
 
+#[no_mangle]
+pub extern "C" fn early_console() {
+    io::early_puts("Early console up\n");
+}
+
+// Stored in the ROM and run from region_3 once copied
+#[link_section = ".reloc_blob"]
+static RELOC_BLOB: [u8; 16] = *b"copied from rom!";
+
+// Cleared by the first hart of group 1 before any of its harts enters Rust
+#[link_section = ".group1_zeroed"]
+static mut GROUP1_BSS: usize = 0x5a5a_5a5a;
+
 const ONE_POINT_ZERO_AS_INT: u64 = 0x3f800000;
 const TWO_POINT_ZERO_AS_INT: u64 = 0x40000000;
 
+// The runtime passes boot id, hart id and DTB pointer to both entrypoints
+const _: EntrypointFn = main;
+const _: EntrypointFn = secondary_main;
+
 #[no_mangle]
-pub extern "C" fn main() {
+pub extern "C" fn main(boot_id: usize, hart_id: usize, dtb: usize) {
+    // Only the boot hart runs this, before anything in the relocated sections is used
+    unsafe { copy_data_to_ram() };
     logger_init();
+    print_boot_banner(io::early_puts);
+    // Secondaries only start once the logger is set up
+    release_secondaries();
+    assert_eq!(boot_id, my_boot_id());
+    assert_eq!(hart_id, my_hart_id());
+    log::info!("dtb: {:#x?}", dtb);
 
     let trap_frame = trapframe();
     log::info!("Hello World from bare-metal start(boot hart)!",);
+    assert!(hart_started(my_boot_id()));
+    assert_eq!(my_stack().1, STACK_SIZE);
+    assert_eq!(xlen_bytes(), core::mem::size_of::<usize>());
+    assert_eq!(
+        trapframe_layout::MEPC_OFFSET,
+        core::mem::offset_of!(TrapFrame, mepc)
+    );
+    assert_eq!(
+        trapframe_layout::MSTATUS_OFFSET,
+        core::mem::offset_of!(TrapFrame, mstatus)
+    );
+    assert_eq!(
+        trapframe_layout::RT_FLAGS_OFFSET,
+        core::mem::offset_of!(TrapFrame, rt_flags)
+    );
+    assert_eq!(
+        trapframe_layout::TRAP_FRAME_SIZE,
+        core::mem::size_of::<TrapFrame>()
+    );
+    assert!(stack_remaining() > 0 && stack_remaining() < STACK_SIZE);
+    assert_eq!(my_interrupt_stack().1, 4096);
+    assert!(!on_trap_stack());
+    assert!(
+        heap_region_end() <= dma_heap_region_start()
+            || dma_heap_region_end() <= heap_region_start()
+    );
+    assert_eq!(dma_heap_region_size(), 4096);
+    assert_eq!(provenance_region_size(), b"rv-runtime-test".len());
+    assert_ne!(reloc_data_load_start(), reloc_data_region_start());
+    assert_eq!(
+        unsafe { core::ptr::read_volatile(&RELOC_BLOB) },
+        *b"copied from rom!"
+    );
+    assert!(lock_text_rodata().iter().all(|&(_, len)| len != 0));
+    fence_tso();
+    assert_eq!(clear_software_interrupt(), 0);
+    // Keep the timer interrupt disarmed
+    set_timer(u64::MAX);
+    let flags = RtFlags::set(
+        RtFlags::set(0, RtFlags::FsStateWasDirty),
+        RtFlags::VsStateWasDirty,
+    );
+    assert!(RtFlags::contains(flags, RtFlags::FsStateWasDirty));
+    assert!(!RtFlags::contains(
+        RtFlags::clear(flags, RtFlags::FsStateWasDirty),
+        RtFlags::FsStateWasDirty
+    ));
 
     // Write 1.0 into f0 and f31
     let one_point_zero = ONE_POINT_ZERO_AS_INT;
@@ -104,8 +176,10 @@ pub extern "C" fn main() {
     assert_eq!(f31, ONE_POINT_ZERO_AS_INT);
 
     log::info!("rt_flags in trapframe: {:#x?}", trap_frame.get_rt_flags());
+    assert_eq!(trap_count(), 0);
     sbicall(0, 0);
     log::info!("back from sbi call");
+    assert_eq!(trap_count(), 1);
 
     // Ensure f0/31 is still the same value
     let f0: u64;
@@ -142,10 +216,24 @@ pub extern "C" fn test_main_mret() -> ! {
 }
 
 #[no_mangle]
-pub extern "C" fn secondary_main() {
+pub extern "C" fn secondary_main(boot_id: usize, hart_id: usize, dtb: usize) {
     logger_init();
 
+    // Keep the bring-up messages of each secondary together
+    boot_lock_acquire();
     log::info!("Hello World from bare-metal start(secondary)!",);
+    log::info!("secondary arg: {:#x?}", secondary_arg());
+    boot_lock_release();
+    assert_eq!(boot_id, my_boot_id());
+    assert_eq!(hart_id, my_hart_id());
+    if hart_id >> 1 == 1 {
+        assert_eq!(
+            unsafe { core::ptr::read_volatile(core::ptr::addr_of!(GROUP1_BSS)) },
+            0
+        );
+    }
+    // Secondaries receive the DTB pointer in a1, which is also the secondary argument
+    assert_eq!(dtb, secondary_arg());
 }
 
 #[no_mangle]
@@ -153,14 +241,28 @@ pub extern "C" fn trap_enter() {
     let trap_frame = trapframe();
 
     log::info!("Hello World from trap!");
+    // handle_trap switches to the interrupt stack unless the trap was taken on it
+    assert!(on_trap_stack());
+    assert!(stack_remaining() > 0 && stack_remaining() < my_interrupt_stack().1);
+    // The test ecall is made from main, which runs in M-mode
+    assert!(!trap_frame.trap_from_lower_mode());
+    // ecall from M-mode
+    assert_eq!(trap_frame.csr(SavedCsr::Cause), Some(11));
+    // ecall doesn't report a faulting address
+    assert_eq!(trap_frame.fault_address(), 0);
     log::info!("rt_flags in trapframe: {:#x?}", trap_frame.get_rt_flags());
     log::info!("f0 in trapframe : {:#x?}", trap_frame.get_f0());
     log::info!("f31 in trapframe: {:#x?}", trap_frame.get_f31());
     assert_eq!(trap_frame.get_f0(), ONE_POINT_ZERO_AS_INT as usize);
     assert_eq!(trap_frame.get_f31(), ONE_POINT_ZERO_AS_INT as usize);
 
-    let mepc = trap_frame.get_mepc();
-    trap_frame.set_mepc(mepc + 4);
+    let return_pc = trap_frame.return_pc();
+    trap_frame.set_return_pc(return_pc + 4);
+    assert_eq!(trap_frame.get_field(TrapFrameField::Mepc), return_pc + 4);
+    assert_eq!(trap_frame.get_mepc(), VAddr(return_pc + 4));
+    assert!(TrapFrameField::all()
+        .iter()
+        .any(|f| trap_frame.get_field(*f) == trap_frame.get_f0()));
 
     // Write a different value into f0/31. This allows us to verify that the restore path
     // is correctly restoring at least f0/31.
@@ -194,6 +296,12 @@ pub extern "C" fn trap_enter() {
     assert_eq!(f31, TWO_POINT_ZERO_AS_INT);
 }
 
+// Timer interrupts are dispatched here rather than to trap_enter, but the timer is kept disarmed
+#[no_mangle]
+pub extern "C" fn timer_enter() {
+    panic!("Unexpected timer interrupt");
+}
+
 /// Entry point for handling stack overflow
 #[no_mangle]
 pub extern "C" fn handle_stack_overflow(expected_val: usize, stack_bottom_val: usize) {